@@ -0,0 +1,191 @@
+use crate::transcription::types::{CaptureFormat, SupportedCaptureConfig};
+
+/// The capture config picked for a requested format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChosenConfig {
+    /// Index into the supported configs it was chosen from.
+    pub index: usize,
+    pub sample_rate: u32,
+    pub buffer_size: Option<u32>,
+}
+
+/// Pick the supported config that satisfies `format`, preferring the
+/// default channel count. The sample rate defaults to `default_rate`.
+/// Errors name the device and what it does support.
+pub fn choose_capture_config(
+    device: &str,
+    supported: &[SupportedCaptureConfig],
+    default_channels: u16,
+    default_rate: u32,
+    format: &CaptureFormat,
+) -> Result<ChosenConfig, String> {
+    if format.buffer_size == Some(0) {
+        return Err("Buffer size must be at least one frame".to_string());
+    }
+    let sample_rate = format.sample_rate.unwrap_or(default_rate);
+    let mut with_rate: Vec<usize> = (0..supported.len())
+        .filter(|&i| {
+            (supported[i].min_sample_rate..=supported[i].max_sample_rate).contains(&sample_rate)
+        })
+        .collect();
+    if with_rate.is_empty() {
+        return Err(format!(
+            "'{}' does not support {} Hz (supported: {})",
+            device,
+            sample_rate,
+            describe(supported, rate_range)
+        ));
+    }
+    // Stable sort keeps the host's order among equally good configs
+    with_rate.sort_by_key(|&i| supported[i].channels != default_channels);
+
+    let Some(frames) = format.buffer_size else {
+        return Ok(ChosenConfig {
+            index: with_rate[0],
+            sample_rate,
+            buffer_size: None,
+        });
+    };
+    // A host that does not report buffer sizes gets to decide at stream start
+    let fits = |c: &SupportedCaptureConfig| match (c.min_buffer_size, c.max_buffer_size) {
+        (Some(min), Some(max)) => (min..=max).contains(&frames),
+        _ => true,
+    };
+    with_rate
+        .iter()
+        .copied()
+        .find(|&i| fits(&supported[i]))
+        .map(|index| ChosenConfig {
+            index,
+            sample_rate,
+            buffer_size: Some(frames),
+        })
+        .ok_or_else(|| {
+            let configs: Vec<_> = with_rate.iter().map(|&i| supported[i].clone()).collect();
+            format!(
+                "'{}' does not support a {}-frame buffer at {} Hz (supported: {})",
+                device,
+                frames,
+                sample_rate,
+                describe(&configs, buffer_range)
+            )
+        })
+}
+
+fn rate_range(config: &SupportedCaptureConfig) -> String {
+    if config.min_sample_rate == config.max_sample_rate {
+        format!("{} Hz", config.min_sample_rate)
+    } else {
+        format!("{}-{} Hz", config.min_sample_rate, config.max_sample_rate)
+    }
+}
+
+fn buffer_range(config: &SupportedCaptureConfig) -> String {
+    match (config.min_buffer_size, config.max_buffer_size) {
+        (Some(min), Some(max)) => format!("{}-{} frames", min, max),
+        _ => "any buffer size".to_string(),
+    }
+}
+
+/// List distinct descriptions of the configs, or say there are none.
+fn describe(
+    configs: &[SupportedCaptureConfig],
+    part: impl Fn(&SupportedCaptureConfig) -> String,
+) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for config in configs {
+        let text = part(config);
+        if !parts.contains(&text) {
+            parts.push(text);
+        }
+    }
+    if parts.is_empty() {
+        "no usable configs".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(
+        channels: u16,
+        rates: (u32, u32),
+        buffer: Option<(u32, u32)>,
+    ) -> SupportedCaptureConfig {
+        SupportedCaptureConfig {
+            channels,
+            min_sample_rate: rates.0,
+            max_sample_rate: rates.1,
+            min_buffer_size: buffer.map(|b| b.0),
+            max_buffer_size: buffer.map(|b| b.1),
+            sample_format: "f32".to_string(),
+        }
+    }
+
+    fn request(sample_rate: Option<u32>, buffer_size: Option<u32>) -> CaptureFormat {
+        CaptureFormat {
+            host: None,
+            sample_rate,
+            buffer_size,
+        }
+    }
+
+    #[test]
+    fn test_prefers_default_channels_at_requested_rate() {
+        let supported = [
+            config(1, (8000, 48000), None),
+            config(2, (44100, 96000), None),
+        ];
+        let chosen =
+            choose_capture_config("USB", &supported, 2, 48000, &request(Some(16000), None))
+                .unwrap();
+        assert_eq!(chosen.index, 0);
+        assert_eq!(chosen.sample_rate, 16000);
+
+        let chosen =
+            choose_capture_config("USB", &supported, 2, 48000, &request(None, None)).unwrap();
+        assert_eq!((chosen.index, chosen.sample_rate), (1, 48000));
+    }
+
+    #[test]
+    fn test_unsupported_rate_lists_supported_rates() {
+        let supported = [
+            config(2, (44100, 48000), None),
+            config(2, (96000, 96000), None),
+        ];
+        let err = choose_capture_config("USB", &supported, 2, 48000, &request(Some(22050), None))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "'USB' does not support 22050 Hz (supported: 44100-48000 Hz, 96000 Hz)"
+        );
+    }
+
+    #[test]
+    fn test_buffer_size_must_fit_host_range() {
+        let supported = [
+            config(2, (48000, 48000), Some((256, 4096))),
+            config(1, (48000, 48000), Some((64, 4096))),
+        ];
+        // Only the mono config takes a 128-frame buffer
+        let chosen =
+            choose_capture_config("USB", &supported, 2, 48000, &request(None, Some(128))).unwrap();
+        assert_eq!(chosen.index, 1);
+        assert_eq!(chosen.buffer_size, Some(128));
+
+        let err = choose_capture_config("USB", &supported, 2, 48000, &request(None, Some(32)))
+            .unwrap_err();
+        assert!(err.contains("32-frame buffer"), "{}", err);
+        assert!(err.contains("256-4096 frames, 64-4096 frames"), "{}", err);
+        assert!(
+            choose_capture_config("USB", &supported, 2, 48000, &request(None, Some(0))).is_err()
+        );
+
+        // Hosts that report no range accept any size
+        let unknown = [config(2, (48000, 48000), None)];
+        assert!(choose_capture_config("USB", &unknown, 2, 48000, &request(None, Some(32))).is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::audio::mic_capture::AudioChunk;
+use crate::transcription::types::ChannelMap;
+
+/// Apply a source's channel map to a captured chunk. `Mix` keeps the chunk
+/// as is, since the resampler averages all channels. Returns `None` when
+/// the capture has none of the selected channels.
+///
+/// A `Split` map never reaches a pipeline: each of its outputs runs as its
+/// own source selecting one channel (see `SourceConfig::routed_sources`).
+pub fn apply_channel_map(chunk: AudioChunk, map: &ChannelMap) -> Option<AudioChunk> {
+    match map {
+        ChannelMap::Mix | ChannelMap::Split { .. } => Some(chunk),
+        ChannelMap::Select { channel } => average_channels(&chunk, &[*channel]),
+        ChannelMap::Average { channels } => average_channels(&chunk, channels),
+    }
+}
+
+/// Average some channels of an interleaved chunk into a mono chunk,
+/// skipping channels the chunk does not have.
+pub fn average_channels(chunk: &AudioChunk, channels: &[u16]) -> Option<AudioChunk> {
+    let width = chunk.channels.max(1) as usize;
+    let mut present: Vec<usize> = channels
+        .iter()
+        .map(|&c| c as usize)
+        .filter(|&c| c < width)
+        .collect();
+    present.sort_unstable();
+    present.dedup();
+    if present.is_empty() {
+        return None;
+    }
+
+    let samples = chunk
+        .samples
+        .chunks_exact(width)
+        .map(|frame| present.iter().map(|&c| frame[c]).sum::<f32>() / present.len() as f32)
+        .collect();
+    Some(AudioChunk {
+        samples,
+        sample_rate: chunk.sample_rate,
+        channels: 1,
+        captured_at: chunk.captured_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::types::{AudioSource, ChannelOutput, SourceConfig};
+    use std::time::Instant;
+
+    /// Two frames of a 4-channel interface.
+    fn four_channels() -> AudioChunk {
+        AudioChunk {
+            samples: vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8],
+            sample_rate: 48000,
+            channels: 4,
+            captured_at: Instant::now(),
+        }
+    }
+
+    fn assert_samples(chunk: &AudioChunk, expected: &[f32]) {
+        assert_eq!(chunk.channels, 1);
+        assert_eq!(chunk.samples.len(), expected.len());
+        for (a, b) in chunk.samples.iter().zip(expected) {
+            assert!(
+                (a - b).abs() < 1e-6,
+                "{:?} != {:?}",
+                chunk.samples,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_one_channel() {
+        let map = ChannelMap::Select { channel: 1 };
+        let chunk = apply_channel_map(four_channels(), &map).unwrap();
+        assert_samples(&chunk, &[0.2, 0.6]);
+    }
+
+    #[test]
+    fn test_average_subset_ignores_missing_channels() {
+        let map = ChannelMap::Average {
+            channels: vec![2, 0, 2, 9],
+        };
+        let chunk = apply_channel_map(four_channels(), &map).unwrap();
+        assert_samples(&chunk, &[0.2, 0.6]);
+
+        let missing = ChannelMap::Select { channel: 4 };
+        assert!(apply_channel_map(four_channels(), &missing).is_none());
+    }
+
+    #[test]
+    fn test_mix_keeps_all_channels() {
+        let chunk = apply_channel_map(four_channels(), &ChannelMap::Mix).unwrap();
+        assert_eq!(chunk.channels, 4);
+        assert_eq!(chunk.samples.len(), 8);
+    }
+
+    #[test]
+    fn test_split_routes_channels_to_sources() {
+        let mut config =
+            SourceConfig::default_sources(Some("usb".to_string()), None, Vec::new()).remove(0);
+        config.language = Some("de".to_string());
+        config.channel_map = ChannelMap::Split {
+            outputs: vec![
+                ChannelOutput {
+                    channel: 0,
+                    id: AudioSource::from("host"),
+                    label: "Host".to_string(),
+                },
+                ChannelOutput {
+                    channel: 1,
+                    id: AudioSource::from("guest"),
+                    label: "Guest".to_string(),
+                },
+            ],
+        };
+
+        let routed = config.routed_sources();
+        assert_eq!(routed.len(), 2);
+        assert_eq!(routed[1].id, AudioSource::from("guest"));
+        assert_eq!(routed[1].device_id.as_deref(), Some("usb"));
+        assert_eq!(routed[1].language.as_deref(), Some("de"));
+        let guest = apply_channel_map(four_channels(), &routed[1].channel_map).unwrap();
+        assert_samples(&guest, &[0.2, 0.6]);
+
+        // Any other map feeds just the source itself
+        config.channel_map = ChannelMap::Select { channel: 0 };
+        assert_eq!(config.routed_sources().len(), 1);
+    }
+}
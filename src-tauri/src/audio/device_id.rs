@@ -0,0 +1,390 @@
+//! Stable device identifiers.
+//!
+//! Display names are not unique (two identical USB mics) and differ between
+//! hosts, so every backend reports IDs of the form `<backend>:<key>`, where
+//! the key is whatever that backend considers stable:
+//!
+//! | backend     | key                                      |
+//! |-------------|------------------------------------------|
+//! | `alsa`      | ALSA PCM string, e.g. `hw:CARD=Mic,DEV=0` |
+//! | `jack`      | JACK port/client name                     |
+//! | `wasapi`    | MMDevice endpoint ID                      |
+//! | `coreaudio` | CoreAudio device UID                      |
+//! | `pulse`     | PulseAudio source name                    |
+//! | `pipewire`  | PipeWire `node.name`                      |
+//!
+//! IDs without a known prefix come from settings saved before this scheme
+//! and are resolved by display name.
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{Device, Host};
+use std::collections::HashSet;
+
+pub const ALSA: &str = "alsa";
+pub const JACK: &str = "jack";
+pub const WASAPI: &str = "wasapi";
+pub const COREAUDIO: &str = "coreaudio";
+pub const PULSE: &str = "pulse";
+pub const PIPEWIRE: &str = "pipewire";
+
+const BACKENDS: &[&str] = &[ALSA, JACK, WASAPI, COREAUDIO, PULSE, PIPEWIRE];
+
+pub fn make_id(backend: &str, key: &str) -> String {
+    format!("{}:{}", backend, key)
+}
+
+/// Split a stable ID into `(backend, key)`. Returns `None` for legacy,
+/// name-based IDs.
+pub fn split_id(id: &str) -> Option<(&str, &str)> {
+    let (backend, key) = id.split_once(':')?;
+    BACKENDS.contains(&backend).then_some((backend, key))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+/// Pair each enumerated device name with a stable key.
+///
+/// `native` lists `(display name, native key)` for the same devices as
+/// reported by the OS API, in that API's order. The n-th device named X is
+/// matched with the n-th native entry named X. Devices without a native
+/// entry fall back to their name, which duplicates share: numbering them
+/// would depend on enumeration order, so the IDs would not be stable.
+fn assign_keys(names: &[String], native: &[(String, String)]) -> Vec<String> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let occurrence = names[..i].iter().filter(|n| *n == name).count();
+            native
+                .iter()
+                .filter(|(n, _)| n == name)
+                .nth(occurrence)
+                .map(|(_, key)| key.clone())
+                .unwrap_or_else(|| name.clone())
+        })
+        .collect()
+}
+
+/// Find the index of `wanted` among `ids`, falling back to a display name
+/// match for legacy IDs.
+fn resolve_index(ids: &[String], names: &[String], wanted: &str) -> Option<usize> {
+    if let Some(i) = ids.iter().position(|id| id == wanted) {
+        return Some(i);
+    }
+    if split_id(wanted).is_none() {
+        return names.iter().position(|n| n == wanted);
+    }
+    None
+}
+
+fn host_backend(host: &Host) -> String {
+    host.id().name().to_lowercase()
+}
+
+/// Enumerate a host's devices together with their stable IDs and names.
+/// Devices that can't be told apart are listed once.
+pub fn cpal_devices(
+    host: &Host,
+    direction: Direction,
+) -> Result<Vec<(Device, String, String)>, String> {
+    let devices: Vec<Device> = match direction {
+        Direction::Input => host.input_devices(),
+        Direction::Output => host.output_devices(),
+    }
+    .map_err(|e| format!("Failed to get devices: {}", e))?
+    .collect();
+
+    let names: Vec<String> = devices
+        .iter()
+        .map(|d| d.name().unwrap_or_default())
+        .collect();
+    let backend = host_backend(host);
+    let native = native_keys(&backend, direction);
+    let keys = assign_keys(&names, &native);
+
+    let mut seen = HashSet::new();
+    Ok(devices
+        .into_iter()
+        .zip(names)
+        .zip(keys)
+        .map(|((device, name), key)| (device, make_id(&backend, &key), name))
+        .filter(|(_, id, _)| seen.insert(id.clone()))
+        .collect())
+}
+
+/// Resolve a stable or legacy ID to a device on `host`, returning the device
+/// and its stable ID.
+pub fn find_cpal_device(
+    host: &Host,
+    direction: Direction,
+    wanted: &str,
+) -> Result<Option<(Device, String)>, String> {
+    let devices = cpal_devices(host, direction)?;
+    let ids: Vec<String> = devices.iter().map(|(_, id, _)| id.clone()).collect();
+    let names: Vec<String> = devices.iter().map(|(_, _, n)| n.clone()).collect();
+    Ok(resolve_index(&ids, &names, wanted).map(|i| {
+        let (device, id, _) = devices.into_iter().nth(i).unwrap();
+        (device, id)
+    }))
+}
+
+/// Stable ID of a host's default device, if any.
+pub fn default_cpal_device_id(host: &Host, direction: Direction) -> Option<String> {
+    let default_name = match direction {
+        Direction::Input => host.default_input_device(),
+        Direction::Output => host.default_output_device(),
+    }?
+    .name()
+    .ok()?;
+    cpal_devices(host, direction)
+        .ok()?
+        .into_iter()
+        .find(|(_, _, name)| *name == default_name)
+        .map(|(_, id, _)| id)
+}
+
+/// ALSA and JACK device names are already stable strings.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn native_keys(_backend: &str, _direction: Direction) -> Vec<(String, String)> {
+    vec![]
+}
+
+/// MMDevice endpoint IDs with their friendly names.
+#[cfg(target_os = "windows")]
+fn native_keys(backend: &str, direction: Direction) -> Vec<(String, String)> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use windows::Win32::Devices::Properties::DEVPKEY_Device_FriendlyName;
+    use windows::Win32::Media::Audio::{
+        eCapture, eRender, IMMDeviceEnumerator, MMDeviceEnumerator, DEVICE_STATE_ACTIVE,
+    };
+    use windows::Win32::System::Com::StructuredStorage::PropVariantClear;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_ALL,
+        COINIT_MULTITHREADED, STGM_READ,
+    };
+    use windows::Win32::System::Variant::VT_LPWSTR;
+
+    if backend != WASAPI {
+        return vec![];
+    }
+    let flow = match direction {
+        Direction::Input => eCapture,
+        Direction::Output => eRender,
+    };
+
+    struct ComGuard;
+    impl Drop for ComGuard {
+        fn drop(&mut self) {
+            unsafe { CoUninitialize() };
+        }
+    }
+
+    let mut keys = Vec::new();
+    unsafe {
+        // May already be initialized on this thread. Every successful call,
+        // including S_FALSE, is balanced; a thread in another mode is not.
+        // Declared first, so the COM objects below are released before it.
+        let _com = CoInitializeEx(None, COINIT_MULTITHREADED)
+            .is_ok()
+            .then_some(ComGuard);
+        let Ok(enumerator) =
+            CoCreateInstance::<_, IMMDeviceEnumerator>(&MMDeviceEnumerator, None, CLSCTX_ALL)
+        else {
+            return keys;
+        };
+        let Ok(collection) = enumerator.EnumAudioEndpoints(flow, DEVICE_STATE_ACTIVE) else {
+            return keys;
+        };
+        let count = collection.GetCount().unwrap_or(0);
+        for i in 0..count {
+            let Ok(device) = collection.Item(i) else {
+                continue;
+            };
+            let Ok(id) = device.GetId() else {
+                continue;
+            };
+            let endpoint_id = id.to_string().unwrap_or_default();
+            CoTaskMemFree(Some(id.0 as *const _));
+
+            let Ok(store) = device.OpenPropertyStore(STGM_READ) else {
+                continue;
+            };
+            let Ok(mut value) =
+                store.GetValue(&DEVPKEY_Device_FriendlyName as *const _ as *const _)
+            else {
+                continue;
+            };
+            let raw = &value.as_raw().Anonymous.Anonymous;
+            if raw.vt == VT_LPWSTR.0 {
+                let ptr = *(&raw.Anonymous as *const _ as *const *const u16);
+                let mut len = 0;
+                while *ptr.add(len) != 0 {
+                    len += 1;
+                }
+                let name = OsString::from_wide(std::slice::from_raw_parts(ptr, len))
+                    .to_string_lossy()
+                    .into_owned();
+                keys.push((name, endpoint_id));
+            }
+            let _ = PropVariantClear(&mut value);
+        }
+    }
+    keys
+}
+
+/// CoreAudio device UIDs with their names.
+#[cfg(target_os = "macos")]
+fn native_keys(backend: &str, _direction: Direction) -> Vec<(String, String)> {
+    use core_foundation_sys::base::CFRelease;
+    use core_foundation_sys::string::{kCFStringEncodingUTF8, CFStringGetCString, CFStringRef};
+    use coreaudio_sys::{
+        kAudioDevicePropertyDeviceNameCFString, kAudioDevicePropertyDeviceUID,
+        kAudioHardwareNoError, kAudioHardwarePropertyDevices, kAudioObjectPropertyElementMaster,
+        kAudioObjectPropertyScopeGlobal, kAudioObjectSystemObject, AudioDeviceID,
+        AudioObjectGetPropertyData, AudioObjectGetPropertyDataSize, AudioObjectPropertyAddress,
+    };
+    use std::ffi::CStr;
+    use std::mem;
+    use std::ptr::null;
+
+    if backend != COREAUDIO {
+        return vec![];
+    }
+
+    unsafe fn cf_string_property(device: AudioDeviceID, selector: u32) -> Option<String> {
+        let address = AudioObjectPropertyAddress {
+            mSelector: selector,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        let mut value: CFStringRef = null();
+        let mut size = mem::size_of::<CFStringRef>() as u32;
+        let status = AudioObjectGetPropertyData(
+            device,
+            &address,
+            0,
+            null(),
+            &mut size,
+            &mut value as *mut _ as *mut _,
+        );
+        if status != kAudioHardwareNoError as i32 || value.is_null() {
+            return None;
+        }
+        let mut buf = [0 as std::os::raw::c_char; 512];
+        let ok = CFStringGetCString(
+            value,
+            buf.as_mut_ptr(),
+            buf.len() as _,
+            kCFStringEncodingUTF8,
+        );
+        CFRelease(value as *const _);
+        if ok == 0 {
+            return None;
+        }
+        Some(CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned())
+    }
+
+    let mut keys = Vec::new();
+    unsafe {
+        let address = AudioObjectPropertyAddress {
+            mSelector: kAudioHardwarePropertyDevices,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        let mut size = 0u32;
+        if AudioObjectGetPropertyDataSize(kAudioObjectSystemObject, &address, 0, null(), &mut size)
+            != kAudioHardwareNoError as i32
+        {
+            return keys;
+        }
+        let count = size as usize / mem::size_of::<AudioDeviceID>();
+        let mut devices: Vec<AudioDeviceID> = vec![0; count];
+        if AudioObjectGetPropertyData(
+            kAudioObjectSystemObject,
+            &address,
+            0,
+            null(),
+            &mut size,
+            devices.as_mut_ptr() as *mut _,
+        ) != kAudioHardwareNoError as i32
+        {
+            return keys;
+        }
+        for device in devices {
+            let name = cf_string_property(device, kAudioDevicePropertyDeviceNameCFString);
+            let uid = cf_string_property(device, kAudioDevicePropertyDeviceUID);
+            if let (Some(name), Some(uid)) = (name, uid) {
+                keys.push((name, uid));
+            }
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_id() {
+        assert_eq!(
+            split_id("alsa:hw:CARD=Mic,DEV=0"),
+            Some(("alsa", "hw:CARD=Mic,DEV=0"))
+        );
+        assert_eq!(
+            split_id("pulse:foo.monitor"),
+            Some(("pulse", "foo.monitor"))
+        );
+        assert_eq!(split_id("USB Microphone"), None);
+        assert_eq!(split_id("Mic: USB"), None);
+    }
+
+    #[test]
+    fn test_assign_keys_uses_native_keys_in_order() {
+        let names = strings(&["USB Mic", "Built-in", "USB Mic"]);
+        let native = vec![
+            ("USB Mic".to_string(), "uid-a".to_string()),
+            ("Built-in".to_string(), "uid-b".to_string()),
+            ("USB Mic".to_string(), "uid-c".to_string()),
+        ];
+        assert_eq!(
+            assign_keys(&names, &native),
+            strings(&["uid-a", "uid-b", "uid-c"])
+        );
+    }
+
+    #[test]
+    fn test_assign_keys_falls_back_to_shared_names() {
+        let names = strings(&["USB Mic", "USB Mic", "default"]);
+        assert_eq!(
+            assign_keys(&names, &[]),
+            strings(&["USB Mic", "USB Mic", "default"])
+        );
+        // Only devices without a native entry share a key
+        let native = vec![("USB Mic".to_string(), "uid-a".to_string())];
+        assert_eq!(
+            assign_keys(&names, &native),
+            strings(&["uid-a", "USB Mic", "default"])
+        );
+    }
+
+    #[test]
+    fn test_resolve_index_by_id_and_legacy_name() {
+        let names = strings(&["USB Mic", "USB Mic"]);
+        let ids = strings(&["wasapi:{a}", "wasapi:{b}"]);
+        assert_eq!(resolve_index(&ids, &names, "wasapi:{b}"), Some(1));
+        // Old settings stored the display name
+        assert_eq!(resolve_index(&ids, &names, "USB Mic"), Some(0));
+        // A stable ID that no longer exists must not match by name
+        assert_eq!(resolve_index(&ids, &names, "wasapi:{gone}"), None);
+    }
+}
@@ -0,0 +1,278 @@
+use crossbeam_channel::{RecvTimeoutError, Sender};
+use log::{info, warn};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::audio::mic_capture::MicCapture;
+use crate::audio::system_capture;
+use crate::transcription::types::{AudioDeviceInfo, DevicesChangedEvent};
+
+/// How often to re-list devices when the platform sends no change notifications.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// With notifications, an occasional re-list still catches devices of hosts
+/// they don't cover, such as JACK.
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Notifications come in bursts when a device appears; wait for the rest
+/// before re-listing.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// Identity of a device list for change detection. Rates and default flags
+/// are included so a new default device also counts as a change.
+fn fingerprint(devices: &[AudioDeviceInfo]) -> Vec<(String, String, bool)> {
+    let mut keys: Vec<_> = devices
+        .iter()
+        .map(|d| (d.id.clone(), d.name.clone(), d.is_default))
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// Input devices of every available cpal host, e.g. both ALSA and JACK.
+/// Their IDs name the backend, so they stay distinct.
+fn all_inputs() -> Vec<AudioDeviceInfo> {
+    MicCapture::host_names()
+        .iter()
+        .filter_map(|host| MicCapture::with_host(Some(host)).ok())
+        .flat_map(|mic| mic.list_devices())
+        .collect()
+}
+
+/// Emit `devices-changed` whenever the input or output device list changes.
+/// The lists are re-read when the platform reports a device change, or by
+/// polling where it can't (cpal itself has no hot-plug notifications).
+pub fn spawn(app_handle: AppHandle) {
+    let (tx, mut rx) = crossbeam_channel::unbounded();
+    let interval = match watch_changes(tx) {
+        Ok(()) => FALLBACK_POLL_INTERVAL,
+        Err(e) => {
+            warn!("Polling for audio device changes: {}", e);
+            POLL_INTERVAL
+        }
+    };
+
+    std::thread::spawn(move || {
+        let mut interval = interval;
+        let mut last: Option<(Vec<_>, Vec<_>)> = None;
+        loop {
+            let inputs = all_inputs();
+            let outputs = system_capture::create_system_capture().list_devices();
+            let current = (fingerprint(&inputs), fingerprint(&outputs));
+
+            if let Some(previous) = &last {
+                if *previous != current {
+                    info!(
+                        "Audio devices changed ({} inputs, {} outputs)",
+                        inputs.len(),
+                        outputs.len()
+                    );
+                    let _ =
+                        app_handle.emit("devices-changed", DevicesChangedEvent { inputs, outputs });
+                }
+            }
+            last = Some(current);
+
+            match rx.recv_timeout(interval) {
+                Ok(()) => {
+                    std::thread::sleep(SETTLE_TIME);
+                    while rx.try_recv().is_ok() {}
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    warn!("Audio device notifications stopped, polling instead");
+                    rx = crossbeam_channel::never();
+                    interval = POLL_INTERVAL;
+                }
+            }
+        }
+    });
+}
+
+/// PulseAudio (and PipeWire through pipewire-pulse) reports sink and source
+/// changes to subscribers.
+#[cfg(target_os = "linux")]
+fn watch_changes(tx: Sender<()>) -> Result<(), String> {
+    use crate::audio::pulse::PulseConnection;
+
+    let (ready_tx, ready_rx) = crossbeam_channel::bounded(1);
+    std::thread::spawn(move || {
+        let mut conn = match PulseConnection::connect().and_then(|mut conn| {
+            conn.subscribe_devices(move || {
+                let _ = tx.send(());
+            })?;
+            Ok(conn)
+        }) {
+            Ok(conn) => conn,
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+                return;
+            }
+        };
+        let _ = ready_tx.send(Ok(()));
+        // Dropping the connection drops the sender, which tells the monitor
+        // to fall back to polling
+        if let Err(e) = conn.run() {
+            warn!("Audio device notifications failed: {}", e);
+        }
+    });
+    ready_rx
+        .recv()
+        .map_err(|_| "Audio device watcher exited".to_string())?
+}
+
+/// CoreAudio calls property listeners on the system object when the device
+/// list or a default device changes.
+#[cfg(target_os = "macos")]
+fn watch_changes(tx: Sender<()>) -> Result<(), String> {
+    use coreaudio_sys::{
+        kAudioHardwareNoError, kAudioHardwarePropertyDefaultInputDevice,
+        kAudioHardwarePropertyDefaultOutputDevice, kAudioHardwarePropertyDevices,
+        kAudioObjectPropertyElementMaster, kAudioObjectPropertyScopeGlobal,
+        kAudioObjectSystemObject, AudioObjectAddPropertyListener, AudioObjectID,
+        AudioObjectPropertyAddress, OSStatus,
+    };
+    use std::ffi::c_void;
+
+    unsafe extern "C" fn on_change(
+        _object: AudioObjectID,
+        _count: u32,
+        _addresses: *const AudioObjectPropertyAddress,
+        client: *mut c_void,
+    ) -> OSStatus {
+        let tx = &*(client as *const Sender<()>);
+        let _ = tx.send(());
+        kAudioHardwareNoError as OSStatus
+    }
+
+    // The listeners stay registered for the life of the app
+    let client = Box::into_raw(Box::new(tx)) as *mut c_void;
+    for selector in [
+        kAudioHardwarePropertyDevices,
+        kAudioHardwarePropertyDefaultInputDevice,
+        kAudioHardwarePropertyDefaultOutputDevice,
+    ] {
+        let address = AudioObjectPropertyAddress {
+            mSelector: selector,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        let status = unsafe {
+            AudioObjectAddPropertyListener(
+                kAudioObjectSystemObject,
+                &address,
+                Some(on_change),
+                client,
+            )
+        };
+        if status != kAudioHardwareNoError as OSStatus {
+            return Err(format!(
+                "Failed to listen for CoreAudio devices ({})",
+                status
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Windows reports endpoint changes to an `IMMNotificationClient`.
+#[cfg(target_os = "windows")]
+fn watch_changes(tx: Sender<()>) -> Result<(), String> {
+    use windows::core::{implement, PCWSTR};
+    use windows::Win32::Media::Audio::{
+        EDataFlow, ERole, IMMDeviceEnumerator, IMMNotificationClient, IMMNotificationClient_Impl,
+        MMDeviceEnumerator, DEVICE_STATE,
+    };
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED,
+    };
+    use windows::Win32::UI::Shell::PropertiesSystem::PROPERTYKEY;
+
+    #[implement(IMMNotificationClient)]
+    struct Notifier(Sender<()>);
+
+    impl Notifier {
+        fn notify(&self) -> windows::core::Result<()> {
+            let _ = self.0.send(());
+            Ok(())
+        }
+    }
+
+    #[allow(non_snake_case)]
+    impl IMMNotificationClient_Impl for Notifier {
+        fn OnDeviceStateChanged(
+            &self,
+            _id: &PCWSTR,
+            _state: DEVICE_STATE,
+        ) -> windows::core::Result<()> {
+            self.notify()
+        }
+
+        fn OnDeviceAdded(&self, _id: &PCWSTR) -> windows::core::Result<()> {
+            self.notify()
+        }
+
+        fn OnDeviceRemoved(&self, _id: &PCWSTR) -> windows::core::Result<()> {
+            self.notify()
+        }
+
+        fn OnDefaultDeviceChanged(
+            &self,
+            _flow: EDataFlow,
+            _role: ERole,
+            _id: &PCWSTR,
+        ) -> windows::core::Result<()> {
+            self.notify()
+        }
+
+        fn OnPropertyValueChanged(
+            &self,
+            _id: &PCWSTR,
+            _key: &PROPERTYKEY,
+        ) -> windows::core::Result<()> {
+            Ok(())
+        }
+    }
+
+    let (ready_tx, ready_rx) = crossbeam_channel::bounded(1);
+    std::thread::spawn(move || {
+        // The thread keeps COM, the enumerator and the registration alive for
+        // the life of the app, so COM is never uninitialized
+        let registered = unsafe {
+            CoInitializeEx(None, COINIT_MULTITHREADED)
+                .ok()
+                .and_then(|()| {
+                    CoCreateInstance::<_, IMMDeviceEnumerator>(
+                        &MMDeviceEnumerator,
+                        None,
+                        CLSCTX_ALL,
+                    )
+                })
+                .and_then(|enumerator| {
+                    let client: IMMNotificationClient = Notifier(tx).into();
+                    enumerator.RegisterEndpointNotificationCallback(&client)?;
+                    Ok((enumerator, client))
+                })
+        };
+        let _registration = match registered {
+            Ok(registration) => registration,
+            Err(e) => {
+                let _ = ready_tx.send(Err(format!(
+                    "Failed to listen for audio endpoint changes: {}",
+                    e
+                )));
+                return;
+            }
+        };
+        let _ = ready_tx.send(Ok(()));
+        loop {
+            std::thread::park();
+        }
+    });
+    ready_rx
+        .recv()
+        .map_err(|_| "Audio device watcher exited".to_string())?
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn watch_changes(_tx: Sender<()>) -> Result<(), String> {
+    Err("No device notifications on this platform".to_string())
+}
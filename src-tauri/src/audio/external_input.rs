@@ -0,0 +1,593 @@
+//! Raw PCM written by other programs (softphones, recorders, `ffmpeg`) to a
+//! Unix domain socket or named pipe.
+//!
+//! A writer starts with one header line describing its audio, e.g.
+//! `rate=48000 channels=2 format=s16le`, followed by interleaved
+//! little-endian samples:
+//!
+//! ```text
+//! (echo "rate=48000 channels=1 format=s16le";
+//!  ffmpeg -i talk.mp3 -f s16le -ac 1 -ar 48000 -) | socat - UNIX-CONNECT:/tmp/pcm.sock
+//! ```
+
+use std::time::{Duration, Instant};
+
+/// Longest accepted header line, in bytes.
+const MAX_HEADER_LEN: usize = 256;
+
+/// Sample encodings an external writer can send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmFormat {
+    S16Le,
+    S32Le,
+    F32Le,
+}
+
+impl PcmFormat {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "s16le" | "s16" => Some(Self::S16Le),
+            "s32le" | "s32" => Some(Self::S32Le),
+            "f32le" | "f32" => Some(Self::F32Le),
+            _ => None,
+        }
+    }
+
+    pub fn sample_bytes(self) -> usize {
+        match self {
+            Self::S16Le => 2,
+            Self::S32Le | Self::F32Le => 4,
+        }
+    }
+
+    /// Decode one sample of `sample_bytes` bytes to f32.
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            Self::S16Le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            Self::S32Le => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32
+                    / 2_147_483_648.0
+            }
+            Self::F32Le => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+/// The audio format announced by an external writer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmHeader {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub format: PcmFormat,
+}
+
+impl PcmHeader {
+    /// Parse a header line of `key=value` fields: `rate`, `channels` and
+    /// `format` (`s16le`, `s32le` or `f32le`).
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let (mut sample_rate, mut channels, mut format) = (None, None, None);
+        for field in line.split_whitespace() {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("Malformed PCM header field '{}'", field))?;
+            match key {
+                "rate" => {
+                    sample_rate = Some(
+                        value
+                            .parse::<u32>()
+                            .ok()
+                            .filter(|r| (1000..=384_000).contains(r))
+                            .ok_or_else(|| format!("Invalid sample rate '{}'", value))?,
+                    )
+                }
+                "channels" => {
+                    channels = Some(
+                        value
+                            .parse::<u16>()
+                            .ok()
+                            .filter(|c| (1..=64).contains(c))
+                            .ok_or_else(|| format!("Invalid channel count '{}'", value))?,
+                    )
+                }
+                "format" => {
+                    format = Some(PcmFormat::parse(value).ok_or_else(|| {
+                        format!(
+                            "Unsupported sample format '{}' (expected s16le, s32le or f32le)",
+                            value
+                        )
+                    })?)
+                }
+                _ => return Err(format!("Unknown PCM header field '{}'", key)),
+            }
+        }
+        Ok(Self {
+            sample_rate: sample_rate.ok_or("PCM header is missing 'rate'")?,
+            channels: channels.ok_or("PCM header is missing 'channels'")?,
+            format: format.ok_or("PCM header is missing 'format'")?,
+        })
+    }
+
+    fn frame_bytes(&self) -> usize {
+        self.format.sample_bytes() * self.channels as usize
+    }
+}
+
+/// Splits one writer's byte stream into its header and whole frames of
+/// samples, carrying partial frames over to the next read.
+#[derive(Debug, Default)]
+pub struct PcmParser {
+    header: Option<PcmHeader>,
+    pending: Vec<u8>,
+}
+
+impl PcmParser {
+    pub fn header(&self) -> Option<PcmHeader> {
+        self.header
+    }
+
+    /// Feed bytes as read, returning the complete frames they finish as
+    /// interleaved f32 samples.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<f32>, String> {
+        self.pending.extend_from_slice(bytes);
+        let header = match self.header {
+            Some(header) => header,
+            None => {
+                let Some(end) = self.pending.iter().position(|&b| b == b'\n') else {
+                    if self.pending.len() > MAX_HEADER_LEN {
+                        return Err("PCM header line is too long".to_string());
+                    }
+                    return Ok(Vec::new());
+                };
+                let line = std::str::from_utf8(&self.pending[..end])
+                    .map_err(|_| "PCM header is not valid text".to_string())?;
+                let header = PcmHeader::parse(line.trim())?;
+                self.pending.drain(..=end);
+                self.header = Some(header);
+                header
+            }
+        };
+
+        let whole = self.pending.len() / header.frame_bytes() * header.frame_bytes();
+        let samples = self.pending[..whole]
+            .chunks_exact(header.format.sample_bytes())
+            .map(|sample| header.format.decode(sample))
+            .collect();
+        self.pending.drain(..whole);
+        Ok(samples)
+    }
+}
+
+/// Capture times for one writer's chunks. Consecutive chunks follow on from
+/// each other, so a writer sending faster than real time keeps its own
+/// timing, but a chunk is never stamped earlier than its arrival allows, so
+/// a writer that pauses picks up at the time it resumes.
+#[derive(Debug, Default)]
+pub struct SampleClock {
+    next: Option<Instant>,
+}
+
+impl SampleClock {
+    /// When the first of `frames` frames at `sample_rate` that arrived at
+    /// `now` was captured.
+    pub fn stamp(&mut self, frames: usize, sample_rate: u32, now: Instant) -> Instant {
+        let duration = Duration::from_secs_f64(frames as f64 / sample_rate.max(1) as f64);
+        let arrived = now.checked_sub(duration).unwrap_or(now);
+        let at = self.next.map_or(arrived, |next| next.max(arrived));
+        self.next = Some(at + duration);
+        at
+    }
+}
+
+#[cfg(unix)]
+pub use transport::start_external_capture;
+
+/// External PCM needs Unix sockets or FIFOs.
+#[cfg(not(unix))]
+pub fn start_external_capture(
+    _input: &crate::transcription::types::ExternalInput,
+    _sender: crate::audio::mic_capture::AudioSender,
+) -> Result<Box<dyn crate::audio::system_capture::CaptureHandle>, String> {
+    Err("External PCM input is only supported on Linux and macOS".to_string())
+}
+
+#[cfg(unix)]
+mod transport {
+    use log::{info, warn};
+    use std::ffi::CString;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{ErrorKind, Read};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, OpenOptionsExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex, PoisonError};
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+
+    use super::{PcmParser, SampleClock};
+    use crate::audio::mic_capture::AudioSender;
+    use crate::audio::system_capture::CaptureHandle;
+    use crate::transcription::types::ExternalInput;
+
+    /// How often idle reads and accepts check for stop.
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+    /// Samples are handed to the pipeline in chunks of about this long.
+    const CHUNK_SECS: f64 = 0.1;
+
+    struct ExternalCaptureHandle {
+        stop_flag: Arc<AtomicBool>,
+        running: Arc<AtomicBool>,
+        thread: Mutex<Option<JoinHandle<()>>>,
+    }
+
+    impl CaptureHandle for ExternalCaptureHandle {
+        /// Waits for the reader thread, so a restart can bind the same path.
+        fn stop(&self) {
+            self.stop_flag.store(true, Ordering::Relaxed);
+            let thread = self
+                .thread
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+            if let Some(thread) = thread {
+                let _ = thread.join();
+            }
+        }
+
+        fn is_running(&self) -> bool {
+            self.running.load(Ordering::Relaxed)
+        }
+
+        /// Writers only send while they have audio.
+        fn idles_when_silent(&self) -> bool {
+            true
+        }
+    }
+
+    /// Start reading PCM from `input`. A socket serves one writer at a time,
+    /// each starting with its own header; a FIFO expects a header from each
+    /// writer that opens it.
+    pub fn start_external_capture(
+        input: &ExternalInput,
+        sender: AudioSender,
+    ) -> Result<Box<dyn CaptureHandle>, String> {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let running = Arc::new(AtomicBool::new(true));
+        let stop = stop_flag.clone();
+        let serve: Box<dyn FnOnce() -> Result<(), String> + Send> = match input {
+            ExternalInput::Socket { path } => {
+                let path = PathBuf::from(path);
+                let listener = bind_socket(&path)?;
+                Box::new(move || {
+                    let result = serve_socket(&listener, &path, &sender, &stop);
+                    let _ = fs::remove_file(&path);
+                    result
+                })
+            }
+            ExternalInput::Fifo { path } => {
+                let path = PathBuf::from(path);
+                let fifo = open_fifo(&path)?;
+                Box::new(move || serve_fifo(fifo, &path, &sender, &stop))
+            }
+        };
+
+        let running_for_thread = running.clone();
+        let thread = std::thread::spawn(move || {
+            if let Err(e) = serve() {
+                warn!("External PCM input stopped: {}", e);
+            }
+            running_for_thread.store(false, Ordering::Relaxed);
+        });
+        Ok(Box::new(ExternalCaptureHandle {
+            stop_flag,
+            running,
+            thread: Mutex::new(Some(thread)),
+        }))
+    }
+
+    fn bind_socket(path: &Path) -> Result<UnixListener, String> {
+        // A socket file left behind by an earlier run would make bind fail.
+        // Anything else at the path is not ours to replace.
+        match fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_socket() => {
+                let _ = fs::remove_file(path);
+            }
+            Ok(_) => return Err(format!("'{}' exists and is not a socket", path.display())),
+            Err(_) => {}
+        }
+        // Bound in a private directory and linked into place once only its
+        // owner may connect, so no other user can get in first. Unlike a
+        // rename, the link fails rather than replace a file created since.
+        let name = path
+            .file_name()
+            .ok_or_else(|| format!("Invalid socket path '{}'", path.display()))?;
+        let private = path.with_file_name(format!(
+            ".{}.{}",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&private)
+            .map_err(|e| format!("Failed to create '{}': {}", private.display(), e))?;
+        let bound = private.join(name);
+        let result = UnixListener::bind(&bound)
+            .and_then(|listener| {
+                fs::set_permissions(&bound, fs::Permissions::from_mode(0o600))?;
+                fs::hard_link(&bound, path)?;
+                Ok(listener)
+            })
+            .map_err(|e| format!("Failed to listen on '{}': {}", path.display(), e));
+        let _ = fs::remove_file(&bound);
+        let _ = fs::remove_dir(&private);
+        let listener = result?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure '{}': {}", path.display(), e))?;
+        Ok(listener)
+    }
+
+    fn serve_socket(
+        listener: &UnixListener,
+        path: &Path,
+        sender: &AudioSender,
+        stop_flag: &AtomicBool,
+    ) -> Result<(), String> {
+        while !stop_flag.load(Ordering::Relaxed) {
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+                Err(e) => return Err(format!("Failed to accept on '{}': {}", path.display(), e)),
+            };
+            info!("External PCM writer connected to '{}'", path.display());
+            // Accepted sockets may inherit non-blocking mode on some platforms
+            let configured = stream
+                .set_nonblocking(false)
+                .and_then(|()| stream.set_read_timeout(Some(POLL_INTERVAL)));
+            let result = configured
+                .map_err(|e| e.to_string())
+                .and_then(|()| read_writer(&stream, &mut PcmParser::default(), sender, stop_flag));
+            match result {
+                Ok(()) => info!("External PCM writer on '{}' disconnected", path.display()),
+                // A bad writer is dropped; the next one may do better
+                Err(e) => warn!("External PCM writer on '{}': {}", path.display(), e),
+            }
+        }
+        Ok(())
+    }
+
+    fn open_fifo(path: &Path) -> Result<File, String> {
+        match fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_fifo() => {}
+            Ok(_) => return Err(format!("'{}' exists and is not a FIFO", path.display())),
+            Err(_) => {
+                let c_path = CString::new(path.as_os_str().as_bytes())
+                    .map_err(|_| format!("Invalid FIFO path '{}'", path.display()))?;
+                if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                    return Err(format!(
+                        "Failed to create FIFO '{}': {}",
+                        path.display(),
+                        std::io::Error::last_os_error()
+                    ));
+                }
+            }
+        }
+        // Non-blocking, so opening does not wait for a writer and reads can
+        // poll for stop
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .map_err(|e| format!("Failed to open FIFO '{}': {}", path.display(), e))
+    }
+
+    fn serve_fifo(
+        fifo: File,
+        path: &Path,
+        sender: &AudioSender,
+        stop_flag: &AtomicBool,
+    ) -> Result<(), String> {
+        // Reads hit end of file whenever no writer has the FIFO open
+        let mut parser = PcmParser::default();
+        while !stop_flag.load(Ordering::Relaxed) {
+            match read_writer(&fifo, &mut parser, sender, stop_flag) {
+                Ok(()) => {}
+                Err(e) => warn!("External PCM writer on '{}': {}", path.display(), e),
+            }
+            if parser.header().is_some() {
+                info!("External PCM writer on '{}' closed", path.display());
+            }
+            parser = PcmParser::default();
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    /// Forward one writer's audio until it closes, sends something invalid
+    /// or capture stops. Samples are batched into chunks of `CHUNK_SECS`, and
+    /// a partial batch is sent whenever the writer pauses. Chunks are
+    /// stamped by `SampleClock`.
+    fn read_writer(
+        mut reader: impl Read,
+        parser: &mut PcmParser,
+        sender: &AudioSender,
+        stop_flag: &AtomicBool,
+    ) -> Result<(), String> {
+        let mut buf = [0u8; 8192];
+        let mut batch = Vec::new();
+        let mut clock = SampleClock::default();
+        let mut flush = |batch: &mut Vec<f32>, parser: &PcmParser| {
+            let Some(header) = parser.header() else {
+                return Ok(());
+            };
+            if batch.is_empty() {
+                return Ok(());
+            }
+            let frames = batch.len() / header.channels as usize;
+            let captured_at = clock.stamp(frames, header.sample_rate, Instant::now());
+            let samples = std::mem::take(batch);
+            if !sender.send_paced(samples, header.sample_rate, header.channels, captured_at) {
+                return Err("Transcription pipeline closed".to_string());
+            }
+            Ok(())
+        };
+
+        while !stop_flag.load(Ordering::Relaxed) {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    batch.extend(parser.push(&buf[..n])?);
+                    if let Some(header) = parser.header() {
+                        let chunk_len = (header.sample_rate as f64 * CHUNK_SECS) as usize
+                            * header.channels as usize;
+                        if batch.len() >= chunk_len {
+                            flush(&mut batch, parser)?;
+                        }
+                    }
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    flush(&mut batch, parser)?;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("Read failed: {}", e)),
+            }
+        }
+        flush(&mut batch, parser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header() {
+        let header = PcmHeader::parse("rate=48000 channels=2 format=s16le").unwrap();
+        assert_eq!(
+            header,
+            PcmHeader {
+                sample_rate: 48000,
+                channels: 2,
+                format: PcmFormat::S16Le,
+            }
+        );
+        assert!(PcmHeader::parse("rate=48000 channels=2").is_err());
+        assert!(PcmHeader::parse("rate=48000 channels=0 format=s16le").is_err());
+        assert!(PcmHeader::parse("rate=48000 channels=1 format=u8").is_err());
+        assert!(PcmHeader::parse("rate=48000 channels=1 format=f32le bits=32").is_err());
+    }
+
+    #[test]
+    fn test_parser_keeps_partial_frames() {
+        let mut parser = PcmParser::default();
+        let mut bytes = b"rate=16000 channels=2 format=s16le\n".to_vec();
+        for sample in [16384i16, -16384, 8192, 0] {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        // Header split across reads, then a frame and a half
+        assert!(parser.push(&bytes[..10]).unwrap().is_empty());
+        assert_eq!(parser.push(&bytes[10..41]).unwrap(), vec![0.5, -0.5]);
+        assert_eq!(parser.header().unwrap().channels, 2);
+        assert_eq!(parser.push(&bytes[41..]).unwrap(), vec![0.25, 0.0]);
+    }
+
+    #[test]
+    fn test_parser_decodes_float_and_rejects_bad_header() {
+        let mut parser = PcmParser::default();
+        let mut bytes = b"rate=8000 channels=1 format=f32le\n".to_vec();
+        bytes.extend_from_slice(&0.75f32.to_le_bytes());
+        assert_eq!(parser.push(&bytes).unwrap(), vec![0.75]);
+
+        let mut parser = PcmParser::default();
+        assert!(parser.push(&[b'x'; MAX_HEADER_LEN + 1]).is_err());
+    }
+
+    #[test]
+    fn test_sample_clock_follows_writer_and_pauses() {
+        let mut clock = SampleClock::default();
+        let start = Instant::now();
+        let tenth = Duration::from_millis(100);
+        // A faster than real time writer: chunks follow on from each other
+        assert_eq!(clock.stamp(800, 8000, start + tenth), start);
+        assert_eq!(clock.stamp(800, 8000, start + tenth), start + tenth);
+        assert_eq!(clock.stamp(800, 8000, start + tenth), start + tenth * 2);
+        // After a pause the stamps catch up with arrival
+        let resumed = start + Duration::from_secs(10);
+        assert_eq!(clock.stamp(800, 8000, resumed), resumed - tenth);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_feeds_pipeline() {
+        use crate::audio::mic_capture::AudioSender;
+        use crate::transcription::pipeline_stats::PipelineStats;
+        use crate::transcription::types::ExternalInput;
+        use std::io::Write;
+        use std::os::unix::net::UnixStream;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("external.sock");
+        let (tx, rx) = crossbeam_channel::bounded(16);
+        let input = ExternalInput::Socket {
+            path: path.to_string_lossy().into_owned(),
+        };
+        let handle =
+            start_external_capture(&input, AudioSender::new(tx, Arc::new(PipelineStats::new())))
+                .unwrap();
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let mut writer = UnixStream::connect(&path).unwrap();
+        writer
+            .write_all(b"rate=8000 channels=1 format=s16le\n")
+            .unwrap();
+        let samples: Vec<u8> = (0..1600).flat_map(|_| 16384i16.to_le_bytes()).collect();
+        writer.write_all(&samples).unwrap();
+        drop(writer);
+
+        let mut received = 0;
+        while received < 1600 {
+            let chunk = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!((chunk.sample_rate, chunk.channels), (8000, 1));
+            assert!(chunk.samples.iter().all(|&s| s == 0.5));
+            received += chunk.samples.len();
+        }
+        assert_eq!(received, 1600);
+
+        handle.stop();
+        assert!(!handle.is_running());
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_does_not_replace_other_files() {
+        use crate::audio::mic_capture::AudioSender;
+        use crate::transcription::pipeline_stats::PipelineStats;
+        use crate::transcription::types::ExternalInput;
+        use std::sync::Arc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("notes.txt");
+        std::fs::write(&path, "keep me").unwrap();
+        let (tx, _rx) = crossbeam_channel::bounded(16);
+        let input = ExternalInput::Socket {
+            path: path.to_string_lossy().into_owned(),
+        };
+        let error =
+            start_external_capture(&input, AudioSender::new(tx, Arc::new(PipelineStats::new())))
+                .err()
+                .unwrap();
+        assert!(error.contains("is not a socket"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+/// How often a meter is read.
+pub const METER_INTERVAL: Duration = Duration::from_millis(50);
+/// Floor for all dB values, reported for digital silence.
+pub const MIN_DB: f32 = -100.0;
+/// Samples at or above this magnitude count as clipped.
+const CLIP_THRESHOLD: f32 = 0.999;
+/// How fast the VU level falls back after a louder window.
+const VU_DECAY_DB_PER_SEC: f32 = 20.0;
+/// Number of bands in the coarse spectrum.
+pub const SPECTRUM_BANDS: usize = 16;
+/// Mono samples analysed for the spectrum.
+const SPECTRUM_WINDOW: usize = 1024;
+const SPECTRUM_MIN_HZ: f32 = 50.0;
+const SPECTRUM_MAX_HZ: f32 = 8000.0;
+
+/// One aggregated meter reading.
+#[derive(Debug, Clone)]
+pub struct LevelReading {
+    /// Linear RMS in 0.0..=1.0.
+    pub rms: f32,
+    pub rms_db: f32,
+    pub peak_db: f32,
+    /// RMS with VU-style ballistics: rises immediately, decays slowly.
+    pub vu_db: f32,
+    /// Clipped samples since the previous reading.
+    pub clipped: u32,
+    /// Band levels in dBFS, log-spaced from 50 Hz up to 8 kHz (or Nyquist).
+    pub spectrum: Option<Vec<f32>>,
+}
+
+/// Aggregates captured audio into readings taken on a timer, so level
+/// updates go out at a fixed rate however often the capture callback fires.
+pub struct LevelMeter {
+    /// When the previous reading was taken.
+    window_start: Option<Instant>,
+    sum_squares: f64,
+    count: u64,
+    peak: f32,
+    clipped: u32,
+    vu_db: f32,
+    /// Recent mono samples for the spectrum.
+    history: VecDeque<f32>,
+    sample_rate: u32,
+}
+
+pub fn to_db(linear: f32) -> f32 {
+    if linear <= 0.0 {
+        MIN_DB
+    } else {
+        (20.0 * linear.log10()).max(MIN_DB)
+    }
+}
+
+impl Default for LevelMeter {
+    fn default() -> Self {
+        Self {
+            window_start: None,
+            sum_squares: 0.0,
+            count: 0,
+            peak: 0.0,
+            clipped: 0,
+            vu_db: MIN_DB,
+            history: VecDeque::with_capacity(SPECTRUM_WINDOW),
+            sample_rate: 0,
+        }
+    }
+}
+
+impl LevelMeter {
+    /// Add interleaved samples to the current window. Samples for the
+    /// spectrum are only kept when `with_spectrum` is set.
+    pub fn push(
+        &mut self,
+        samples: &[f32],
+        channels: usize,
+        sample_rate: u32,
+        with_spectrum: bool,
+    ) {
+        let channels = channels.max(1);
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.history.clear();
+        }
+
+        for &s in samples {
+            self.sum_squares += (s as f64) * (s as f64);
+            self.peak = self.peak.max(s.abs());
+            if s.abs() >= CLIP_THRESHOLD {
+                self.clipped += 1;
+            }
+        }
+        self.count += samples.len() as u64;
+
+        if with_spectrum {
+            for frame in samples.chunks(channels) {
+                if self.history.len() == SPECTRUM_WINDOW {
+                    self.history.pop_front();
+                }
+                self.history
+                    .push_back(frame.iter().sum::<f32>() / frame.len() as f32);
+            }
+        }
+    }
+
+    /// Close the window at `now` and return its reading. A window without
+    /// audio reads as silence, so the level falls while capture delivers
+    /// nothing. The spectrum is only computed when `with_spectrum` is set.
+    pub fn reading(&mut self, now: Instant, with_spectrum: bool) -> LevelReading {
+        let elapsed = self
+            .window_start
+            .map_or(Duration::ZERO, |start| now.saturating_duration_since(start));
+        let rms = if self.count == 0 {
+            0.0
+        } else {
+            (self.sum_squares / self.count as f64).sqrt() as f32
+        };
+        let rms_db = to_db(rms);
+        let decayed = self.vu_db - VU_DECAY_DB_PER_SEC * elapsed.as_secs_f32();
+        self.vu_db = rms_db.max(decayed).max(MIN_DB);
+
+        let reading = LevelReading {
+            rms: rms.min(1.0),
+            rms_db,
+            peak_db: to_db(self.peak),
+            vu_db: self.vu_db,
+            clipped: self.clipped,
+            spectrum: with_spectrum.then(|| self.spectrum()),
+        };
+
+        self.window_start = Some(now);
+        self.sum_squares = 0.0;
+        self.count = 0;
+        self.peak = 0.0;
+        self.clipped = 0;
+        reading
+    }
+
+    /// Band levels of the recent history, using one Goertzel filter per band
+    /// over a Hann window. Cheap enough at 16 bands and 20 Hz that no FFT is
+    /// needed.
+    fn spectrum(&self) -> Vec<f32> {
+        let n = self.history.len();
+        if n == 0 || self.sample_rate == 0 {
+            return vec![MIN_DB; SPECTRUM_BANDS];
+        }
+        let window: Vec<f32> = (0..n)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / n as f32).cos())
+            .collect();
+        let window_sum: f32 = window.iter().sum();
+        let max_hz = SPECTRUM_MAX_HZ.min(self.sample_rate as f32 / 2.0);
+
+        (0..SPECTRUM_BANDS)
+            .map(|band| {
+                let t = band as f32 / (SPECTRUM_BANDS - 1) as f32;
+                let freq = SPECTRUM_MIN_HZ * (max_hz / SPECTRUM_MIN_HZ).powf(t);
+                let coeff = 2.0 * (2.0 * PI * freq / self.sample_rate as f32).cos();
+                let (mut s1, mut s2) = (0.0f32, 0.0f32);
+                for (x, w) in self.history.iter().zip(&window) {
+                    let s = x * w + coeff * s1 - s2;
+                    s2 = s1;
+                    s1 = s;
+                }
+                let power = (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0);
+                // Scale so a full-scale sine at the band frequency reads 0 dBFS
+                to_db(2.0 * power.sqrt() / window_sum)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, amplitude: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (2.0 * PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_reading_covers_all_chunks_since_the_last() {
+        let mut meter = LevelMeter::default();
+        let start = Instant::now();
+        meter.reading(start, false);
+        // Five 10ms callbacks between two readings
+        for _ in 0..4 {
+            meter.push(&[0.0; 480], 1, 48000, false);
+        }
+        meter.push(&[1.0; 480], 1, 48000, false);
+        let reading = meter.reading(start + METER_INTERVAL, false);
+        assert!(
+            (reading.rms - 0.2f32.sqrt()).abs() < 1e-4,
+            "{}",
+            reading.rms
+        );
+        assert_eq!(reading.clipped, 480);
+
+        // No audio in the next window
+        let reading = meter.reading(start + METER_INTERVAL * 2, false);
+        assert_eq!(reading.rms_db, MIN_DB);
+        assert_eq!(reading.peak_db, MIN_DB);
+        assert_eq!(reading.clipped, 0);
+    }
+
+    #[test]
+    fn test_sine_levels() {
+        let mut meter = LevelMeter::default();
+        let samples = sine(1000.0, 0.5, 48000, 4800);
+        meter.push(&samples, 1, 48000, false);
+        let reading = meter.reading(Instant::now(), false);
+        // RMS of a sine is amplitude / sqrt(2): -9.03 dBFS at half scale
+        assert!((reading.rms_db + 9.03).abs() < 0.1, "{}", reading.rms_db);
+        assert!((reading.peak_db + 6.02).abs() < 0.1, "{}", reading.peak_db);
+        assert_eq!(reading.clipped, 0);
+        assert!(reading.spectrum.is_none());
+    }
+
+    #[test]
+    fn test_clipping_and_silence() {
+        let mut meter = LevelMeter::default();
+        let start = Instant::now();
+        meter.push(&[1.0, -1.0, 0.2, 1.0], 1, 48000, false);
+        let reading = meter.reading(start, false);
+        assert_eq!(reading.clipped, 3);
+
+        meter.push(&[0.0; 4], 1, 48000, false);
+        let reading = meter.reading(start + METER_INTERVAL, false);
+        assert_eq!(reading.rms_db, MIN_DB);
+        assert_eq!(reading.clipped, 0);
+    }
+
+    #[test]
+    fn test_vu_decays_after_loud_window() {
+        let mut meter = LevelMeter::default();
+        let start = Instant::now();
+        let loud = sine(440.0, 1.0, 48000, 2400);
+        let quiet = vec![0.0f32; 2400];
+        meter.push(&loud, 1, 48000, false);
+        let first = meter.reading(start, false);
+        meter.push(&quiet, 1, 48000, false);
+        let second = meter.reading(start + METER_INTERVAL, false);
+        // Silence reads MIN_DB right away, while the VU level falls back slowly
+        assert_eq!(second.rms_db, MIN_DB);
+        let expected = first.vu_db - VU_DECAY_DB_PER_SEC * METER_INTERVAL.as_secs_f32();
+        assert!((second.vu_db - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_spectrum_peaks_at_sine_band() {
+        let mut meter = LevelMeter::default();
+        // Band 8 of 16 between 50 Hz and 8 kHz
+        let band = 8;
+        let freq = SPECTRUM_MIN_HZ
+            * (SPECTRUM_MAX_HZ / SPECTRUM_MIN_HZ).powf(band as f32 / (SPECTRUM_BANDS - 1) as f32);
+        let samples = sine(freq, 0.5, 16000, 2048);
+        meter.push(&samples, 1, 16000, true);
+        let spectrum = meter.reading(Instant::now(), true).spectrum.unwrap();
+        assert_eq!(spectrum.len(), SPECTRUM_BANDS);
+        let loudest = (0..SPECTRUM_BANDS)
+            .max_by(|&a, &b| spectrum[a].total_cmp(&spectrum[b]))
+            .unwrap();
+        assert_eq!(loudest, band);
+        // Half-scale sine reads about -6 dBFS in its band
+        assert!((spectrum[band] + 6.0).abs() < 1.0, "{}", spectrum[band]);
+    }
+}
@@ -1,15 +1,148 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Host, SampleFormat, Stream, StreamConfig};
-use crossbeam_channel::Sender;
-use log::{error, info};
+use cpal::{
+    BufferSize, Device, Host, SampleFormat, SampleRate, Stream, StreamConfig, SupportedBufferSize,
+    SupportedStreamConfigRange,
+};
+use crossbeam_channel::{Sender, TrySendError};
+use log::{error, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::transcription::types::AudioDeviceInfo;
+use crate::audio::capture_format::choose_capture_config;
+use crate::audio::device_id::{
+    cpal_devices, default_cpal_device_id, find_cpal_device, Direction,
+};
+use crate::transcription::pipeline_stats::PipelineStats;
+use crate::transcription::types::{
+    AudioDeviceInfo, CaptureFormat, DeviceCapabilities, SupportedCaptureConfig,
+};
 
 /// Raw audio chunk from capture thread
+#[derive(Clone)]
 pub struct AudioChunk {
     pub samples: Vec<f32>, // interleaved f32 samples
     pub sample_rate: u32,
     pub channels: u16,
+    /// When the first frame was captured, from the device clock where the
+    /// backend reports one.
+    pub captured_at: Instant,
+}
+
+/// Sending half of a capture channel, used from audio callbacks.
+/// `send_captured` never blocks: when the pipeline falls behind, the chunk
+/// is dropped and counted.
+#[derive(Clone)]
+pub struct AudioSender {
+    sender: Sender<AudioChunk>,
+    stats: Arc<PipelineStats>,
+}
+
+impl AudioSender {
+    pub fn new(sender: Sender<AudioChunk>, stats: Arc<PipelineStats>) -> Self {
+        Self { sender, stats }
+    }
+
+    /// Send samples whose first frame was captured at `captured_at`.
+    pub fn send_captured(
+        &self,
+        samples: Vec<f32>,
+        sample_rate: u32,
+        channels: u16,
+        captured_at: Instant,
+    ) {
+        let chunk = AudioChunk {
+            samples,
+            sample_rate,
+            channels,
+            captured_at,
+        };
+        let dropped = matches!(self.sender.try_send(chunk), Err(TrySendError::Full(_)));
+        self.stats.record_captured(dropped, self.sender.len());
+    }
+
+    /// Like `send_captured`, but waits for room when the pipeline falls
+    /// behind, for sources that can be paced rather than dropped. Returns
+    /// false once the pipeline is gone.
+    pub fn send_paced(
+        &self,
+        samples: Vec<f32>,
+        sample_rate: u32,
+        channels: u16,
+        captured_at: Instant,
+    ) -> bool {
+        let chunk = AudioChunk {
+            samples,
+            sample_rate,
+            channels,
+            captured_at,
+        };
+        let sent = self.sender.send(chunk).is_ok();
+        self.stats.record_captured(false, self.sender.len());
+        sent
+    }
+
+    /// Chunks sent so far, for telling whether capture is still alive.
+    pub fn chunks_sent(&self) -> u64 {
+        self.stats.chunks_captured()
+    }
+}
+
+/// The instant `ago` before now.
+pub fn instant_before(ago: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_sub(ago).unwrap_or(now)
+}
+
+/// The instant at which `clock`, a libc clock ID such as `CLOCK_MONOTONIC`,
+/// read `nanos`; for timestamps taken by an audio server.
+#[cfg(unix)]
+pub fn instant_at_clock(clock: libc::clockid_t, nanos: u64) -> Instant {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `now` is a valid timespec for the call to fill in
+    if unsafe { libc::clock_gettime(clock, &mut now) } != 0 {
+        return Instant::now();
+    }
+    let now = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+    instant_before(now.saturating_sub(Duration::from_nanos(nanos)))
+}
+
+/// When the first frame of a cpal input callback was captured, from the
+/// stream's own timestamps.
+pub fn cpal_capture_instant(info: &cpal::InputCallbackInfo) -> Instant {
+    let timestamp = info.timestamp();
+    let latency = timestamp
+        .callback
+        .duration_since(&timestamp.capture)
+        .unwrap_or_default();
+    instant_before(latency)
+}
+
+/// Set by a cpal stream's error callback so its owner can tell the stream
+/// died (e.g. the device was unplugged). A stream that goes silent without
+/// an error is caught by the capture watchdog's stall check.
+pub struct StreamHealth {
+    errored: AtomicBool,
+}
+
+impl StreamHealth {
+    pub fn new() -> Self {
+        Self {
+            errored: AtomicBool::new(false),
+        }
+    }
+
+    fn mark_error(&self) {
+        self.errored.store(true, Ordering::Relaxed);
+    }
+
+    /// True if the stream reported an error.
+    pub fn has_failed(&self) -> bool {
+        self.errored.load(Ordering::Relaxed)
+    }
 }
 
 pub struct MicCapture {
@@ -22,23 +155,74 @@ impl MicCapture {
         Self { host }
     }
 
-    /// List available input (microphone) devices
+    /// Capture through the named cpal host (e.g. `JACK`), or the platform
+    /// default when `None`.
+    pub fn with_host(name: Option<&str>) -> Result<Self, String> {
+        let Some(name) = name else {
+            return Ok(Self::new());
+        };
+        let id = cpal::available_hosts()
+            .into_iter()
+            .find(|id| id.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "Audio host '{}' is not available (available: {})",
+                    name,
+                    Self::host_names().join(", ")
+                )
+            })?;
+        let host = cpal::host_from_id(id)
+            .map_err(|e| format!("Failed to open audio host '{}': {}", name, e))?;
+        Ok(Self { host })
+    }
+
+    /// Names of the cpal hosts available on this platform.
+    pub fn host_names() -> Vec<String> {
+        cpal::available_hosts()
+            .into_iter()
+            .map(|id| id.name().to_string())
+            .collect()
+    }
+
+    /// Supported capture configs of an input device, or the default one.
+    pub fn capabilities(&self, device_id: Option<&str>) -> Result<DeviceCapabilities, String> {
+        let (device, id) = self.get_device(device_id)?;
+        let default = device.default_input_config().ok();
+        let configs = device
+            .supported_input_configs()
+            .map_err(|e| format!("Failed to query supported configs: {}", e))?
+            .map(|range| supported_config(&range))
+            .collect();
+        Ok(DeviceCapabilities {
+            host: self.host.id().name().to_string(),
+            device: AudioDeviceInfo {
+                is_default: default_cpal_device_id(&self.host, Direction::Input).as_deref()
+                    == Some(id.as_str()),
+                id,
+                name: device.name().unwrap_or_default(),
+                sample_rate: default.as_ref().map(|c| c.sample_rate().0),
+                channels: default.as_ref().map(|c| c.channels()),
+            },
+            configs,
+        })
+    }
+
+    /// List available input (microphone) devices with stable IDs
     pub fn list_devices(&self) -> Vec<AudioDeviceInfo> {
-        let default_name = self
-            .host
-            .default_input_device()
-            .and_then(|d| d.name().ok())
-            .unwrap_or_default();
+        let default_id = default_cpal_device_id(&self.host, Direction::Input);
 
-        match self.host.input_devices() {
+        match cpal_devices(&self.host, Direction::Input) {
             Ok(devices) => devices
-                .filter_map(|d| {
-                    let name = d.name().ok()?;
-                    Some(AudioDeviceInfo {
-                        id: name.clone(),
-                        is_default: name == default_name,
+                .into_iter()
+                .map(|(d, id, name)| {
+                    let config = d.default_input_config().ok();
+                    AudioDeviceInfo {
+                        is_default: default_id.as_deref() == Some(id.as_str()),
+                        id,
                         name,
-                    })
+                        sample_rate: config.as_ref().map(|c| c.sample_rate().0),
+                        channels: config.as_ref().map(|c| c.channels()),
+                    }
                 })
                 .collect(),
             Err(e) => {
@@ -48,84 +232,77 @@ impl MicCapture {
         }
     }
 
-    /// Get a device by name, or the default input device
-    pub fn get_device(&self, device_id: Option<&str>) -> Result<Device, String> {
+    /// Get a device by stable ID (or, for old settings, by name), or the
+    /// default input device. Returns the device together with its stable ID.
+    pub fn get_device(&self, device_id: Option<&str>) -> Result<(Device, String), String> {
         match device_id {
-            Some(id) => {
-                let devices = self
+            Some(id) => find_cpal_device(&self.host, Direction::Input, id)?
+                .ok_or_else(|| format!("Device '{}' not found", id)),
+            None => {
+                let device = self
                     .host
-                    .input_devices()
-                    .map_err(|e| format!("Failed to get devices: {}", e))?;
-                for device in devices {
-                    if device.name().ok().as_deref() == Some(id) {
-                        return Ok(device);
-                    }
-                }
-                Err(format!("Device '{}' not found", id))
+                    .default_input_device()
+                    .ok_or_else(|| "No default input device found".to_string())?;
+                let id = default_cpal_device_id(&self.host, Direction::Input)
+                    .unwrap_or_else(|| device.name().unwrap_or_default());
+                Ok((device, id))
             }
-            None => self
-                .host
-                .default_input_device()
-                .ok_or_else(|| "No default input device found".to_string()),
         }
     }
 
-    /// Start capturing from the given device. Sends AudioChunks over the channel.
+    /// Start capturing from the given device in the requested `format`.
+    /// Sends AudioChunks through `sender` and records stream errors in
+    /// `health`.
     /// Returns the Stream handle -- capture stops when this is dropped.
     pub fn start_capture(
         &self,
         device: &Device,
-        sender: Sender<AudioChunk>,
+        format: &CaptureFormat,
+        sender: AudioSender,
+        health: Arc<StreamHealth>,
     ) -> Result<(Stream, StreamConfig), String> {
-        let config = device
-            .default_input_config()
-            .map_err(|e| format!("Failed to get input config: {}", e))?;
-
-        let sample_rate = config.sample_rate().0;
-        let channels = config.channels();
-        let sample_format = config.sample_format();
+        let (stream_config, sample_format) = stream_config(device, format)?;
+        let sample_rate = stream_config.sample_rate.0;
+        let channels = stream_config.channels;
 
         info!(
-            "Mic capture: {} Hz, {} ch, {:?}",
-            sample_rate, channels, sample_format
+            "Mic capture: {} Hz, {} ch, {:?}, buffer {:?}",
+            sample_rate, channels, sample_format, stream_config.buffer_size
         );
 
-        let stream_config: StreamConfig = config.into();
         let sr = sample_rate;
         let ch = channels;
 
         let stream = match sample_format {
             SampleFormat::F32 => {
                 let sender = sender.clone();
+                let health_err = health.clone();
                 device.build_input_stream(
                     &stream_config,
-                    move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                        let _ = sender.try_send(AudioChunk {
-                            samples: data.to_vec(),
-                            sample_rate: sr,
-                            channels: ch,
-                        });
+                    move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                        sender.send_captured(data.to_vec(), sr, ch, cpal_capture_instant(info));
+                    },
+                    move |err| {
+                        error!("Mic stream error: {}", err);
+                        health_err.mark_error();
                     },
-                    |err| error!("Mic stream error: {}", err),
                     None,
                 )
             }
             SampleFormat::I16 => {
                 let sender = sender.clone();
+                let health_err = health.clone();
                 device.build_input_stream(
                     &stream_config,
-                    move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                        let samples: Vec<f32> = data
-                            .iter()
-                            .map(|&s| s as f32 / i16::MAX as f32)
-                            .collect();
-                        let _ = sender.try_send(AudioChunk {
-                            samples,
-                            sample_rate: sr,
-                            channels: ch,
-                        });
+                    move |data: &[i16], info: &cpal::InputCallbackInfo| {
+                        let samples: Vec<f32> =
+                            data.iter().map(|&s| s as f32 / i16::MAX as f32).collect();
+                        sender.send_captured(samples, sr, ch, cpal_capture_instant(info));
+                    },
+                    move |err| {
+                        error!("Mic stream error: {}", err);
+                        health_err.mark_error();
                     },
-                    |err| error!("Mic stream error: {}", err),
                     None,
                 )
             }
@@ -140,3 +317,63 @@ impl MicCapture {
         Ok((stream, stream_config))
     }
 }
+
+/// The stream config for `format`, or the device's default config when no
+/// sample rate or buffer size is requested.
+fn stream_config(
+    device: &Device,
+    format: &CaptureFormat,
+) -> Result<(StreamConfig, SampleFormat), String> {
+    let default = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to get input config: {}", e))?;
+    if format.sample_rate.is_none() && format.buffer_size.is_none() {
+        return Ok((default.config(), default.sample_format()));
+    }
+
+    // Only formats the capture callbacks handle
+    let ranges: Vec<SupportedStreamConfigRange> = device
+        .supported_input_configs()
+        .map_err(|e| format!("Failed to query supported configs: {}", e))?
+        .filter(|r| matches!(r.sample_format(), SampleFormat::F32 | SampleFormat::I16))
+        .collect();
+    let supported: Vec<SupportedCaptureConfig> = ranges.iter().map(supported_config).collect();
+    let chosen = choose_capture_config(
+        &device.name().unwrap_or_default(),
+        &supported,
+        default.channels(),
+        default.sample_rate().0,
+        format,
+    )?;
+
+    let range = ranges[chosen.index].clone();
+    if chosen.buffer_size.is_some() && matches!(range.buffer_size(), SupportedBufferSize::Unknown) {
+        warn!(
+            "Host reports no buffer size range; requesting {:?} anyway",
+            chosen.buffer_size
+        );
+    }
+    let sample_format = range.sample_format();
+    let mut config = range
+        .with_sample_rate(SampleRate(chosen.sample_rate))
+        .config();
+    if let Some(frames) = chosen.buffer_size {
+        config.buffer_size = BufferSize::Fixed(frames);
+    }
+    Ok((config, sample_format))
+}
+
+fn supported_config(range: &SupportedStreamConfigRange) -> SupportedCaptureConfig {
+    let (min_buffer_size, max_buffer_size) = match range.buffer_size() {
+        SupportedBufferSize::Range { min, max } => (Some(*min), Some(*max)),
+        SupportedBufferSize::Unknown => (None, None),
+    };
+    SupportedCaptureConfig {
+        channels: range.channels(),
+        min_sample_rate: range.min_sample_rate().0,
+        max_sample_rate: range.max_sample_rate().0,
+        min_buffer_size,
+        max_buffer_size,
+        sample_format: format!("{:?}", range.sample_format()).to_lowercase(),
+    }
+}
@@ -1,9 +1,22 @@
+pub mod capture_format;
+pub mod channel_map;
+pub mod device_id;
+pub mod device_monitor;
+pub mod external_input;
+pub mod file_decoder;
+pub mod meter;
 pub mod mic_capture;
 pub mod resampler;
+pub mod ring_buffer;
 pub mod system_capture;
+pub mod watchdog;
 
+#[cfg(target_os = "linux")]
+pub mod pulse;
 #[cfg(target_os = "linux")]
 pub mod system_linux;
+#[cfg(target_os = "linux")]
+pub mod system_pipewire;
 #[cfg(target_os = "windows")]
 pub mod system_windows;
 #[cfg(target_os = "macos")]
@@ -0,0 +1,400 @@
+use libpulse_binding as pulse;
+use pulse::callbacks::ListResult;
+use pulse::context::introspect::SinkInputInfo;
+use pulse::context::subscribe::{Facility, InterestMaskSet, Operation as SubscribeOperation};
+use pulse::context::{Context, FlagSet as ContextFlagSet, State as ContextState};
+use pulse::mainloop::standard::{IterateResult, Mainloop};
+use pulse::operation::{Operation, State as OperationState};
+use pulse::proplist::properties;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// A PulseAudio source that monitors a sink (i.e. "what you hear" on that output).
+#[derive(Debug, Clone)]
+pub struct MonitorSource {
+    /// Pulse source name, e.g. `alsa_output.pci-0000_00_1f.3.analog-stereo.monitor`.
+    /// This is what `pa_simple_new` expects as the device argument.
+    pub name: String,
+    /// Human readable description, e.g. "Monitor of Built-in Audio Analog Stereo".
+    pub description: String,
+    pub rate: u32,
+    pub channels: u8,
+    pub is_default: bool,
+}
+
+/// A client playback stream (Pulse "sink input").
+#[derive(Debug, Clone)]
+pub struct SinkInput {
+    pub index: u32,
+    /// Index of the sink the stream is currently playing to.
+    pub sink: u32,
+    pub application_name: Option<String>,
+    pub media_name: Option<String>,
+    pub process_id: Option<u32>,
+    pub binary: Option<String>,
+}
+
+/// A module loaded into the server.
+#[derive(Debug, Clone)]
+pub struct ModuleInfo {
+    pub index: u32,
+    pub name: String,
+    pub argument: String,
+}
+
+/// Blocking connection to the PulseAudio server for introspection queries.
+///
+/// libpulse's introspection API is asynchronous, so each query drives a
+/// standard (non-threaded) mainloop until its operation completes. This is
+/// only meant for short, infrequent calls such as device enumeration.
+pub struct PulseConnection {
+    mainloop: Mainloop,
+    context: Context,
+}
+
+impl PulseConnection {
+    pub fn connect() -> Result<Self, String> {
+        let mut mainloop = Mainloop::new().ok_or("Failed to create PulseAudio mainloop")?;
+        let mut context =
+            Context::new(&mainloop, "DualScribe").ok_or("Failed to create PulseAudio context")?;
+        context
+            .connect(None, ContextFlagSet::NOFLAGS, None)
+            .map_err(|e| format!("Failed to connect to PulseAudio: {}", e))?;
+
+        loop {
+            Self::iterate(&mut mainloop)?;
+            match context.get_state() {
+                ContextState::Ready => break,
+                ContextState::Failed | ContextState::Terminated => {
+                    return Err("PulseAudio context failed to become ready".to_string());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self { mainloop, context })
+    }
+
+    fn iterate(mainloop: &mut Mainloop) -> Result<(), String> {
+        match mainloop.iterate(true) {
+            IterateResult::Success(_) => Ok(()),
+            IterateResult::Quit(_) => Err("PulseAudio mainloop quit".to_string()),
+            IterateResult::Err(e) => Err(format!("PulseAudio mainloop error: {}", e)),
+        }
+    }
+
+    /// Drive the mainloop until `op` is no longer running.
+    fn wait_for<T: ?Sized>(&mut self, op: Operation<T>) -> Result<(), String> {
+        while op.get_state() == OperationState::Running {
+            Self::iterate(&mut self.mainloop)?;
+        }
+        match op.get_state() {
+            OperationState::Done => Ok(()),
+            _ => Err("PulseAudio operation was cancelled".to_string()),
+        }
+    }
+
+    /// Call `on_change` whenever a sink or source is added or removed, or the
+    /// server's defaults change.
+    pub fn subscribe_devices(
+        &mut self,
+        mut on_change: impl FnMut() + 'static,
+    ) -> Result<(), String> {
+        self.context
+            .set_subscribe_callback(Some(Box::new(move |facility, operation, _index| {
+                // Sinks and sources also report volume changes, which are not
+                // of interest
+                let relevant = match facility {
+                    Some(Facility::Sink | Facility::Source) => matches!(
+                        operation,
+                        Some(SubscribeOperation::New | SubscribeOperation::Removed)
+                    ),
+                    Some(Facility::Server) => true,
+                    _ => false,
+                };
+                if relevant {
+                    on_change();
+                }
+            })));
+        let ok = Rc::new(Cell::new(false));
+        let ok_clone = ok.clone();
+        let op = self.context.subscribe(
+            InterestMaskSet::SINK | InterestMaskSet::SOURCE | InterestMaskSet::SERVER,
+            move |success| ok_clone.set(success),
+        );
+        self.wait_for(op)?;
+        if ok.get() {
+            Ok(())
+        } else {
+            Err("Failed to subscribe to PulseAudio events".to_string())
+        }
+    }
+
+    /// Call `on_event` with the index of each sink input that is created,
+    /// and whether it was removed instead.
+    pub fn subscribe_sink_inputs(
+        &mut self,
+        mut on_event: impl FnMut(u32, bool) + 'static,
+    ) -> Result<(), String> {
+        self.context.set_subscribe_callback(Some(Box::new(
+            move |facility, operation, index| match (facility, operation) {
+                (Some(Facility::SinkInput), Some(SubscribeOperation::New)) => {
+                    on_event(index, false)
+                }
+                (Some(Facility::SinkInput), Some(SubscribeOperation::Removed)) => {
+                    on_event(index, true)
+                }
+                _ => {}
+            },
+        )));
+        let ok = Rc::new(Cell::new(false));
+        let ok_clone = ok.clone();
+        let op = self
+            .context
+            .subscribe(InterestMaskSet::SINK_INPUT, move |success| {
+                ok_clone.set(success)
+            });
+        self.wait_for(op)?;
+        if ok.get() {
+            Ok(())
+        } else {
+            Err("Failed to subscribe to PulseAudio events".to_string())
+        }
+    }
+
+    /// Dispatch events until the connection fails.
+    pub fn run(&mut self) -> Result<(), String> {
+        loop {
+            Self::iterate(&mut self.mainloop)?;
+        }
+    }
+
+    /// Dispatch the events that have arrived, without waiting for more.
+    pub fn dispatch(&mut self) -> Result<(), String> {
+        loop {
+            match self.mainloop.iterate(false) {
+                IterateResult::Success(0) => return Ok(()),
+                IterateResult::Success(_) => {}
+                IterateResult::Quit(_) => return Err("PulseAudio mainloop quit".to_string()),
+                IterateResult::Err(e) => return Err(format!("PulseAudio mainloop error: {}", e)),
+            }
+        }
+    }
+
+    /// Name of the server's default sink, if any.
+    pub fn default_sink_name(&mut self) -> Result<Option<String>, String> {
+        let result = Rc::new(RefCell::new(None));
+        let result_clone = result.clone();
+        let op = self.context.introspect().get_server_info(move |info| {
+            *result_clone.borrow_mut() = info.default_sink_name.as_ref().map(|n| n.to_string());
+        });
+        self.wait_for(op)?;
+        let name = result.borrow_mut().take();
+        Ok(name)
+    }
+
+    /// All monitor sources, one per sink. The monitor of the default sink is
+    /// flagged `is_default`.
+    pub fn monitor_sources(&mut self) -> Result<Vec<MonitorSource>, String> {
+        let default_monitor = self.default_sink_name()?.map(|s| format!("{}.monitor", s));
+
+        let sources = Rc::new(RefCell::new(Vec::new()));
+        let sources_clone = sources.clone();
+        let op = self
+            .context
+            .introspect()
+            .get_source_info_list(move |result| {
+                if let ListResult::Item(info) = result {
+                    if info.monitor_of_sink.is_none() {
+                        return;
+                    }
+                    let Some(name) = info.name.as_ref().map(|n| n.to_string()) else {
+                        return;
+                    };
+                    let description = info
+                        .description
+                        .as_ref()
+                        .map(|d| d.to_string())
+                        .unwrap_or_else(|| name.clone());
+                    sources_clone.borrow_mut().push(MonitorSource {
+                        description,
+                        rate: info.sample_spec.rate,
+                        channels: info.sample_spec.channels,
+                        is_default: false,
+                        name,
+                    });
+                }
+            });
+        self.wait_for(op)?;
+
+        let mut sources = sources.take();
+        for source in &mut sources {
+            source.is_default = default_monitor.as_deref() == Some(source.name.as_str());
+        }
+        Ok(sources)
+    }
+
+    /// All sink inputs that belong to a client application.
+    pub fn sink_inputs(&mut self) -> Result<Vec<SinkInput>, String> {
+        let inputs = Rc::new(RefCell::new(Vec::new()));
+        let inputs_clone = inputs.clone();
+        let op = self
+            .context
+            .introspect()
+            .get_sink_input_info_list(move |result| {
+                if let ListResult::Item(info) = result {
+                    inputs_clone.borrow_mut().extend(to_sink_input(info));
+                }
+            });
+        self.wait_for(op)?;
+        Ok(inputs.take())
+    }
+
+    /// The sink input with the given index, if it exists and belongs to a
+    /// client application.
+    pub fn sink_input(&mut self, index: u32) -> Result<Option<SinkInput>, String> {
+        let result = Rc::new(RefCell::new(None));
+        let result_clone = result.clone();
+        let op = self
+            .context
+            .introspect()
+            .get_sink_input_info(index, move |result| {
+                if let ListResult::Item(info) = result {
+                    *result_clone.borrow_mut() = to_sink_input(info);
+                }
+            });
+        self.wait_for(op)?;
+        let input = result.borrow_mut().take();
+        Ok(input)
+    }
+
+    /// Name of the sink with the given index.
+    pub fn sink_name(&mut self, index: u32) -> Result<Option<String>, String> {
+        let result = Rc::new(RefCell::new(None));
+        let result_clone = result.clone();
+        let op = self
+            .context
+            .introspect()
+            .get_sink_info_by_index(index, move |result| {
+                if let ListResult::Item(info) = result {
+                    *result_clone.borrow_mut() = info.name.as_ref().map(|n| n.to_string());
+                }
+            });
+        self.wait_for(op)?;
+        let name = result.borrow_mut().take();
+        Ok(name)
+    }
+
+    /// All modules loaded into the server.
+    pub fn modules(&mut self) -> Result<Vec<ModuleInfo>, String> {
+        let modules = Rc::new(RefCell::new(Vec::new()));
+        let modules_clone = modules.clone();
+        let op = self
+            .context
+            .introspect()
+            .get_module_info_list(move |result| {
+                if let ListResult::Item(info) = result {
+                    modules_clone.borrow_mut().push(ModuleInfo {
+                        index: info.index,
+                        name: info.name.as_deref().unwrap_or_default().to_string(),
+                        argument: info.argument.as_deref().unwrap_or_default().to_string(),
+                    });
+                }
+            });
+        self.wait_for(op)?;
+        Ok(modules.take())
+    }
+
+    /// Load a server module and return its index.
+    pub fn load_module(&mut self, name: &str, argument: &str) -> Result<u32, String> {
+        let index = Rc::new(Cell::new(u32::MAX));
+        let index_clone = index.clone();
+        let op = self
+            .context
+            .introspect()
+            .load_module(name, argument, move |idx| index_clone.set(idx));
+        self.wait_for(op)?;
+        match index.get() {
+            u32::MAX => Err(format!("Failed to load {} ({})", name, argument)),
+            idx => Ok(idx),
+        }
+    }
+
+    pub fn unload_module(&mut self, index: u32) -> Result<(), String> {
+        let ok = Rc::new(Cell::new(false));
+        let ok_clone = ok.clone();
+        let op = self
+            .context
+            .introspect()
+            .unload_module(index, move |success| ok_clone.set(success));
+        self.wait_for(op)?;
+        if ok.get() {
+            Ok(())
+        } else {
+            Err(format!("Failed to unload module {}", index))
+        }
+    }
+
+    pub fn move_sink_input_to_name(&mut self, index: u32, sink_name: &str) -> Result<(), String> {
+        let ok = Rc::new(Cell::new(false));
+        let ok_clone = ok.clone();
+        let op = self.context.introspect().move_sink_input_by_name(
+            index,
+            sink_name,
+            Some(Box::new(move |success| ok_clone.set(success))),
+        );
+        self.wait_for(op)?;
+        if ok.get() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Failed to move sink input {} to '{}'",
+                index, sink_name
+            ))
+        }
+    }
+
+    pub fn move_sink_input_to_index(&mut self, index: u32, sink_index: u32) -> Result<(), String> {
+        let ok = Rc::new(Cell::new(false));
+        let ok_clone = ok.clone();
+        let op = self.context.introspect().move_sink_input_by_index(
+            index,
+            sink_index,
+            Some(Box::new(move |success| ok_clone.set(success))),
+        );
+        self.wait_for(op)?;
+        if ok.get() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Failed to move sink input {} to sink {}",
+                index, sink_index
+            ))
+        }
+    }
+}
+
+/// Streams created by modules (loopbacks, combine sinks) have no client and
+/// are left out.
+fn to_sink_input(info: &SinkInputInfo) -> Option<SinkInput> {
+    info.client?;
+    Some(SinkInput {
+        index: info.index,
+        sink: info.sink,
+        application_name: info.proplist.get_str(properties::APPLICATION_NAME),
+        media_name: info.proplist.get_str(properties::MEDIA_NAME),
+        process_id: info
+            .proplist
+            .get_str(properties::APPLICATION_PROCESS_ID)
+            .and_then(|p| p.parse().ok()),
+        binary: info
+            .proplist
+            .get_str(properties::APPLICATION_PROCESS_BINARY),
+    })
+}
+
+impl Drop for PulseConnection {
+    fn drop(&mut self) {
+        self.context.disconnect();
+    }
+}
@@ -1,11 +1,18 @@
 use rubato::{
     Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
 };
+use std::time::{Duration, Instant};
 
 pub struct AudioResampler {
     resampler: Option<SincFixedIn<f32>>,
+    input_rate: u32,
+    output_rate: u32,
     input_channels: usize,
     chunk_size: usize,
+    /// Mono frames short of a full chunk, resampled with the next input.
+    pending: Vec<f32>,
+    /// When the first pending frame was captured.
+    pending_at: Option<Instant>,
 }
 
 impl AudioResampler {
@@ -13,8 +20,12 @@ impl AudioResampler {
         if input_rate == output_rate && channels == 1 {
             return Ok(Self {
                 resampler: None,
+                input_rate,
+                output_rate,
                 input_channels: channels,
                 chunk_size: 0,
+                pending: Vec::new(),
+                pending_at: None,
             });
         }
 
@@ -38,13 +49,23 @@ impl AudioResampler {
 
         Ok(Self {
             resampler: Some(resampler),
+            input_rate,
+            output_rate,
             input_channels: channels,
             chunk_size,
+            pending: Vec::new(),
+            pending_at: None,
         })
     }
 
+    /// Whether this resampler was built for the given input format.
+    pub fn accepts(&self, input_rate: u32, channels: usize) -> bool {
+        self.input_rate == input_rate && self.input_channels == channels
+    }
+
     /// Process interleaved audio samples. If multi-channel, mixes down to mono first.
-    /// Returns resampled mono f32 samples.
+    /// Returns resampled mono f32 samples. Frames short of a full resampler
+    /// chunk are held back until the next call.
     pub fn process(&mut self, input: &[f32]) -> Result<Vec<f32>, String> {
         // Mix to mono if needed
         let mono: Vec<f32> = if self.input_channels > 1 {
@@ -59,24 +80,55 @@ impl AudioResampler {
         match &mut self.resampler {
             None => Ok(mono), // passthrough: same rate, already mono
             Some(resampler) => {
+                self.pending.extend_from_slice(&mono);
+                let full = self.pending.len() - self.pending.len() % self.chunk_size;
                 let mut output = Vec::new();
-                // Process full chunks
-                for chunk in mono.chunks(self.chunk_size) {
-                    if chunk.len() == self.chunk_size {
-                        // rubato expects Vec<Vec<f32>> — one inner vec per channel
-                        let input_buf = vec![chunk.to_vec()];
-                        let result = resampler
-                            .process(&input_buf, None)
-                            .map_err(|e| format!("Resample error: {}", e))?;
-                        output.extend_from_slice(&result[0]);
-                    }
-                    // Partial chunks (< chunk_size) are skipped for now.
-                    // In streaming usage, the continuous audio stream provides full chunks.
+                for chunk in self.pending[..full].chunks_exact(self.chunk_size) {
+                    // rubato expects one slice per channel
+                    let result = resampler
+                        .process(&[chunk], None)
+                        .map_err(|e| format!("Resample error: {}", e))?;
+                    output.extend_from_slice(&result[0]);
                 }
+                self.pending.drain(..full);
                 Ok(output)
             }
         }
     }
+
+    /// Like `process`, given when the first frame of `input` was captured,
+    /// and also returning when the first output frame was. Accounts for the
+    /// frames held back from earlier input and the resampler's filter delay.
+    pub fn process_captured(
+        &mut self,
+        input: &[f32],
+        captured_at: Instant,
+    ) -> Result<(Vec<f32>, Instant), String> {
+        let held = self.pending.len();
+        let first = match self.pending_at {
+            Some(at) if held > 0 => at,
+            _ => captured_at,
+        };
+        let output = self.process(input)?;
+
+        // Where the frames now held back start, counted from the first held
+        // before this call
+        let frames = held + input.len() / self.input_channels.max(1);
+        let next = frames - self.pending.len();
+        self.pending_at = Some(if next < held {
+            first + frames_duration(next, self.input_rate)
+        } else {
+            captured_at + frames_duration(next - held, self.input_rate)
+        });
+
+        let delay = self.resampler.as_ref().map_or(0, |r| r.output_delay());
+        let delay = frames_duration(delay, self.output_rate);
+        Ok((output, first.checked_sub(delay).unwrap_or(first)))
+    }
+}
+
+fn frames_duration(frames: usize, rate: u32) -> Duration {
+    Duration::from_secs_f64(frames as f64 / rate.max(1) as f64)
 }
 
 /// Convert f32 samples (-1.0..1.0) to i16 Linear16 for Deepgram.
@@ -136,6 +188,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resampler_keeps_partial_chunks_and_their_timing() {
+        let mut resampler = AudioResampler::new(48000, 16000, 1).unwrap();
+        let start = Instant::now();
+        let delay = frames_duration(resampler.resampler.as_ref().unwrap().output_delay(), 16000);
+
+        // 300 frames are short of a chunk and held back
+        let (output, _) = resampler.process_captured(&[0.1; 300], start).unwrap();
+        assert!(output.is_empty());
+        let later = start + frames_duration(300, 48000);
+        let (output, first) = resampler.process_captured(&[0.1; 300], later).unwrap();
+        assert!(!output.is_empty());
+        // The output starts with the held frames, less the filter delay
+        assert_eq!(first, start - delay);
+        assert_eq!(resampler.pending.len(), 120);
+        assert_eq!(
+            resampler.pending_at,
+            Some(start + frames_duration(480, 48000))
+        );
+    }
+
+    #[test]
+    fn test_resampler_accepts_only_its_input_format() {
+        let resampler = AudioResampler::new(48000, 16000, 2).unwrap();
+        assert!(resampler.accepts(48000, 2));
+        assert!(!resampler.accepts(44100, 2));
+        assert!(!resampler.accepts(48000, 1));
+    }
+
     #[test]
     fn test_to_linear16() {
         let samples = vec![0.0f32, 0.5, -0.5, 1.0, -1.0];
@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+
+use crate::audio::resampler::to_linear16;
+
+/// Seconds of audio kept per source when a source does not configure it.
+pub const DEFAULT_REWIND_SECS: u64 = 60;
+
+/// A stretch of captured audio at its original rate and channel count.
+#[derive(Debug, Clone)]
+pub struct AudioClip {
+    /// Session time of the first frame.
+    pub start: f64,
+    /// Interleaved 16-bit samples.
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioClip {
+    pub fn duration(&self) -> f64 {
+        frames(self.samples.len(), self.channels) as f64 / self.sample_rate as f64
+    }
+
+    /// Session time just past the clip's last frame.
+    pub fn end(&self) -> f64 {
+        self.start + self.duration()
+    }
+
+    /// Encode as a 16-bit PCM WAV file.
+    pub fn to_wav(&self) -> Vec<u8> {
+        let data_len = (self.samples.len() * 2) as u32;
+        let block_align = self.channels * 2;
+        let mut bytes = Vec::with_capacity(44 + data_len as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&self.channels.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(self.sample_rate * block_align as u32).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in &self.samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+fn frames(samples: usize, channels: u16) -> usize {
+    samples / channels.max(1) as usize
+}
+
+/// The last few seconds of one source's captured audio, so a range can be
+/// replayed or transcribed again.
+///
+/// Chunks are stored as they were captured and placed on the session
+/// timeline by their capture time. Audio that was never captured, e.g. while
+/// the source was paused, comes back as silence.
+#[derive(Debug)]
+pub struct AudioRingBuffer {
+    /// Seconds of audio to keep; 0 disables buffering.
+    capacity: f64,
+    chunks: VecDeque<AudioClip>,
+}
+
+impl AudioRingBuffer {
+    pub fn new(capacity_secs: u64) -> Self {
+        Self {
+            capacity: capacity_secs as f64,
+            chunks: VecDeque::new(),
+        }
+    }
+
+    /// Add a chunk of interleaved f32 samples whose first frame was captured
+    /// at session time `start`, evicting audio older than the capacity.
+    pub fn push(&mut self, samples: &[f32], sample_rate: u32, channels: u16, start: f64) {
+        if self.capacity <= 0.0 || samples.is_empty() || sample_rate == 0 {
+            return;
+        }
+        let chunk = AudioClip {
+            start,
+            samples: to_linear16(samples),
+            sample_rate,
+            channels: channels.max(1),
+        };
+        let oldest = start + chunk.duration() - self.capacity;
+        self.chunks.push_back(chunk);
+        while self
+            .chunks
+            .front()
+            .is_some_and(|c| c.start + c.duration() <= oldest)
+        {
+            self.chunks.pop_front();
+        }
+    }
+
+    /// Audio between session times `start` and `end`, trimmed to what is
+    /// still buffered. Chunks in a different format than the most recent
+    /// one in the range (e.g. from before a device switch) are left out.
+    pub fn clip(&self, start: f64, end: f64) -> Option<AudioClip> {
+        let in_range: Vec<&AudioClip> = self
+            .chunks
+            .iter()
+            .filter(|c| c.start < end && c.start + c.duration() > start)
+            .collect();
+        let latest = in_range.last()?;
+        let (sample_rate, channels) = (latest.sample_rate, latest.channels);
+        let in_format = in_range
+            .iter()
+            .filter(|c| c.sample_rate == sample_rate && c.channels == channels);
+
+        let first = in_format.clone().next()?;
+        let clip_start = start.max(first.start);
+        let clip_end = end.min(latest.start + latest.duration());
+        let rate = sample_rate as f64;
+        let total_frames = ((clip_end - clip_start) * rate).round() as usize;
+        if total_frames == 0 {
+            return None;
+        }
+
+        let channels_usize = channels as usize;
+        let mut samples = vec![0i16; total_frames * channels_usize];
+        for chunk in in_format {
+            let offset = ((chunk.start - clip_start) * rate).round() as i64;
+            for (i, frame) in chunk.samples.chunks(channels_usize).enumerate() {
+                let Ok(at) = usize::try_from(offset + i as i64) else {
+                    continue;
+                };
+                if at >= total_frames {
+                    break;
+                }
+                samples[at * channels_usize..(at + 1) * channels_usize].copy_from_slice(frame);
+            }
+        }
+        Some(AudioClip {
+            start: clip_start,
+            samples,
+            sample_rate,
+            channels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::file_decoder::FileDecoder;
+
+    /// 100ms of a constant value at 1 kHz mono.
+    fn tone(value: f32) -> Vec<f32> {
+        vec![value; 100]
+    }
+
+    #[test]
+    fn test_evicts_audio_older_than_capacity() {
+        let mut buffer = AudioRingBuffer::new(1);
+        for i in 0..30 {
+            buffer.push(&tone(0.5), 1000, 1, i as f64 * 0.1);
+        }
+        // 3 seconds were pushed, the last second is kept
+        let clip = buffer.clip(0.0, 10.0).unwrap();
+        assert!((clip.start - 2.0).abs() < 1e-9);
+        assert!((clip.duration() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_clip_fills_gaps_with_silence() {
+        let mut buffer = AudioRingBuffer::new(60);
+        buffer.push(&tone(0.5), 1000, 1, 0.0);
+        // Paused between 0.1s and 0.3s
+        buffer.push(&tone(0.5), 1000, 1, 0.3);
+
+        let clip = buffer.clip(0.05, 0.35).unwrap();
+        assert_eq!(clip.samples.len(), 300);
+        assert!(clip.samples[..50].iter().all(|&s| s == 16383));
+        assert!(clip.samples[50..250].iter().all(|&s| s == 0));
+        assert!(clip.samples[250..].iter().all(|&s| s == 16383));
+    }
+
+    #[test]
+    fn test_clip_skips_other_formats() {
+        let mut buffer = AudioRingBuffer::new(60);
+        buffer.push(&tone(0.5), 1000, 1, 0.0);
+        // Device switched to stereo at a different rate
+        buffer.push(&[0.25; 400], 2000, 2, 0.1);
+
+        let clip = buffer.clip(0.0, 0.2).unwrap();
+        assert_eq!((clip.sample_rate, clip.channels), (2000, 2));
+        assert!((clip.start - 0.1).abs() < 1e-9);
+        assert_eq!(clip.samples.len(), 400);
+        assert!(buffer.clip(5.0, 6.0).is_none());
+        assert!(AudioRingBuffer::new(0).clip(0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_wav_round_trip() {
+        let mut buffer = AudioRingBuffer::new(60);
+        let stereo: Vec<f32> = (0..800).flat_map(|_| [0.0, 0.5]).collect();
+        buffer.push(&stereo, 8000, 2, 0.0);
+        let clip = buffer.clip(0.0, 0.1).unwrap();
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("clip.wav");
+        std::fs::write(&path, clip.to_wav()).unwrap();
+        let mut decoder = FileDecoder::open(&path).unwrap();
+        assert_eq!(decoder.channels, Some(2));
+        assert_eq!(decoder.duration, Some(0.1));
+        let chunk = decoder.next_chunk().unwrap().unwrap();
+        assert_eq!(chunk.sample_rate, 8000);
+        assert!(chunk
+            .samples
+            .chunks(2)
+            .all(|f| f[0] == 0.0 && (f[1] - 0.5).abs() < 1e-3));
+    }
+}
@@ -1,25 +1,64 @@
-use crossbeam_channel::Sender;
 
-use crate::audio::mic_capture::AudioChunk;
-use crate::transcription::types::AudioDeviceInfo;
+use crate::audio::mic_capture::AudioSender;
+use crate::transcription::types::{AudioApplicationInfo, AudioDeviceInfo};
 
 pub trait SystemAudioCapture: Send {
     fn list_devices(&self) -> Vec<AudioDeviceInfo>;
     fn start_capture(
         &self,
         device_id: Option<&str>,
-        sender: Sender<AudioChunk>,
+        sender: AudioSender,
     ) -> Result<Box<dyn CaptureHandle>, String>;
+
+    /// Applications currently playing audio, for per-application capture.
+    fn list_applications(&self) -> Vec<AudioApplicationInfo> {
+        vec![]
+    }
+
+    /// Capture only the given applications (IDs from `list_applications`)
+    /// instead of a whole output device. Any routing changes made to do this
+    /// are undone when the returned handle is stopped.
+    fn start_app_capture(
+        &self,
+        _app_ids: &[String],
+        _sender: AudioSender,
+    ) -> Result<Box<dyn CaptureHandle>, String> {
+        Err("Per-application capture is not supported on this platform".to_string())
+    }
 }
 
 pub trait CaptureHandle: Send {
+    /// Stop capturing. Backends that reroute audio undo it before returning.
     fn stop(&self);
+
+    /// The device actually being captured, if the requested one was unavailable
+    /// and the backend fell back to another device.
+    fn fallback_device(&self) -> Option<AudioDeviceInfo> {
+        None
+    }
+
+    /// False once capture has ended on its own, e.g. after a device error.
+    fn is_running(&self) -> bool {
+        true
+    }
+
+    /// True if the backend delivers no data at all (rather than silence)
+    /// while nothing is playing, so a quiet period is not a stall.
+    fn idles_when_silent(&self) -> bool {
+        false
+    }
 }
 
 pub fn create_system_capture() -> Box<dyn SystemAudioCapture> {
     #[cfg(target_os = "linux")]
     {
-        Box::new(super::system_linux::LinuxSystemCapture::new())
+        // Prefer native PipeWire; the PulseAudio path also covers pipewire-pulse
+        // and plain PulseAudio systems.
+        if super::system_pipewire::is_available() {
+            Box::new(super::system_pipewire::PipeWireSystemCapture::new())
+        } else {
+            Box::new(super::system_linux::LinuxSystemCapture::new())
+        }
     }
     #[cfg(target_os = "windows")]
     {
@@ -1,14 +1,33 @@
-use crossbeam_channel::Sender;
 use libpulse_binding as pulse;
 use libpulse_simple_binding as psimple;
-use log::{error, info};
-use std::sync::atomic::{AtomicBool, Ordering};
+use log::{error, info, warn};
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
-use std::thread;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use crate::audio::mic_capture::AudioChunk;
+use crate::audio::device_id::{make_id, split_id, PIPEWIRE, PULSE};
+use crate::audio::mic_capture::{instant_before, AudioSender};
+use crate::audio::pulse::{MonitorSource, PulseConnection, SinkInput};
 use crate::audio::system_capture::{CaptureHandle, SystemAudioCapture};
-use crate::transcription::types::AudioDeviceInfo;
+use crate::transcription::types::{AudioApplicationInfo, AudioDeviceInfo};
+
+/// Legacy ID from before real enumeration; still accepted as "use the default".
+pub(crate) const LEGACY_DEFAULT_ID: &str = "default_monitor";
+
+/// Used only when the server can't be introspected at all.
+const FALLBACK_RATE: u32 = 44100;
+const FALLBACK_CHANNELS: u8 = 1;
+
+/// How long `stop` waits for the recording thread, which may be blocked in
+/// a read from a suspended monitor.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the routing thread checks for new streams of the selected apps.
+const ROUTING_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct LinuxSystemCapture;
 
@@ -16,96 +35,651 @@ impl LinuxSystemCapture {
     pub fn new() -> Self {
         Self
     }
+
+    /// Pick the monitor source to open for `device_id`. Falls back to the
+    /// default sink's monitor when the requested one no longer exists; the
+    /// returned flag is true in that case.
+    fn resolve_source(device_id: Option<&str>) -> Result<(MonitorSource, bool), String> {
+        let sources = PulseConnection::connect()?.monitor_sources()?;
+
+        let mut fell_back = false;
+        if let Some(id) = device_id.filter(|id| *id != LEGACY_DEFAULT_ID) {
+            if let Some(source) = sources.iter().find(|s| source_matches(s, id)) {
+                return Ok((source.clone(), false));
+            }
+            warn!(
+                "Monitor source '{}' not found, falling back to default monitor",
+                id
+            );
+            fell_back = true;
+        }
+
+        sources
+            .iter()
+            .find(|s| s.is_default)
+            .or_else(|| sources.first())
+            .cloned()
+            .map(|s| (s, fell_back))
+            .ok_or_else(|| "No PulseAudio monitor sources available".to_string())
+    }
+}
+
+/// Match a monitor source against a stable ID. PipeWire IDs name the sink
+/// node, whose monitor pipewire-pulse exposes as `<node>.monitor`. Legacy
+/// IDs are bare source names or descriptions.
+fn source_matches(source: &MonitorSource, id: &str) -> bool {
+    match split_id(id) {
+        Some((PULSE, name)) => source.name == name,
+        Some((PIPEWIRE, node)) => source.name == format!("{}.monitor", node),
+        Some(_) => false,
+        None => source.name == id || source.description == id,
+    }
+}
+
+fn to_device_info(source: &MonitorSource) -> AudioDeviceInfo {
+    AudioDeviceInfo {
+        name: source.description.clone(),
+        id: make_id(PULSE, &source.name),
+        is_default: source.is_default,
+        sample_rate: Some(source.rate),
+        channels: Some(source.channels as u16),
+    }
 }
 
 struct LinuxCaptureHandle {
     stop_flag: Arc<AtomicBool>,
+    /// Cleared when the recording thread exits, e.g. on a read error.
+    running: Arc<AtomicBool>,
+    fallback_device: Option<AudioDeviceInfo>,
 }
 
 impl CaptureHandle for LinuxCaptureHandle {
     fn stop(&self) {
         self.stop_flag.store(true, Ordering::Relaxed);
+        // The thread undoes app routing on exit; wait for it so a restart
+        // doesn't set up routing while the old one is still in place.
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while self.is_running() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn fallback_device(&self) -> Option<AudioDeviceInfo> {
+        self.fallback_device.clone()
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+}
+
+/// ID of the application playing `input`. Sink input indexes change
+/// whenever an app opens a new stream, e.g. for the next call, so the ID
+/// names the app itself and still matches after it is restarted.
+fn app_id(input: &SinkInput) -> String {
+    if let Some(binary) = &input.binary {
+        format!("binary:{}", binary)
+    } else if let Some(name) = &input.application_name {
+        format!("name:{}", name)
+    } else if let Some(pid) = input.process_id {
+        format!("pid:{}", pid)
+    } else {
+        format!("stream:{}", input.index)
+    }
+}
+
+impl From<SinkInput> for AudioApplicationInfo {
+    fn from(input: SinkInput) -> Self {
+        let id = app_id(&input);
+        let name = input
+            .application_name
+            .or_else(|| input.binary.clone())
+            .unwrap_or_else(|| format!("Stream {}", input.index));
+        Self {
+            id,
+            name,
+            media_name: input.media_name,
+            process_id: input.process_id,
+            binary: input.binary,
+        }
     }
 }
 
 impl SystemAudioCapture for LinuxSystemCapture {
     fn list_devices(&self) -> Vec<AudioDeviceInfo> {
-        vec![AudioDeviceInfo {
-            name: "Default System Audio".to_string(),
-            id: "default_monitor".to_string(),
-            is_default: true,
-        }]
+        match PulseConnection::connect().and_then(|mut c| c.monitor_sources()) {
+            Ok(sources) => sources.iter().map(to_device_info).collect(),
+            Err(e) => {
+                error!("Failed to enumerate PulseAudio monitor sources: {}", e);
+                vec![AudioDeviceInfo {
+                    name: "Default System Audio".to_string(),
+                    id: LEGACY_DEFAULT_ID.to_string(),
+                    is_default: true,
+                    sample_rate: None,
+                    channels: None,
+                }]
+            }
+        }
     }
 
     fn start_capture(
         &self,
-        _device_id: Option<&str>,
-        sender: Sender<AudioChunk>,
+        device_id: Option<&str>,
+        sender: AudioSender,
+    ) -> Result<Box<dyn CaptureHandle>, String> {
+        let (source, fell_back) = match Self::resolve_source(device_id) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("{}; opening @DEFAULT_MONITOR@ with a fixed format", e);
+                let source = MonitorSource {
+                    name: "@DEFAULT_MONITOR@".to_string(),
+                    description: "Default System Audio".to_string(),
+                    rate: FALLBACK_RATE,
+                    channels: FALLBACK_CHANNELS,
+                    is_default: true,
+                };
+                (source, device_id.is_some_and(|id| id != LEGACY_DEFAULT_ID))
+            }
+        };
+
+        let fallback_device = fell_back.then(|| to_device_info(&source));
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let running = record_monitor(source, sender, stop_flag.clone(), None)?;
+
+        Ok(Box::new(LinuxCaptureHandle {
+            stop_flag,
+            running,
+            fallback_device,
+        }))
+    }
+
+    fn list_applications(&self) -> Vec<AudioApplicationInfo> {
+        match PulseConnection::connect().and_then(|mut c| c.sink_inputs()) {
+            Ok(inputs) => {
+                // One entry per app, however many streams it plays
+                let mut apps: Vec<AudioApplicationInfo> = Vec::new();
+                for app in inputs.into_iter().map(AudioApplicationInfo::from) {
+                    if !apps.iter().any(|a| a.id == app.id) {
+                        apps.push(app);
+                    }
+                }
+                apps
+            }
+            Err(e) => {
+                error!("Failed to enumerate PulseAudio sink inputs: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    fn start_app_capture(
+        &self,
+        app_ids: &[String],
+        sender: AudioSender,
     ) -> Result<Box<dyn CaptureHandle>, String> {
+        let (routing, source) = RoutingThread::start(app_ids)?;
         let stop_flag = Arc::new(AtomicBool::new(false));
-        let stop_clone = stop_flag.clone();
+        let running = record_monitor(source, sender, stop_flag.clone(), Some(routing))?;
 
-        let spec = pulse::sample::Spec {
-            format: pulse::sample::Format::F32le,
-            channels: 1,
-            rate: 44100,
-        };
+        Ok(Box::new(LinuxCaptureHandle {
+            stop_flag,
+            running,
+            fallback_device: None,
+        }))
+    }
+}
 
-        let attr = pulse::def::BufferAttr {
-            maxlength: u32::MAX,
-            tlength: u32::MAX,
-            prebuf: u32::MAX,
-            minreq: u32::MAX,
-            fragsize: 4410 * 4, // 100ms at 44100Hz, 4 bytes per f32
-        };
+/// Prefix of the capture sinks, followed by the app's process ID.
+const SINK_PREFIX: &str = "dualscribe_capture_";
+
+/// Tells apart the routings of one process, e.g. while a restarted capture
+/// sets up its own before the old one is removed.
+static NEXT_ROUTING: AtomicU32 = AtomicU32::new(0);
+
+/// Keeps the selected apps routed while capture runs: streams they open
+/// later are moved onto the capture sinks as they appear. Stopped, and the
+/// routing undone, when dropped.
+struct RoutingThread {
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RoutingThread {
+    fn start(app_ids: &[String]) -> Result<(Self, MonitorSource), String> {
+        let app_ids = app_ids.to_vec();
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let stop = stop_flag.clone();
+        let (result_tx, result_rx) = crossbeam_channel::bounded(1);
 
-        thread::spawn(move || {
-            let simple = match psimple::Simple::new(
-                None,
-                "DualScribe",
-                pulse::stream::Direction::Record,
-                Some("@DEFAULT_MONITOR@"),
-                "system-audio-capture",
-                &spec,
-                None,
-                Some(&attr),
-            ) {
-                Ok(s) => s,
+        // Pulse connections can't leave the thread that made them
+        let thread = thread::spawn(move || {
+            let setup = PulseConnection::connect().and_then(|mut conn| {
+                // Subscribed first so no stream opened during setup is missed
+                let events = Rc::new(RefCell::new(Vec::new()));
+                let events_clone = events.clone();
+                conn.subscribe_sink_inputs(move |index, removed| {
+                    events_clone.borrow_mut().push((index, removed))
+                })?;
+                let (routing, source) = AppRouting::setup(&mut conn, app_ids)?;
+                Ok((conn, events, routing, source))
+            });
+            let (mut conn, events, mut routing) = match setup {
+                Ok((conn, events, routing, source)) => {
+                    let _ = result_tx.send(Ok(source));
+                    (conn, events, routing)
+                }
                 Err(e) => {
-                    error!("Failed to open PulseAudio monitor: {}", e);
+                    let _ = result_tx.send(Err(e));
                     return;
                 }
             };
 
-            info!("Linux system audio capture started");
+            // Without events the routing stays as it is until stopped
+            let mut following = true;
+            while !stop.load(Ordering::Relaxed) {
+                if following {
+                    match conn.dispatch() {
+                        Ok(()) => {
+                            for (index, removed) in events.take() {
+                                if removed {
+                                    routing.forget(index);
+                                } else {
+                                    routing.follow(&mut conn, index);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Stopped routing new application streams: {}", e);
+                            following = false;
+                        }
+                    }
+                }
+                thread::sleep(ROUTING_POLL_INTERVAL);
+            }
+        });
 
-            let chunk_samples = 4410; // 100ms at 44100Hz mono
-            let mut buf = vec![0u8; chunk_samples * 4]; // f32 = 4 bytes
+        let source = result_rx
+            .recv()
+            .map_err(|e| format!("PulseAudio channel error: {}", e))?;
+        let routing = Self {
+            stop_flag,
+            thread: Some(thread),
+        };
+        Ok((routing, source?))
+    }
+}
 
-            while !stop_clone.load(Ordering::Relaxed) {
-                match simple.read(&mut buf) {
-                    Ok(()) => {
-                        let samples: Vec<f32> = buf
-                            .chunks_exact(4)
-                            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
-                            .collect();
+impl Drop for RoutingThread {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
 
-                        let _ = sender.try_send(AudioChunk {
-                            samples,
-                            sample_rate: 44100,
-                            channels: 1,
-                        });
-                    }
-                    Err(e) => {
-                        error!("PulseAudio read error: {}", e);
-                        break;
+/// Routing set up for per-application capture. The selected apps' streams
+/// are moved onto private null sinks, one per output they played to. Each is
+/// looped back to that output so the user still hears them, and into the
+/// capture sink whose monitor we record. Undone when dropped.
+struct AppRouting {
+    /// IDs of the selected apps, see `app_id`.
+    app_ids: Vec<String>,
+    capture_sink: String,
+    /// (original sink index, app sink name)
+    app_sinks: Vec<(u32, String)>,
+    /// Loaded modules, unloaded in reverse order.
+    modules: Vec<u32>,
+    /// (sink input index, original sink index)
+    moved: Vec<(u32, u32)>,
+}
+
+impl AppRouting {
+    fn setup(
+        conn: &mut PulseConnection,
+        app_ids: Vec<String>,
+    ) -> Result<(Self, MonitorSource), String> {
+        let capture_sink = format!(
+            "{}{}_{}",
+            SINK_PREFIX,
+            std::process::id(),
+            NEXT_ROUTING.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut routing = AppRouting {
+            app_ids,
+            capture_sink,
+            app_sinks: Vec::new(),
+            modules: Vec::new(),
+            moved: Vec::new(),
+        };
+        let argument = format!(
+            "sink_name={} sink_properties=device.description=DualScribe_Capture",
+            routing.capture_sink
+        );
+        routing.load(conn, "module-null-sink", &argument)?;
+
+        // Apps that aren't playing yet are routed once they do
+        let selected: Vec<SinkInput> = conn
+            .sink_inputs()?
+            .into_iter()
+            .filter(|input| routing.selects(input))
+            .collect();
+        for input in &selected {
+            if let Err(e) = routing.route(conn, input) {
+                warn!("{}", e);
+            }
+        }
+        if !selected.is_empty() && routing.moved.is_empty() {
+            return Err("Failed to route any of the selected applications".to_string());
+        }
+
+        let monitor_name = format!("{}.monitor", routing.capture_sink);
+        let source = conn
+            .monitor_sources()
+            .ok()
+            .and_then(|sources| sources.into_iter().find(|s| s.name == monitor_name))
+            .ok_or_else(|| format!("Capture sink monitor '{}' not found", monitor_name))?;
+
+        info!(
+            "Routed {} application stream(s) to '{}'",
+            routing.moved.len(),
+            routing.capture_sink
+        );
+        Ok((routing, source))
+    }
+
+    fn selects(&self, input: &SinkInput) -> bool {
+        self.app_ids.contains(&app_id(input))
+    }
+
+    /// Route the sink input with `index`, just opened, if it belongs to one
+    /// of the selected apps.
+    fn follow(&mut self, conn: &mut PulseConnection, index: u32) {
+        match conn.sink_input(index) {
+            Ok(Some(input)) if self.selects(&input) => match self.route(conn, &input) {
+                Ok(()) => info!("Routed new stream {} to '{}'", index, self.capture_sink),
+                Err(e) => warn!("{}", e),
+            },
+            Ok(_) => {}
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    /// Stop tracking a sink input that has ended.
+    fn forget(&mut self, index: u32) {
+        self.moved.retain(|(input, _)| *input != index);
+    }
+
+    fn route(&mut self, conn: &mut PulseConnection, input: &SinkInput) -> Result<(), String> {
+        if self.moved.iter().any(|(index, _)| *index == input.index) {
+            return Ok(());
+        }
+        // The server may remember our sinks for an app and open its next
+        // stream on one directly; it is captured already
+        let output_name = conn.sink_name(input.sink)?;
+        if output_name
+            .as_deref()
+            .is_some_and(|name| name.starts_with(&self.capture_sink))
+        {
+            return Ok(());
+        }
+        let app_sink = match self.app_sinks.iter().find(|(sink, _)| *sink == input.sink) {
+            Some((_, name)) => name.clone(),
+            None => self.add_output(conn, input.sink, output_name)?,
+        };
+        conn.move_sink_input_to_name(input.index, &app_sink)?;
+        self.moved.push((input.index, input.sink));
+        Ok(())
+    }
+
+    /// Set up the app sink for streams that played to `output`, and return
+    /// its name.
+    fn add_output(
+        &mut self,
+        conn: &mut PulseConnection,
+        output: u32,
+        output_name: Option<String>,
+    ) -> Result<String, String> {
+        let app_sink = format!("{}_{}", self.capture_sink, output);
+        self.load(
+            conn,
+            "module-null-sink",
+            &format!(
+                "sink_name={} sink_properties=device.description=DualScribe_Applications",
+                app_sink
+            ),
+        )?;
+        let loopback = format!(
+            "source={}.monitor latency_msec=30 source_dont_move=true sink_dont_move=true",
+            app_sink
+        );
+        let to_capture = format!("{} sink={}", loopback, self.capture_sink);
+        self.load(conn, "module-loopback", &to_capture)?;
+        // An output that went away plays on the default sink
+        let playback = match output_name {
+            Some(name) => format!("{} sink={}", loopback, name),
+            None => loopback,
+        };
+        self.load(conn, "module-loopback", &playback)?;
+        self.app_sinks.push((output, app_sink.clone()));
+        Ok(app_sink)
+    }
+
+    fn load(
+        &mut self,
+        conn: &mut PulseConnection,
+        name: &str,
+        argument: &str,
+    ) -> Result<(), String> {
+        self.modules.push(conn.load_module(name, argument)?);
+        Ok(())
+    }
+}
+
+impl Drop for AppRouting {
+    /// Move the streams back where they were and remove our modules. Streams
+    /// that ended in the meantime are ignored.
+    fn drop(&mut self) {
+        let mut conn = match PulseConnection::connect() {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Failed to restore application routing: {}", e);
+                return;
+            }
+        };
+        for (input, sink) in &self.moved {
+            if let Err(e) = conn.move_sink_input_to_index(*input, *sink) {
+                warn!("{}", e);
+            }
+        }
+        for module in self.modules.iter().rev() {
+            if let Err(e) = conn.unload_module(*module) {
+                warn!("{}", e);
+            }
+        }
+        info!("Restored application routing");
+    }
+}
+
+/// Unload capture sinks and loopbacks left behind by an instance of the app
+/// that did not exit cleanly, e.g. because it crashed while capturing.
+pub fn remove_stale_routing() {
+    let result = PulseConnection::connect().and_then(|mut conn| {
+        let mut stale: Vec<_> = conn
+            .modules()?
+            .into_iter()
+            .filter(|module| {
+                routing_owner(&module.argument).is_some_and(|pid| {
+                    pid != std::process::id() && !Path::new(&format!("/proc/{}", pid)).exists()
+                })
+            })
+            .collect();
+        // Loopbacks first, as they go away with their null sink
+        stale.sort_by_key(|module| module.name != "module-loopback");
+        for module in stale {
+            match conn.unload_module(module.index) {
+                Ok(()) => info!("Unloaded stale {} ({})", module.name, module.argument),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        warn!("Failed to check for stale capture routing: {}", e);
+    }
+}
+
+/// Process ID of the app instance whose routing a module with `argument`
+/// belongs to.
+fn routing_owner(argument: &str) -> Option<u32> {
+    let start = argument.find(SINK_PREFIX)? + SINK_PREFIX.len();
+    let pid: String = argument[start..]
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    pid.parse().ok()
+}
+
+/// Record `source` on a dedicated thread until `stop_flag` is set. Any
+/// `routing` is stopped and restored when the thread exits. Returns a flag that is
+/// cleared when the thread exits.
+fn record_monitor(
+    source: MonitorSource,
+    sender: AudioSender,
+    stop_flag: Arc<AtomicBool>,
+    routing: Option<RoutingThread>,
+) -> Result<Arc<AtomicBool>, String> {
+    let spec = pulse::sample::Spec {
+        format: pulse::sample::Format::F32le,
+        channels: source.channels,
+        rate: source.rate,
+    };
+    if !spec.is_valid() {
+        return Err(format!(
+            "Unsupported monitor format: {} Hz, {} ch",
+            source.rate, source.channels
+        ));
+    }
+
+    // 100ms worth of interleaved f32 frames
+    let chunk_frames = source.rate / 10;
+    let chunk_samples = chunk_frames as usize * source.channels as usize;
+    let chunk_duration = Duration::from_secs_f64(chunk_frames as f64 / source.rate as f64);
+
+    let attr = pulse::def::BufferAttr {
+        maxlength: u32::MAX,
+        tlength: u32::MAX,
+        prebuf: u32::MAX,
+        minreq: u32::MAX,
+        fragsize: (chunk_samples * 4) as u32,
+    };
+
+    // Report startup success/failure back to the caller
+    let (result_tx, result_rx) = crossbeam_channel::bounded::<Result<(), String>>(1);
+    let running = Arc::new(AtomicBool::new(true));
+    let running_for_thread = running.clone();
+
+    thread::spawn(move || {
+        match psimple::Simple::new(
+            None,
+            "DualScribe",
+            pulse::stream::Direction::Record,
+            Some(&source.name),
+            "system-audio-capture",
+            &spec,
+            None,
+            Some(&attr),
+        ) {
+            Ok(simple) => {
+                info!(
+                    "Linux system audio capture started on '{}' ({} Hz, {} ch)",
+                    source.name, source.rate, source.channels
+                );
+                let _ = result_tx.send(Ok(()));
+
+                let mut buf = vec![0u8; chunk_samples * 4]; // f32 = 4 bytes
+
+                while !stop_flag.load(Ordering::Relaxed) {
+                    match simple.read(&mut buf) {
+                        Ok(()) => {
+                            let samples: Vec<f32> = buf
+                                .chunks_exact(4)
+                                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                                .collect();
+
+                            // The record latency is the age of the last frame
+                            // read; the first one is a chunk older.
+                            let latency = simple
+                                .get_latency()
+                                .map(|l| Duration::from_micros(l.0))
+                                .unwrap_or_default();
+                            sender.send_captured(
+                                samples,
+                                source.rate,
+                                source.channels as u16,
+                                instant_before(latency + chunk_duration),
+                            );
+                        }
+                        Err(e) => {
+                            error!("PulseAudio read error: {}", e);
+                            break;
+                        }
                     }
                 }
+
+                info!("Linux system audio capture stopped");
+            }
+            Err(e) => {
+                let _ = result_tx.send(Err(format!(
+                    "Failed to open PulseAudio monitor '{}': {}",
+                    source.name, e
+                )));
             }
+        }
 
-            info!("Linux system audio capture stopped");
-        });
+        drop(routing);
+        running_for_thread.store(false, Ordering::Relaxed);
+    });
+
+    result_rx
+        .recv()
+        .map_err(|e| format!("PulseAudio channel error: {}", e))??;
+    Ok(running)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(Box::new(LinuxCaptureHandle { stop_flag }))
+    #[test]
+    fn test_routing_owner() {
+        assert_eq!(
+            routing_owner("sink_name=dualscribe_capture_4242_0 sink_properties=x"),
+            Some(4242)
+        );
+        assert_eq!(
+            routing_owner("source=dualscribe_capture_17_3_52.monitor latency_msec=30"),
+            Some(17)
+        );
+        assert_eq!(routing_owner("sink_name=other_sink"), None);
+    }
+
+    #[test]
+    fn test_app_id_outlasts_the_stream() {
+        let input = |index, binary: Option<&str>| SinkInput {
+            index,
+            sink: 0,
+            application_name: Some("Firefox".to_string()),
+            media_name: None,
+            process_id: Some(4242),
+            binary: binary.map(str::to_string),
+        };
+        assert_eq!(app_id(&input(7, Some("firefox"))), "binary:firefox");
+        assert_eq!(
+            app_id(&input(7, Some("firefox"))),
+            app_id(&input(12, Some("firefox")))
+        );
+        assert_eq!(app_id(&input(7, None)), "name:Firefox");
     }
 }
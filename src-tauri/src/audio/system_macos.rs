@@ -1,10 +1,10 @@
-use crossbeam_channel::Sender;
 use log::info;
 use screencapturekit::prelude::*;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::audio::mic_capture::AudioChunk;
+use crate::audio::mic_capture::{instant_at_clock, instant_before, AudioSender};
 use crate::audio::system_capture::{CaptureHandle, SystemAudioCapture};
 use crate::transcription::types::AudioDeviceInfo;
 
@@ -30,7 +30,7 @@ impl MacosSystemCapture {
 }
 
 struct AudioHandler {
-    sender: Sender<AudioChunk>,
+    sender: AudioSender,
 }
 
 impl SCStreamOutputTrait for AudioHandler {
@@ -56,28 +56,40 @@ impl SCStreamOutputTrait for AudioHandler {
         }
 
         if !all_samples.is_empty() {
-            let _ = self.sender.try_send(AudioChunk {
-                samples: all_samples,
-                sample_rate: SAMPLE_RATE,
-                channels: CHANNELS,
-            });
+            let frames = all_samples.len() / CHANNELS as usize;
+            let captured_at = sample_captured_at(&sample, frames);
+            self.sender
+                .send_captured(all_samples, SAMPLE_RATE, CHANNELS, captured_at);
         }
     }
 }
 
+/// When the first of the buffer's `frames` was captured, from its
+/// presentation time on the host clock (`CLOCK_UPTIME_RAW`).
+fn sample_captured_at(sample: &CMSampleBuffer, frames: usize) -> Instant {
+    let pts = sample.presentation_timestamp();
+    if pts.value <= 0 || pts.timescale <= 0 {
+        return instant_before(Duration::from_secs_f64(frames as f64 / SAMPLE_RATE as f64));
+    }
+    let nanos = pts.value as i128 * 1_000_000_000 / pts.timescale as i128;
+    instant_at_clock(libc::CLOCK_UPTIME_RAW, nanos as u64)
+}
+
 impl SystemAudioCapture for MacosSystemCapture {
     fn list_devices(&self) -> Vec<AudioDeviceInfo> {
         vec![AudioDeviceInfo {
             name: "System Audio".to_string(),
             id: "screencapturekit".to_string(),
             is_default: true,
+            sample_rate: Some(SAMPLE_RATE),
+            channels: Some(CHANNELS),
         }]
     }
 
     fn start_capture(
         &self,
         _device_id: Option<&str>,
-        sender: Sender<AudioChunk>,
+        sender: AudioSender,
     ) -> Result<Box<dyn CaptureHandle>, String> {
         let stop_flag = Arc::new(AtomicBool::new(false));
         let stop_clone = stop_flag.clone();
@@ -0,0 +1,557 @@
+use log::{error, info, warn};
+use pipewire as pw;
+use pw::properties::properties;
+use pw::spa;
+use pw::spa::param::format::{MediaSubtype, MediaType};
+use pw::spa::param::format_utils;
+use pw::spa::pod::Pod;
+use pw::types::ObjectType;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::audio::device_id::{make_id, split_id, PIPEWIRE, PULSE};
+use crate::audio::mic_capture::{instant_at_clock, instant_before, AudioSender};
+use crate::audio::system_capture::{CaptureHandle, SystemAudioCapture};
+use crate::audio::system_linux::{LinuxSystemCapture, LEGACY_DEFAULT_ID};
+use crate::transcription::types::{AudioApplicationInfo, AudioDeviceInfo};
+
+/// A PipeWire audio sink whose monitor ports can be captured.
+#[derive(Debug, Clone)]
+struct SinkNode {
+    /// `node.name`, stable across restarts and accepted by `target.object`.
+    name: String,
+    /// `object.serial`, unique for the lifetime of the daemon.
+    serial: Option<String>,
+    description: String,
+    rate: Option<u32>,
+    channels: Option<u16>,
+    is_default: bool,
+}
+
+impl SinkNode {
+    /// Match against a stable ID. Pulse IDs name the sink's monitor source
+    /// (`<node>.monitor`); legacy IDs are a node name, serial or description.
+    fn matches(&self, id: &str) -> bool {
+        match split_id(id) {
+            Some((PIPEWIRE, node)) => self.name == node,
+            Some((PULSE, source)) => source.strip_suffix(".monitor") == Some(self.name.as_str()),
+            Some(_) => false,
+            None => self.name == id || self.serial.as_deref() == Some(id) || self.description == id,
+        }
+    }
+
+    fn to_device_info(&self) -> AudioDeviceInfo {
+        AudioDeviceInfo {
+            name: self.description.clone(),
+            id: make_id(PIPEWIRE, &self.name),
+            is_default: self.is_default,
+            sample_rate: self.rate,
+            channels: self.channels,
+        }
+    }
+}
+
+/// Returns true if a PipeWire daemon accepts connections for this user.
+/// Checked once per run, as every backend lookup asks.
+pub fn is_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        pw::init();
+        let Ok(mainloop) = pw::main_loop::MainLoop::new(None) else {
+            return false;
+        };
+        let Ok(context) = pw::context::Context::new(&mainloop) else {
+            return false;
+        };
+        context.connect(None).is_ok()
+    })
+}
+
+/// Parse the value of the `default.audio.sink` metadata key, which is a
+/// small JSON object such as `{"name":"alsa_output.pci-0000_00_1f.3.analog-stereo"}`.
+fn parse_default_sink(value: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(value)
+        .ok()?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Enumerate `Audio/Sink` nodes from the registry and mark the default sink.
+fn list_sinks() -> Result<Vec<SinkNode>, String> {
+    pw::init();
+    let mainloop = pw::main_loop::MainLoop::new(None)
+        .map_err(|e| format!("Failed to create PipeWire main loop: {}", e))?;
+    let context = pw::context::Context::new(&mainloop)
+        .map_err(|e| format!("Failed to create PipeWire context: {}", e))?;
+    let core = context
+        .connect(None)
+        .map_err(|e| format!("Failed to connect to PipeWire: {}", e))?;
+    let registry = Rc::new(
+        core.get_registry()
+            .map_err(|e| format!("Failed to get PipeWire registry: {}", e))?,
+    );
+
+    let sinks: Rc<RefCell<Vec<SinkNode>>> = Rc::new(RefCell::new(Vec::new()));
+    let default_sink: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+    let metadata: Rc<RefCell<Option<pw::metadata::Metadata>>> = Rc::new(RefCell::new(None));
+    let metadata_listener = Rc::new(RefCell::new(None));
+
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let sinks = sinks.clone();
+            let default_sink = default_sink.clone();
+            let metadata = metadata.clone();
+            let metadata_listener = metadata_listener.clone();
+            let registry = registry.clone();
+            move |global| {
+                let Some(props) = global.props else {
+                    return;
+                };
+                match global.type_ {
+                    ObjectType::Node if props.get("media.class") == Some("Audio/Sink") => {
+                        let Some(name) = props.get("node.name") else {
+                            return;
+                        };
+                        sinks.borrow_mut().push(SinkNode {
+                            name: name.to_string(),
+                            serial: props.get("object.serial").map(str::to_string),
+                            description: props
+                                .get("node.description")
+                                .or_else(|| props.get("node.nick"))
+                                .unwrap_or(name)
+                                .to_string(),
+                            rate: props.get("audio.rate").and_then(|r| r.parse().ok()),
+                            channels: props.get("audio.channels").and_then(|c| c.parse().ok()),
+                            is_default: false,
+                        });
+                    }
+                    ObjectType::Metadata if props.get("metadata.name") == Some("default") => {
+                        let Ok(bound) = registry.bind::<pw::metadata::Metadata, _>(global) else {
+                            return;
+                        };
+                        let default_sink = default_sink.clone();
+                        let listener = bound
+                            .add_listener_local()
+                            .property(move |_, key, _, value| {
+                                if key == Some("default.audio.sink") {
+                                    *default_sink.borrow_mut() = value.and_then(parse_default_sink);
+                                }
+                                0
+                            })
+                            .register();
+                        *metadata_listener.borrow_mut() = Some(listener);
+                        *metadata.borrow_mut() = Some(bound);
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .register();
+
+    // Two round trips: the first delivers the registry globals (and binds the
+    // metadata object), the second delivers the bound metadata's properties.
+    for _ in 0..2 {
+        let pending = core
+            .sync(0)
+            .map_err(|e| format!("PipeWire sync failed: {}", e))?;
+        let _core_listener = core
+            .add_listener_local()
+            .done({
+                let mainloop = mainloop.clone();
+                move |id, seq| {
+                    if id == pw::core::PW_ID_CORE && seq == pending {
+                        mainloop.quit();
+                    }
+                }
+            })
+            .register();
+        mainloop.run();
+    }
+
+    let default_sink = default_sink.borrow().clone();
+    let mut sinks = sinks.take();
+    for sink in &mut sinks {
+        sink.is_default = default_sink.as_deref() == Some(sink.name.as_str());
+    }
+    Ok(sinks)
+}
+
+/// Captures sink monitors through a native PipeWire stream.
+pub struct PipeWireSystemCapture;
+
+impl PipeWireSystemCapture {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// How long `stop` waits for the capture thread to leave its main loop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+struct PipeWireCaptureHandle {
+    quit_tx: pw::channel::Sender<()>,
+    /// Cleared when the capture thread exits, e.g. on a stream error.
+    running: Arc<AtomicBool>,
+    fallback_device: Option<AudioDeviceInfo>,
+}
+
+impl CaptureHandle for PipeWireCaptureHandle {
+    fn stop(&self) {
+        let _ = self.quit_tx.send(());
+        // Wait for the stream to disconnect so a restart doesn't open a
+        // second one while the old one is still there
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while self.is_running() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn fallback_device(&self) -> Option<AudioDeviceInfo> {
+        self.fallback_device.clone()
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+}
+
+struct StreamData {
+    format: spa::param::audio::AudioInfoRaw,
+    sender: AudioSender,
+}
+
+impl SystemAudioCapture for PipeWireSystemCapture {
+    fn list_devices(&self) -> Vec<AudioDeviceInfo> {
+        match list_sinks() {
+            Ok(sinks) => sinks.iter().map(SinkNode::to_device_info).collect(),
+            Err(e) => {
+                error!("Failed to enumerate PipeWire sinks: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    fn start_capture(
+        &self,
+        device_id: Option<&str>,
+        sender: AudioSender,
+    ) -> Result<Box<dyn CaptureHandle>, String> {
+        // Resolve the target up front so a vanished device falls back to the
+        // default sink (no target.object) instead of failing the stream. The
+        // legacy default ID from the PulseAudio backend means the default sink.
+        let mut fallback_device = None;
+        let target = match device_id.filter(|id| *id != LEGACY_DEFAULT_ID) {
+            Some(id) => {
+                let sinks = list_sinks()?;
+                match sinks.iter().find(|s| s.matches(id)) {
+                    Some(sink) => Some(sink.name.clone()),
+                    None => {
+                        warn!("PipeWire sink '{}' not found, capturing default sink", id);
+                        fallback_device = sinks
+                            .iter()
+                            .find(|s| s.is_default)
+                            .map(SinkNode::to_device_info);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let (quit_tx, quit_rx) = pw::channel::channel::<()>();
+        // Report startup success/failure back to the caller
+        let (result_tx, result_rx) = crossbeam_channel::bounded::<Result<(), String>>(1);
+        let running = Arc::new(AtomicBool::new(true));
+        let running_for_thread = running.clone();
+
+        std::thread::spawn(move || {
+            if let Err(e) = run_capture(target, sender, quit_rx, &result_tx) {
+                let _ = result_tx.send(Err(e));
+            }
+            running_for_thread.store(false, Ordering::Relaxed);
+        });
+
+        result_rx
+            .recv()
+            .map_err(|e| format!("PipeWire channel error: {}", e))??;
+
+        Ok(Box::new(PipeWireCaptureHandle {
+            quit_tx,
+            running,
+            fallback_device,
+        }))
+    }
+
+    // Per-application routing goes through pipewire-pulse, which exposes each
+    // playback node as a sink input and handles module-null-sink/module-loopback.
+    fn list_applications(&self) -> Vec<AudioApplicationInfo> {
+        LinuxSystemCapture::new().list_applications()
+    }
+
+    fn start_app_capture(
+        &self,
+        app_ids: &[String],
+        sender: AudioSender,
+    ) -> Result<Box<dyn CaptureHandle>, String> {
+        LinuxSystemCapture::new().start_app_capture(app_ids, sender)
+    }
+}
+
+/// When the first of the `frames` dequeued in this cycle was captured: the
+/// cycle's graph time, less the capture delay and the buffer's length.
+fn buffer_captured_at(stream: &pw::stream::StreamRef, frames: usize, rate: u32) -> Instant {
+    let length = Duration::from_secs_f64(frames as f64 / rate as f64);
+    // SAFETY: pw_time is plain data, and its size is passed along with it
+    let mut time: pw::sys::pw_time = unsafe { std::mem::zeroed() };
+    let result = unsafe {
+        pw::sys::pw_stream_get_time_n(
+            stream.as_raw_ptr(),
+            &mut time,
+            std::mem::size_of::<pw::sys::pw_time>(),
+        )
+    };
+    if result < 0 || time.now <= 0 || time.rate.denom == 0 {
+        return instant_before(length);
+    }
+    let delay = Duration::from_secs_f64(
+        time.delay.max(0) as f64 * time.rate.num as f64 / time.rate.denom as f64,
+    );
+    let cycle = Duration::from_nanos(time.now as u64);
+    let captured = cycle.saturating_sub(delay + length);
+    instant_at_clock(libc::CLOCK_MONOTONIC, captured.as_nanos() as u64)
+}
+
+/// Runs on the capture thread until `quit_rx` receives. PipeWire objects are
+/// not `Send`, so everything is created and dropped here.
+fn run_capture(
+    target: Option<String>,
+    sender: AudioSender,
+    quit_rx: pw::channel::Receiver<()>,
+    result_tx: &crossbeam_channel::Sender<Result<(), String>>,
+) -> Result<(), String> {
+    pw::init();
+    let mainloop = pw::main_loop::MainLoop::new(None)
+        .map_err(|e| format!("Failed to create PipeWire main loop: {}", e))?;
+    let context = pw::context::Context::new(&mainloop)
+        .map_err(|e| format!("Failed to create PipeWire context: {}", e))?;
+    let core = context
+        .connect(None)
+        .map_err(|e| format!("Failed to connect to PipeWire: {}", e))?;
+
+    let _quit_receiver = quit_rx.attach(mainloop.loop_(), {
+        let mainloop = mainloop.clone();
+        move |_| mainloop.quit()
+    });
+
+    let mut props = properties! {
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Capture",
+        *pw::keys::MEDIA_ROLE => "Communication",
+        *pw::keys::APP_NAME => "DualScribe",
+        *pw::keys::NODE_NAME => "dualscribe-system-capture",
+        // Record the monitor ports when the target is a sink
+        *pw::keys::STREAM_CAPTURE_SINK => "true",
+    };
+    if let Some(target) = &target {
+        props.insert(*pw::keys::TARGET_OBJECT, target.as_str());
+    }
+
+    let stream = pw::stream::Stream::new(&core, "system-audio-capture", props)
+        .map_err(|e| format!("Failed to create PipeWire stream: {}", e))?;
+
+    let data = StreamData {
+        format: Default::default(),
+        sender,
+    };
+
+    let _listener = stream
+        .add_local_listener_with_user_data(data)
+        .state_changed({
+            // A failed stream delivers nothing more; ending the capture lets
+            // the watchdog restart it
+            let mainloop = mainloop.clone();
+            move |_, _, _, state| {
+                if let pw::stream::StreamState::Error(e) = state {
+                    error!("PipeWire system capture failed: {}", e);
+                    mainloop.quit();
+                }
+            }
+        })
+        .param_changed(|_, data, id, param| {
+            let Some(param) = param else {
+                return;
+            };
+            if id != spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+            let Ok((media_type, media_subtype)) = format_utils::parse_format(param) else {
+                return;
+            };
+            if media_type != MediaType::Audio || media_subtype != MediaSubtype::Raw {
+                return;
+            }
+            if data.format.parse(param).is_ok() {
+                info!(
+                    "PipeWire system capture negotiated {} Hz, {} ch",
+                    data.format.rate(),
+                    data.format.channels()
+                );
+            }
+        })
+        .process(|stream, data| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let datas = buffer.datas_mut();
+            let Some(first) = datas.first_mut() else {
+                return;
+            };
+            let offset = first.chunk().offset() as usize;
+            let size = first.chunk().size() as usize;
+            let rate = data.format.rate();
+            let channels = data.format.channels() as u16;
+            if rate == 0 || channels == 0 {
+                return;
+            }
+            let Some(bytes) = first.data() else {
+                return;
+            };
+            let end = (offset + size).min(bytes.len());
+            let samples: Vec<f32> = bytes[offset.min(end)..end]
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            if !samples.is_empty() {
+                let frames = samples.len() / channels as usize;
+                let captured_at = buffer_captured_at(stream, frames, rate);
+                data.sender
+                    .send_captured(samples, rate, channels, captured_at);
+            }
+        })
+        .register()
+        .map_err(|e| format!("Failed to register PipeWire stream listener: {}", e))?;
+
+    // Only pin the sample format; rate and channel count follow the target
+    // node so we capture at its native format.
+    let mut audio_info = spa::param::audio::AudioInfoRaw::new();
+    audio_info.set_format(spa::param::audio::AudioFormat::F32LE);
+    let format = spa::pod::Object {
+        type_: spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: spa::param::ParamType::EnumFormat.as_raw(),
+        properties: audio_info.into(),
+    };
+    let values: Vec<u8> = spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(format),
+    )
+    .map_err(|e| format!("Failed to serialize PipeWire format: {:?}", e))?
+    .0
+    .into_inner();
+    let mut params = [Pod::from_bytes(&values).ok_or("Invalid PipeWire format pod")?];
+
+    stream
+        .connect(
+            spa::utils::Direction::Input,
+            None,
+            pw::stream::StreamFlags::AUTOCONNECT
+                | pw::stream::StreamFlags::MAP_BUFFERS
+                | pw::stream::StreamFlags::RT_PROCESS,
+            &mut params,
+        )
+        .map_err(|e| format!("Failed to connect PipeWire stream: {}", e))?;
+
+    info!(
+        "PipeWire system audio capture started (target: {})",
+        target.as_deref().unwrap_or("default sink")
+    );
+    let _ = result_tx.send(Ok(()));
+
+    mainloop.run();
+
+    let _ = stream.disconnect();
+    info!("PipeWire system audio capture stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::pipeline_stats::PipelineStats;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_default_sink() {
+        assert_eq!(
+            parse_default_sink(r#"{"name":"alsa_output.pci-0000_00_1f.3.analog-stereo"}"#),
+            Some("alsa_output.pci-0000_00_1f.3.analog-stereo".to_string())
+        );
+        assert_eq!(parse_default_sink("not json"), None);
+        assert_eq!(parse_default_sink(r#"{"other":1}"#), None);
+    }
+
+    #[test]
+    fn test_sink_matches_stable_and_legacy_ids() {
+        let sink = SinkNode {
+            name: "alsa_output.usb".to_string(),
+            serial: Some("42".to_string()),
+            description: "USB Headset".to_string(),
+            rate: Some(48000),
+            channels: Some(2),
+            is_default: false,
+        };
+        assert!(sink.matches("pipewire:alsa_output.usb"));
+        assert!(sink.matches("pulse:alsa_output.usb.monitor"));
+        assert!(sink.matches("alsa_output.usb"));
+        assert!(sink.matches("42"));
+        assert!(sink.matches("USB Headset"));
+        assert!(!sink.matches("pipewire:other"));
+        assert!(!sink.matches("wasapi:alsa_output.usb"));
+    }
+
+    /// Needs a running PipeWire daemon with a null sink, e.g.:
+    ///
+    /// ```sh
+    /// pipewire & wireplumber &
+    /// pw-cli create-node adapter '{ factory.name=support.null-audio-sink
+    ///     node.name=dualscribe-test-sink media.class=Audio/Sink
+    ///     audio.position=[FL FR] object.linger=true }'
+    /// cargo test -- --ignored pipewire
+    /// ```
+    #[test]
+    #[ignore]
+    fn test_pipewire_null_sink_capture() {
+        assert!(is_available(), "no PipeWire daemon running");
+
+        let capture = PipeWireSystemCapture::new();
+        let devices = capture.list_devices();
+        assert!(
+            devices
+                .iter()
+                .any(|d| d.id == "pipewire:dualscribe-test-sink"),
+            "null sink not listed: {:?}",
+            devices
+        );
+
+        let (tx, rx) = crossbeam_channel::bounded(50);
+        let stats = Arc::new(PipelineStats::new());
+        let sender = AudioSender::new(tx, stats.clone());
+        let handle = capture
+            .start_capture(Some("pipewire:dualscribe-test-sink"), sender)
+            .unwrap();
+        assert!(handle.fallback_device().is_none());
+
+        // A null sink monitor still produces (silent) buffers.
+        let chunk = rx
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .expect("no audio from null sink monitor");
+        assert!(chunk.sample_rate > 0);
+        assert!(chunk.channels > 0);
+        assert!(stats.snapshot("test".into()).chunks_captured > 0);
+        handle.stop();
+    }
+}
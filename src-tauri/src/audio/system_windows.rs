@@ -1,11 +1,13 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::SampleFormat;
-use crossbeam_channel::Sender;
 use log::{error, info};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use crate::audio::mic_capture::AudioChunk;
+use crate::audio::device_id::{
+    cpal_devices, default_cpal_device_id, find_cpal_device, Direction,
+};
+use crate::audio::mic_capture::{cpal_capture_instant, AudioSender};
 use crate::audio::system_capture::{CaptureHandle, SystemAudioCapture};
 use crate::transcription::types::AudioDeviceInfo;
 
@@ -13,12 +15,23 @@ pub struct WindowsSystemCapture;
 
 struct WindowsCaptureHandle {
     stop_flag: Arc<AtomicBool>,
+    /// Set by the stream's error callback.
+    failed: Arc<AtomicBool>,
 }
 
 impl CaptureHandle for WindowsCaptureHandle {
     fn stop(&self) {
         self.stop_flag.store(true, Ordering::Relaxed);
     }
+
+    fn is_running(&self) -> bool {
+        !self.failed.load(Ordering::Relaxed)
+    }
+
+    // WASAPI loopback gets no packets while nothing is playing.
+    fn idles_when_silent(&self) -> bool {
+        true
+    }
 }
 
 impl WindowsSystemCapture {
@@ -30,20 +43,20 @@ impl WindowsSystemCapture {
 impl SystemAudioCapture for WindowsSystemCapture {
     fn list_devices(&self) -> Vec<AudioDeviceInfo> {
         let host = cpal::default_host();
-        let default_name = host
-            .default_output_device()
-            .and_then(|d| d.name().ok())
-            .unwrap_or_default();
+        let default_id = default_cpal_device_id(&host, Direction::Output);
 
-        match host.output_devices() {
+        match cpal_devices(&host, Direction::Output) {
             Ok(devices) => devices
-                .filter_map(|d| {
-                    let name = d.name().ok()?;
-                    Some(AudioDeviceInfo {
-                        id: name.clone(),
-                        is_default: name == default_name,
+                .into_iter()
+                .map(|(d, id, name)| {
+                    let config = d.default_output_config().ok();
+                    AudioDeviceInfo {
+                        is_default: default_id.as_deref() == Some(id.as_str()),
+                        id,
                         name,
-                    })
+                        sample_rate: config.as_ref().map(|c| c.sample_rate().0),
+                        channels: config.as_ref().map(|c| c.channels()),
+                    }
                 })
                 .collect(),
             Err(e) => {
@@ -56,24 +69,14 @@ impl SystemAudioCapture for WindowsSystemCapture {
     fn start_capture(
         &self,
         device_id: Option<&str>,
-        sender: Sender<AudioChunk>,
+        sender: AudioSender,
     ) -> Result<Box<dyn CaptureHandle>, String> {
         let host = cpal::default_host();
 
         let device = match device_id {
-            Some(id) => {
-                let devices = host
-                    .output_devices()
-                    .map_err(|e| format!("Failed to get output devices: {}", e))?;
-                let mut found = None;
-                for d in devices {
-                    if d.name().ok().as_deref() == Some(id) {
-                        found = Some(d);
-                        break;
-                    }
-                }
-                found.ok_or_else(|| format!("Output device '{}' not found", id))?
-            }
+            Some(id) => find_cpal_device(&host, Direction::Output, id)?
+                .map(|(device, _)| device)
+                .ok_or_else(|| format!("Output device '{}' not found", id))?,
             None => host
                 .default_output_device()
                 .ok_or_else(|| "No default output device found".to_string())?,
@@ -94,6 +97,8 @@ impl SystemAudioCapture for WindowsSystemCapture {
 
         let stop_flag = Arc::new(AtomicBool::new(false));
         let stop_clone = stop_flag.clone();
+        let failed = Arc::new(AtomicBool::new(false));
+        let failed_clone = failed.clone();
 
         // Report startup success/failure back to the caller
         let (result_tx, result_rx) = crossbeam_channel::bounded::<Result<(), String>>(1);
@@ -102,6 +107,10 @@ impl SystemAudioCapture for WindowsSystemCapture {
             let stream_config: cpal::StreamConfig = config.into();
             let sr = sample_rate;
             let ch = channels;
+            let on_error = move |err: cpal::StreamError| {
+                error!("System audio stream error: {}", err);
+                failed_clone.store(true, Ordering::Relaxed);
+            };
 
             // build_input_stream on an output device enables WASAPI loopback
             let stream = match sample_format {
@@ -109,14 +118,10 @@ impl SystemAudioCapture for WindowsSystemCapture {
                     let sender = sender.clone();
                     device.build_input_stream(
                         &stream_config,
-                        move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                            let _ = sender.try_send(AudioChunk {
-                                samples: data.to_vec(),
-                                sample_rate: sr,
-                                channels: ch,
-                            });
+                        move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                            sender.send_captured(data.to_vec(), sr, ch, cpal_capture_instant(info));
                         },
-                        |err| error!("System audio stream error: {}", err),
+                        on_error,
                         None,
                     )
                 }
@@ -124,18 +129,14 @@ impl SystemAudioCapture for WindowsSystemCapture {
                     let sender = sender.clone();
                     device.build_input_stream(
                         &stream_config,
-                        move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                        move |data: &[i16], info: &cpal::InputCallbackInfo| {
                             let samples: Vec<f32> = data
                                 .iter()
                                 .map(|&s| s as f32 / i16::MAX as f32)
                                 .collect();
-                            let _ = sender.try_send(AudioChunk {
-                                samples,
-                                sample_rate: sr,
-                                channels: ch,
-                            });
+                            sender.send_captured(samples, sr, ch, cpal_capture_instant(info));
                         },
-                        |err| error!("System audio stream error: {}", err),
+                        on_error,
                         None,
                     )
                 }
@@ -176,6 +177,6 @@ impl SystemAudioCapture for WindowsSystemCapture {
             .map_err(|e| format!("Loopback channel error: {}", e))?
             .map_err(|e| e)?;
 
-        Ok(Box::new(WindowsCaptureHandle { stop_flag }))
+        Ok(Box::new(WindowsCaptureHandle { stop_flag, failed }))
     }
 }
@@ -0,0 +1,168 @@
+use std::time::{Duration, Instant};
+
+/// Stall timeout used when a source does not configure one.
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(3);
+/// Restarts tried in a row, without audio arriving, before giving up.
+pub const MAX_CAPTURE_RESTARTS: u32 = 5;
+
+/// What the owner of a capture should do after a watchdog poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    /// Capture stopped delivering audio; tear it down.
+    Stalled,
+    /// Try opening capture again. Carries the attempt number, from 1.
+    Restart(u32),
+    /// Audio is flowing again after a stall.
+    Recovered,
+    /// Every restart failed to bring audio back.
+    GaveUp,
+}
+
+/// Notices a capture that stopped delivering audio and paces its restarts.
+///
+/// Progress is measured by a count of chunks sent, so the watchdog works the
+/// same for every backend. Each restart gets a full timeout to produce audio
+/// before the next one, and the budget only resets once audio arrives.
+pub struct CaptureWatchdog {
+    /// `None` for captures that deliver nothing while no audio plays. Those
+    /// only stall when known to have failed, and recover once a restarted
+    /// capture is no longer failing.
+    timeout: Option<Duration>,
+    max_restarts: u32,
+    last_count: u64,
+    last_progress: Instant,
+    stalled: bool,
+    restarts: u32,
+    last_restart: Option<Instant>,
+}
+
+impl CaptureWatchdog {
+    pub fn new(timeout: Option<Duration>, max_restarts: u32, now: Instant) -> Self {
+        Self {
+            timeout,
+            max_restarts,
+            last_count: 0,
+            last_progress: now,
+            stalled: false,
+            restarts: 0,
+            last_restart: None,
+        }
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Check progress given the chunks sent so far. `failed` reports a
+    /// capture that is known to be dead, which stalls it right away.
+    pub fn poll(&mut self, chunks: u64, failed: bool, now: Instant) -> Option<WatchdogEvent> {
+        let progressed = chunks != self.last_count;
+        if progressed {
+            self.last_count = chunks;
+            self.last_progress = now;
+        }
+
+        if !self.stalled {
+            let timed_out = self
+                .timeout
+                .is_some_and(|t| now.saturating_duration_since(self.last_progress) >= t);
+            if failed || timed_out {
+                self.stalled = true;
+                return Some(WatchdogEvent::Stalled);
+            }
+            return None;
+        }
+
+        let restarted_quietly = self.timeout.is_none() && self.last_restart.is_some() && !failed;
+        if progressed || restarted_quietly {
+            self.stalled = false;
+            self.restarts = 0;
+            self.last_restart = None;
+            return Some(WatchdogEvent::Recovered);
+        }
+        let interval = self.timeout.unwrap_or(DEFAULT_STALL_TIMEOUT);
+        let due = self
+            .last_restart
+            .is_none_or(|at| now.saturating_duration_since(at) >= interval);
+        if !due {
+            return None;
+        }
+        if self.restarts >= self.max_restarts {
+            return Some(WatchdogEvent::GaveUp);
+        }
+        self.restarts += 1;
+        self.last_restart = Some(now);
+        Some(WatchdogEvent::Restart(self.restarts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(3);
+
+    #[test]
+    fn test_stall_restart_and_recovery() {
+        let start = Instant::now();
+        let mut watchdog = CaptureWatchdog::new(Some(TIMEOUT), 2, start);
+        assert_eq!(
+            watchdog.poll(10, false, start + Duration::from_secs(1)),
+            None
+        );
+        assert_eq!(
+            watchdog.poll(20, false, start + Duration::from_secs(3)),
+            None
+        );
+        // No new chunks for a full timeout
+        let at = start + Duration::from_secs(6);
+        assert_eq!(watchdog.poll(20, false, at), Some(WatchdogEvent::Stalled));
+        assert_eq!(
+            watchdog.poll(20, false, at),
+            Some(WatchdogEvent::Restart(1))
+        );
+        // The restarted capture gets a full timeout before the next attempt
+        assert_eq!(watchdog.poll(20, false, at + Duration::from_secs(1)), None);
+        assert_eq!(
+            watchdog.poll(20, false, at + TIMEOUT),
+            Some(WatchdogEvent::Restart(2))
+        );
+        assert_eq!(
+            watchdog.poll(21, false, at + TIMEOUT + Duration::from_secs(1)),
+            Some(WatchdogEvent::Recovered)
+        );
+        assert!(!watchdog.is_stalled());
+    }
+
+    #[test]
+    fn test_gives_up_after_max_restarts() {
+        let start = Instant::now();
+        let mut watchdog = CaptureWatchdog::new(Some(TIMEOUT), 1, start);
+        // A known failure stalls immediately, without waiting for the timeout
+        assert_eq!(watchdog.poll(0, true, start), Some(WatchdogEvent::Stalled));
+        assert_eq!(
+            watchdog.poll(0, true, start),
+            Some(WatchdogEvent::Restart(1))
+        );
+        assert_eq!(
+            watchdog.poll(0, false, start + TIMEOUT),
+            Some(WatchdogEvent::GaveUp)
+        );
+    }
+
+    #[test]
+    fn test_silent_capture_only_stalls_on_failure() {
+        let start = Instant::now();
+        let mut watchdog = CaptureWatchdog::new(None, 3, start);
+        // Long silence is not a stall
+        assert_eq!(watchdog.poll(0, false, start + TIMEOUT * 10), None);
+        let at = start + TIMEOUT * 11;
+        assert_eq!(watchdog.poll(0, true, at), Some(WatchdogEvent::Stalled));
+        assert_eq!(watchdog.poll(0, true, at), Some(WatchdogEvent::Restart(1)));
+        // The restarted capture is healthy, even though it is still quiet
+        assert_eq!(
+            watchdog.poll(0, false, at + Duration::from_millis(100)),
+            Some(WatchdogEvent::Recovered)
+        );
+    }
+}
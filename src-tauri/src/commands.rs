@@ -1,21 +1,76 @@
-use std::sync::Mutex;
-use tauri::State;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use tauri::ipc::{Channel, Response};
+use tauri::{Emitter, Manager, State};
 
 use crate::audio::mic_capture::MicCapture;
 use crate::audio::system_capture;
-use crate::settings::AppSettings;
+use crate::settings::{self, AppSettings};
+use crate::storage::credentials::{self, CredentialVault};
+use crate::storage::encryption::{self, DataKey};
+use crate::storage::journal::{self, JOURNAL_DIR};
+use crate::storage::session_store::{SessionStore, DATABASE_FILE};
+use crate::transcription::deepgram_client;
+use crate::transcription::retranscribe;
 use crate::transcription::stream_manager::StreamManager;
-use crate::transcription::types::AudioDeviceInfo;
+use crate::transcription::transcript_store::{TranscriptStore, MAX_PAGE_SIZE};
+use crate::transcription::types::{
+    AudioApplicationInfo, AudioDeviceInfo, AudioLevelEvent, AudioSource, DeviceCapabilities,
+    EditHistory, EncryptionStatus, PipelineStatsSnapshot, RecoveredJournal, RetranscribeRequest,
+    SearchHit, SearchQuery, SegmentsReplacedEvent, Session, SessionSummary, SessionUpdate,
+    SourceConfig, SourceKind, StartParams, StoredSession, TranscriptChange, TranscriptEdit,
+    TranscriptPage, TranscriptSegment,
+};
 
 pub struct AppState {
     pub stream_manager: Mutex<StreamManager>,
     pub settings: Mutex<AppSettings>,
+    /// Unset if the session database could not be opened, or is encrypted
+    /// and was not unlocked yet.
+    pub session_store: Mutex<Option<Arc<SessionStore>>>,
+    /// Provider API keys. Unset until the app data is unlocked, or if the
+    /// vault could not be opened.
+    pub credentials: Mutex<Option<Arc<CredentialVault>>>,
+    /// Transcript of the current (or last) session, shared with the stream
+    /// manager's pipelines.
+    pub transcript: Arc<TranscriptStore>,
+    /// Where sessions and journals are kept, if the app data dir is known.
+    pub data_dir: Option<PathBuf>,
+    /// Set while the app data is encrypted and was not unlocked yet. Nothing
+    /// is written to it until then.
+    pub locked: AtomicBool,
 }
 
+fn ensure_unlocked(state: &AppState) -> Result<(), String> {
+    if state.locked.load(Ordering::Acquire) {
+        return Err("App data is encrypted; unlock it first".to_string());
+    }
+    Ok(())
+}
+
+/// Input devices of the given cpal host, or of the platform default host.
+#[tauri::command]
+pub fn list_input_devices(host: Option<String>) -> Result<Vec<AudioDeviceInfo>, String> {
+    let mic = MicCapture::with_host(host.as_deref())?;
+    Ok(mic.list_devices())
+}
+
+/// cpal hosts available for input capture, e.g. ALSA on Linux, and JACK when
+/// built with the `jack` feature.
 #[tauri::command]
-pub fn list_input_devices() -> Vec<AudioDeviceInfo> {
-    let mic = MicCapture::new();
-    mic.list_devices()
+pub fn list_audio_hosts() -> Vec<String> {
+    MicCapture::host_names()
+}
+
+/// Sample rates, channel counts and buffer sizes an input device supports.
+#[tauri::command]
+pub fn get_device_capabilities(
+    host: Option<String>,
+    device_id: Option<String>,
+) -> Result<DeviceCapabilities, String> {
+    let mic = MicCapture::with_host(host.as_deref())?;
+    mic.capabilities(device_id.as_deref())
 }
 
 #[tauri::command]
@@ -24,17 +79,92 @@ pub fn list_output_devices() -> Vec<AudioDeviceInfo> {
     system.list_devices()
 }
 
+#[tauri::command]
+pub fn list_audio_applications() -> Vec<AudioApplicationInfo> {
+    let system = system_capture::create_system_capture();
+    system.list_applications()
+}
+
+/// Stream level meters for all sources to `on_event` at a fixed rate until
+/// unsubscribed. Returns the subscription ID.
+#[tauri::command]
+pub fn subscribe_audio_levels(
+    state: State<'_, AppState>,
+    on_event: Channel<AudioLevelEvent>,
+    spectrum: Option<bool>,
+) -> Result<u32, String> {
+    let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    Ok(manager
+        .level_subscribers()
+        .subscribe(on_event, spectrum.unwrap_or(false)))
+}
+
+#[tauri::command]
+pub fn unsubscribe_audio_levels(state: State<'_, AppState>, id: u32) -> Result<(), String> {
+    let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    manager.level_subscribers().unsubscribe(id);
+    Ok(())
+}
+
+/// Start live transcription with the language, devices and sources from the
+/// saved settings.
 #[tauri::command]
 pub fn start_transcription(
     app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
-    api_key: String,
-    language: String,
-    mic_device_id: Option<String>,
-    system_device_id: Option<String>,
+    params: StartParams,
 ) -> Result<(), String> {
+    let api_key = api_key(&state, params.provider.as_deref())?;
+    let settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    // Without a configured source list, capture the default mic and system sources
+    let sources = settings.sources.clone().unwrap_or_else(|| {
+        let mut sources = SourceConfig::default_sources(
+            settings.mic_device_id.clone(),
+            settings.system_device_id.clone(),
+            params.system_app_ids,
+        );
+        for source in sources.iter_mut().filter(|s| s.kind == SourceKind::Input) {
+            source.capture_format = settings.mic_capture_format.clone();
+        }
+        sources
+    });
     let mut manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
-    manager.start(app_handle, api_key, language, mic_device_id, system_device_id)
+    manager.start(
+        app_handle,
+        api_key,
+        settings.language.clone(),
+        sources,
+        &settings,
+    )
+}
+
+#[tauri::command]
+pub fn transcribe_file(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    provider: Option<String>,
+    path: String,
+    split_channels: Option<bool>,
+) -> Result<(), String> {
+    let api_key = api_key(&state, provider.as_deref())?;
+    let settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    let mut manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    let handle = app_handle.clone();
+    manager.start_file(
+        app_handle,
+        api_key,
+        path.into(),
+        split_channels.unwrap_or(false),
+        &settings,
+        move |stop_flag, ended_at| {
+            let state = handle.state::<AppState>();
+            let mut manager = state
+                .stream_manager
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            manager.finish_file(&stop_flag, ended_at);
+        },
+    )
 }
 
 #[tauri::command]
@@ -44,69 +174,632 @@ pub fn stop_transcription(state: State<'_, AppState>) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+pub fn pause_source(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    source: AudioSource,
+) -> Result<(), String> {
+    let mut manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    manager.pause_source(&app_handle, source)
+}
+
+#[tauri::command]
+pub fn resume_source(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    source: AudioSource,
+) -> Result<(), String> {
+    let mut manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    manager.resume_source(&app_handle, source)
+}
+
+/// Drop, queue and latency counters for each source of the current session.
+#[tauri::command]
+pub fn get_pipeline_stats(
+    state: State<'_, AppState>,
+) -> Result<Vec<PipelineStatsSnapshot>, String> {
+    let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    Ok(manager.pipeline_stats())
+}
+
+/// A source's recent audio between two session times, as WAV bytes.
+#[tauri::command]
+pub fn get_audio_clip(
+    state: State<'_, AppState>,
+    source: AudioSource,
+    start: f64,
+    end: f64,
+) -> Result<Response, String> {
+    let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    let clip = manager.audio_clip(&source, start, end)?;
+    Ok(Response::new(clip.to_wav()))
+}
+
+/// Transcribe a range of a source's recent audio again and replace its
+/// final segments in the part of it still buffered, announced through
+/// `segments-replaced`.
+#[tauri::command]
+pub async fn retranscribe_range(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    provider: Option<String>,
+    request: RetranscribeRequest,
+) -> Result<Vec<TranscriptSegment>, String> {
+    let api_key = api_key(&state, provider.as_deref())?;
+    let (clip, config) = {
+        let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+        manager.retranscribe_job(&api_key, &request)?
+    };
+    // Only the audio still buffered is transcribed again, so only its part
+    // of the range is replaced
+    let (start, end) = (clip.start, clip.end());
+    let segments = retranscribe::retranscribe(request.source.clone(), config, clip).await?;
+    // Later exports and pages see the new segments, which are also recorded
+    let transcript = &state.transcript;
+    let segments = transcript.replace_range(&request.source, start, end, segments);
+    let _ = app_handle.emit(
+        "segments-replaced",
+        SegmentsReplacedEvent {
+            source: request.source,
+            start,
+            end,
+            segments: segments.clone(),
+        },
+    );
+    Ok(segments)
+}
+
+/// A page of the current (or last) session's transcript, of one source or
+/// all of them, with open utterances as their latest interim result.
+#[tauri::command]
+pub fn get_transcript(
+    state: State<'_, AppState>,
+    source: Option<AudioSource>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> TranscriptPage {
+    state.transcript.page(
+        source.as_ref(),
+        offset.unwrap_or(0),
+        limit.unwrap_or(MAX_PAGE_SIZE),
+    )
+}
+
+/// Who made an edit: the given name, or else the OS user.
+fn edit_author(author: Option<String>) -> String {
+    author
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Tell the frontend what an edit changed. The transcript store has queued
+/// it for the history and journal already.
+fn emit_transcript_change(
+    app_handle: &tauri::AppHandle,
+    change: TranscriptChange,
+) -> Result<TranscriptChange, String> {
+    let _ = app_handle.emit("transcript-edited", change.clone());
+    Ok(change)
+}
+
+/// Edit, split, merge, delete or reassign final segments of the current (or
+/// last) session. The recognizer's text stays available on edited segments.
+#[tauri::command]
+pub fn edit_transcript(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    edit: TranscriptEdit,
+    author: Option<String>,
+) -> Result<TranscriptChange, String> {
+    if let TranscriptEdit::Reassign {
+        source: Some(source),
+        ..
+    } = &edit
+    {
+        let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+        if !manager.sources().iter().any(|s| s.id == *source) {
+            return Err(format!("Unknown source: {}", source.as_str()));
+        }
+    }
+    let change = state.transcript.edit(edit, &edit_author(author))?;
+    emit_transcript_change(&app_handle, change)
+}
+
+#[tauri::command]
+pub fn undo_transcript_edit(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    author: Option<String>,
+) -> Result<TranscriptChange, String> {
+    let change = state.transcript.undo(&edit_author(author))?;
+    emit_transcript_change(&app_handle, change)
+}
+
+#[tauri::command]
+pub fn redo_transcript_edit(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    author: Option<String>,
+) -> Result<TranscriptChange, String> {
+    let change = state.transcript.redo(&edit_author(author))?;
+    emit_transcript_change(&app_handle, change)
+}
+
+/// Who edited a transcript and how, oldest first: the current (or last)
+/// session's, or that of the recorded session `session_id`.
+#[tauri::command]
+pub fn get_edit_history(
+    state: State<'_, AppState>,
+    session_id: Option<i64>,
+) -> Result<EditHistory, String> {
+    let current = {
+        let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+        manager.session().and_then(|s| s.id)
+    };
+    match session_id {
+        Some(id) if current != Some(id) => Ok(EditHistory {
+            records: session_store(&state)?.edit_history(id)?,
+            can_undo: false,
+            can_redo: false,
+        }),
+        _ => Ok(state.transcript.history()),
+    }
+}
+
+/// Export the current (or last) session's final segments.
 #[tauri::command]
 pub fn export_transcript(
-    segments: Vec<crate::transcription::types::TranscriptSegment>,
+    state: State<'_, AppState>,
     format: String,
     show_timestamps: bool,
 ) -> Result<String, String> {
-    match format.as_str() {
-        "markdown" => Ok(crate::export::export_markdown(&segments, show_timestamps)),
-        "text" => Ok(crate::export::export_plain_text(&segments, show_timestamps)),
-        "json" => crate::export::export_json(&segments),
-        _ => Err(format!("Unknown format: {}", format)),
+    let segments = state.transcript.finals();
+    let (mut sources, pauses, session) = {
+        let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+        (
+            manager.sources(),
+            manager.pauses().to_vec(),
+            manager.session().cloned(),
+        )
+    };
+    if sources.is_empty() {
+        sources = SourceConfig::default_sources(None, None, Vec::new());
     }
+    crate::export::export_as(
+        &format,
+        &segments,
+        &sources,
+        &pauses,
+        session.as_ref(),
+        show_timestamps,
+    )
 }
 
-#[tauri::command]
-pub fn auto_save_transcript(
-    app_handle: tauri::AppHandle,
-    segments: Vec<crate::transcription::types::TranscriptSegment>,
-) -> Result<(), String> {
-    use tauri::Manager;
-    let path = app_handle
-        .path()
-        .app_data_dir()
+fn session_store(state: &AppState) -> Result<Arc<SessionStore>, String> {
+    ensure_unlocked(state)?;
+    state
+        .session_store
+        .lock()
         .map_err(|e| e.to_string())?
-        .join("autosave.json");
-    // Ensure the directory exists
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        .clone()
+        .ok_or_else(|| "Session history is unavailable".to_string())
+}
+
+/// Recorded sessions, newest first.
+#[tauri::command]
+pub fn list_sessions(state: State<'_, AppState>) -> Result<Vec<SessionSummary>, String> {
+    session_store(&state)?.list_sessions()
+}
+
+/// A recorded session with its sources, settings, segments and pauses.
+#[tauri::command]
+pub fn open_session(state: State<'_, AppState>, id: i64) -> Result<StoredSession, String> {
+    session_store(&state)?.session(id)
+}
+
+/// Edit a session in the history, through the stream manager if it is the
+/// current one so both stay in step.
+fn edit_session(state: &AppState, id: i64, update: &SessionUpdate) -> Result<Session, String> {
+    {
+        let mut manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+        if manager.session().is_some_and(|s| s.id == Some(id)) {
+            return manager.update_session(update);
+        }
     }
-    let json = serde_json::to_string(&segments).map_err(|e| e.to_string())?;
-    std::fs::write(&path, json).map_err(|e| e.to_string())?;
+    session_store(state)?.update_session(id, update)
+}
+
+#[tauri::command]
+pub fn rename_session(state: State<'_, AppState>, id: i64, title: String) -> Result<(), String> {
+    let update = SessionUpdate {
+        title: Some(title),
+        ..Default::default()
+    };
+    edit_session(&state, id, &update).map(|_| ())
+}
+
+/// Edit a recorded session's title, participants, tags or notes.
+#[tauri::command]
+pub fn update_session(
+    state: State<'_, AppState>,
+    id: i64,
+    update: SessionUpdate,
+) -> Result<Session, String> {
+    edit_session(&state, id, &update)
+}
+
+/// The running session, or the last one if none is running.
+#[tauri::command]
+pub fn get_current_session(state: State<'_, AppState>) -> Result<Option<Session>, String> {
+    let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    Ok(manager.session().cloned())
+}
+
+/// Edit the running (or last) session's metadata, whether or not it is in
+/// the history.
+#[tauri::command]
+pub fn update_current_session(
+    state: State<'_, AppState>,
+    update: SessionUpdate,
+) -> Result<Session, String> {
+    let mut manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    manager.update_session(&update)
+}
+
+#[tauri::command]
+pub fn delete_session(state: State<'_, AppState>, id: i64) -> Result<(), String> {
+    session_store(&state)?.delete_session(id)
+}
+
+/// Export a recorded session like `export_transcript` does the current one.
+#[tauri::command]
+pub fn export_session(
+    state: State<'_, AppState>,
+    id: i64,
+    format: String,
+    show_timestamps: bool,
+) -> Result<String, String> {
+    let stored = session_store(&state)?.session(id)?;
+    crate::export::export_as(
+        &format,
+        &stored.segments,
+        &stored.sources,
+        &stored.pauses,
+        Some(&stored.session),
+        show_timestamps,
+    )
+}
+
+/// Segments from all recorded sessions matching `query`, best matches first.
+#[tauri::command]
+pub fn search_transcripts(
+    state: State<'_, AppState>,
+    query: SearchQuery,
+) -> Result<Vec<SearchHit>, String> {
+    session_store(&state)?.search(&query)
+}
+
+#[tauri::command]
+pub fn rebuild_search_index(state: State<'_, AppState>) -> Result<(), String> {
+    session_store(&state)?.rebuild_search_index()
+}
+
+fn data_dir(state: &AppState) -> Result<&Path, String> {
+    state
+        .data_dir
+        .as_deref()
+        .ok_or_else(|| "App data dir is unknown".to_string())
+}
+
+/// The running session's journal and the key journals are encrypted with.
+fn journal_state(state: &AppState) -> Result<(Option<PathBuf>, Option<DataKey>), String> {
+    let manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    Ok((
+        manager.journal().map(|j| j.path().to_path_buf()),
+        manager.data_key(),
+    ))
+}
+
+/// Sessions that did not end cleanly, e.g. because the app crashed,
+/// replayed from their journals.
+#[tauri::command]
+pub fn check_autosave(state: State<'_, AppState>) -> Result<Vec<RecoveredJournal>, String> {
+    ensure_unlocked(&state)?;
+    let (active, key) = journal_state(&state)?;
+    journal::recover_journals(
+        &data_dir(&state)?.join(JOURNAL_DIR),
+        active.as_deref(),
+        key.as_ref(),
+    )
+}
+
+/// Discard the journal `id` from `check_autosave`, or all of them.
+#[tauri::command]
+pub fn clear_autosave(state: State<'_, AppState>, id: Option<String>) -> Result<(), String> {
+    ensure_unlocked(&state)?;
+    let (active, _) = journal_state(&state)?;
+    journal::remove_journals(
+        &data_dir(&state)?.join(JOURNAL_DIR),
+        active.as_deref(),
+        id.as_deref(),
+    )
+}
+
+#[tauri::command]
+pub fn get_encryption_status(state: State<'_, AppState>) -> EncryptionStatus {
+    EncryptionStatus {
+        enabled: state
+            .data_dir
+            .as_deref()
+            .is_some_and(encryption::is_enabled),
+        locked: state.locked.load(Ordering::Acquire),
+    }
+}
+
+/// Unlock the encrypted app data with the user's passphrase, so sessions
+/// can be recorded and the history opened.
+#[tauri::command]
+pub fn unlock_app_data(state: State<'_, AppState>, passphrase: String) -> Result<(), String> {
+    if !state.locked.load(Ordering::Acquire) {
+        return Ok(());
+    }
+    let dir = data_dir(&state)?;
+    let (key, store) = unlock_session_store(dir, &passphrase)?;
+    let store = match store {
+        Ok(store) => Some(Arc::new(store)),
+        // The app runs without history rather than not at all
+        Err(e) => {
+            log::error!("Session history unavailable: {}", e);
+            None
+        }
+    };
+    *state.session_store.lock().map_err(|e| e.to_string())? = store.clone();
+    let vault = open_credential_vault(Some(dir), Some(&key));
+    if let Some(vault) = &vault {
+        let settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+        move_legacy_api_key(dir, vault, &settings);
+    }
+    *state.credentials.lock().map_err(|e| e.to_string())? = vault;
+    let mut manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    manager.set_storage(store, Some(key));
+    state.locked.store(false, Ordering::Release);
     Ok(())
 }
 
+/// The data key `passphrase` unseals and the session database opened with
+/// it. After an interrupted re-key, the key the database opens with is kept
+/// and the journals and vault are brought under it.
+fn unlock_session_store(
+    dir: &Path,
+    passphrase: &str,
+) -> Result<(DataKey, Result<SessionStore, String>), String> {
+    let path = dir.join(DATABASE_FILE);
+    let current = encryption::unlock(dir, passphrase);
+    let Some(pending) = encryption::unlock_pending(dir, passphrase) else {
+        let key = current?;
+        let store = SessionStore::open(&path, Some(&key));
+        return Ok((key, store));
+    };
+    log::warn!("Completing an interrupted re-key of the app data");
+    let keys: Vec<DataKey> = current.ok().into_iter().chain([pending.clone()]).collect();
+    let mut opened = None;
+    for key in &keys {
+        if let Ok(store) = SessionStore::open(&path, Some(key)) {
+            opened = Some((key.clone(), Ok(store)));
+            break;
+        }
+    }
+    let (key, store) = opened.unwrap_or_else(|| {
+        let store = SessionStore::open(&path, Some(&pending));
+        (pending.clone(), store)
+    });
+    let keys: Vec<&DataKey> = keys.iter().collect();
+    let normalized = journal::rekey_journals(&dir.join(JOURNAL_DIR), None, &keys, Some(&key))
+        .and_then(|_| credentials::reseal_key_file(dir, &keys, Some(&key)));
+    if key.as_bytes() == pending.as_bytes() {
+        encryption::commit_pending_key(dir)?;
+    }
+    match normalized {
+        // Data may still be under the pending key, so it is kept for next time
+        Err(e) => log::error!("Failed to re-key all app data: {}", e),
+        Ok(()) => encryption::discard_pending_key(dir)?,
+    }
+    Ok((key, store))
+}
+
+/// Encrypt the app data under a new key sealed with `new_passphrase`, or
+/// decrypt it without one. `passphrase` is the current one if the data is
+/// encrypted. Exports are not affected.
 #[tauri::command]
-pub fn check_autosave(
-    app_handle: tauri::AppHandle,
-) -> Result<Option<Vec<crate::transcription::types::TranscriptSegment>>, String> {
-    use tauri::Manager;
-    let path = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("autosave.json");
-    if path.exists() {
-        let json = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        let segments = serde_json::from_str(&json).map_err(|e| e.to_string())?;
-        Ok(Some(segments))
+pub fn rekey_app_data(
+    state: State<'_, AppState>,
+    passphrase: Option<String>,
+    new_passphrase: Option<String>,
+) -> Result<EncryptionStatus, String> {
+    let store = session_store(&state)?;
+    let dir = data_dir(&state)?;
+    // Held throughout, so no session starts while the data is re-keyed
+    let mut manager = state.stream_manager.lock().map_err(|e| e.to_string())?;
+    if manager.is_running() {
+        return Err("Stop transcription before changing encryption".to_string());
+    }
+    let old_key = if encryption::is_enabled(dir) {
+        Some(encryption::unlock(
+            dir,
+            passphrase.as_deref().unwrap_or_default(),
+        )?)
     } else {
-        Ok(None)
+        None
+    };
+    if let Some(new_passphrase) = &new_passphrase {
+        encryption::check_passphrase(new_passphrase)?;
     }
+    let new_key = new_passphrase.as_ref().map(|_| DataKey::generate());
+    if old_key.is_none() && new_key.is_none() {
+        return Ok(EncryptionStatus::default());
+    }
+    // The new key is saved before anything is encrypted with it, so it is
+    // never lost, and only replaces the current one once everything is
+    if let (Some(key), Some(new_passphrase)) = (&new_key, &new_passphrase) {
+        encryption::save_pending_key(dir, key, new_passphrase)?;
+    }
+    let journal_dir = dir.join(JOURNAL_DIR);
+    let active = manager.journal().map(|j| j.path().to_path_buf());
+    let vault = credential_vault(&state).ok();
+    let rekey = |from: Option<&DataKey>, to: Option<&DataKey>| -> Result<(), String> {
+        store.rekey(to)?;
+        journal::rekey_journals(&journal_dir, active.as_deref(), from.as_slice(), to)?;
+        match &vault {
+            Some(vault) => vault.rekey(to),
+            None => Ok(()),
+        }
+    };
+    if let Err(e) = rekey(old_key.as_ref(), new_key.as_ref()) {
+        return match rekey(new_key.as_ref(), old_key.as_ref()) {
+            Ok(()) => {
+                encryption::discard_pending_key(dir)?;
+                Err(e)
+            }
+            // The pending key is kept, and unlocking finishes the re-key
+            Err(rollback) => Err(format!("{}; rolling back also failed: {}", e, rollback)),
+        };
+    }
+    if new_key.is_some() {
+        encryption::commit_pending_key(dir)?;
+    } else {
+        encryption::remove_key(dir)?;
+    }
+    manager.set_storage(Some(store), new_key.clone());
+    Ok(EncryptionStatus {
+        enabled: new_key.is_some(),
+        locked: false,
+    })
 }
 
-#[tauri::command]
-pub fn clear_autosave(app_handle: tauri::AppHandle) -> Result<(), String> {
-    use tauri::Manager;
-    let path = app_handle
-        .path()
-        .app_data_dir()
+/// Open the provider API key vault in the app data dir, sealed with the app
+/// data key if the data is encrypted.
+pub fn open_credential_vault(
+    dir: Option<&Path>,
+    data_key: Option<&DataKey>,
+) -> Option<Arc<CredentialVault>> {
+    let dir = dir?;
+    let vault = std::fs::create_dir_all(dir)
+        .map_err(|e| e.to_string())
+        .and_then(|_| CredentialVault::open(dir, data_key));
+    match vault {
+        Ok(vault) => Some(Arc::new(vault)),
+        Err(e) => {
+            log::error!("API key vault unavailable: {}", e);
+            None
+        }
+    }
+}
+
+/// Move the API key older versions kept in the settings file to the vault.
+pub fn move_legacy_api_key(dir: &Path, vault: &CredentialVault, settings: &AppSettings) {
+    let Some(api_key) = settings::legacy_api_key(dir) else {
+        return;
+    };
+    // Saving the settings again drops the plain text key
+    let moved = vault
+        .set(deepgram_client::PROVIDER, &api_key)
+        .and_then(|_| settings::save(dir, settings));
+    if let Err(e) = moved {
+        log::error!("Failed to move the saved API key to the vault: {}", e);
+    }
+}
+
+fn credential_vault(state: &AppState) -> Result<Arc<CredentialVault>, String> {
+    ensure_unlocked(state)?;
+    state
+        .credentials
+        .lock()
         .map_err(|e| e.to_string())?
-        .join("autosave.json");
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+        .clone()
+        .ok_or_else(|| "API key vault is unavailable".to_string())
+}
+
+fn check_provider(provider: &str) -> Result<(), String> {
+    if provider != deepgram_client::PROVIDER {
+        return Err(format!("Unknown provider {}", provider));
     }
     Ok(())
 }
+
+/// The stored API key of `provider`, Deepgram by default.
+fn api_key(state: &AppState, provider: Option<&str>) -> Result<String, String> {
+    let provider = provider.unwrap_or(deepgram_client::PROVIDER);
+    check_provider(provider)?;
+    credential_vault(state)?
+        .get(provider)?
+        .ok_or_else(|| format!("No API key set for {}", provider))
+}
+
+/// Providers with a stored API key. The keys themselves never leave the
+/// backend.
+#[tauri::command]
+pub fn list_credentials(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    credential_vault(&state)?.providers()
+}
+
+#[tauri::command]
+pub fn set_credential(
+    state: State<'_, AppState>,
+    provider: String,
+    api_key: String,
+) -> Result<(), String> {
+    check_provider(&provider)?;
+    let api_key = api_key.trim();
+    if api_key.is_empty() {
+        return Err("API key is empty".to_string());
+    }
+    credential_vault(&state)?.set(&provider, api_key)
+}
+
+/// Check `api_key` with the provider, or the stored key if none is given.
+#[tauri::command]
+pub async fn test_credential(
+    state: State<'_, AppState>,
+    provider: String,
+    api_key: Option<String>,
+) -> Result<(), String> {
+    check_provider(&provider)?;
+    let api_key = match api_key {
+        Some(api_key) => api_key.trim().to_string(),
+        None => self::api_key(&state, Some(&provider))?,
+    };
+    if api_key.is_empty() {
+        return Err("API key is empty".to_string());
+    }
+    deepgram_client::check_api_key(&api_key).await
+}
+
+#[tauri::command]
+pub fn delete_credential(state: State<'_, AppState>, provider: String) -> Result<(), String> {
+    check_provider(&provider)?;
+    credential_vault(&state)?.delete(&provider)
+}
+
+#[tauri::command]
+pub fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, String> {
+    Ok(state.settings.lock().map_err(|e| e.to_string())?.clone())
+}
+
+/// Validate and persist `settings`, announced through `settings-changed`.
+#[tauri::command]
+pub fn save_settings(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    settings: AppSettings,
+) -> Result<AppSettings, String> {
+    let mut current = state.settings.lock().map_err(|e| e.to_string())?;
+    settings::save(data_dir(&state)?, &settings)?;
+    *current = settings.clone();
+    drop(current);
+    let _ = app_handle.emit("settings-changed", &settings);
+    Ok(settings)
+}
@@ -1,4 +1,11 @@
-use crate::transcription::types::{AudioSource, TranscriptSegment};
+use chrono::Local;
+
+use crate::transcription::types::{
+    AudioSource, PauseInterval, Session, SourceConfig, TranscriptSegment,
+};
+
+/// Heading of an export without a session.
+const DEFAULT_TITLE: &str = "DualScribe Transcript";
 
 pub fn format_timestamp(seconds: f64) -> String {
     let total_secs = seconds as u64;
@@ -12,123 +19,262 @@ pub fn format_timestamp(seconds: f64) -> String {
     }
 }
 
-pub fn export_markdown(segments: &[TranscriptSegment], show_timestamps: bool) -> String {
-    let mut output = String::from("# DualScribe Transcript\n\n");
-    let mic_segments: Vec<_> = segments
-        .iter()
-        .filter(|s| s.source == AudioSource::Mic && s.is_final)
-        .collect();
-    let sys_segments: Vec<_> = segments
+/// A line in one source's section: either a segment or a pause gap.
+enum Entry<'a> {
+    Segment(&'a TranscriptSegment),
+    Pause(&'a PauseInterval),
+}
+
+impl Entry<'_> {
+    fn time(&self) -> f64 {
+        match self {
+            Entry::Segment(seg) => seg.timestamp,
+            Entry::Pause(pause) => pause.start,
+        }
+    }
+}
+
+/// Section order and headings: the configured sources first, then any other
+/// source found in the segments, headed by its ID.
+fn sections<'a>(
+    segments: &'a [TranscriptSegment],
+    sources: &'a [SourceConfig],
+) -> Vec<(&'a AudioSource, &'a str)> {
+    let mut sections: Vec<_> = sources.iter().map(|s| (&s.id, s.label.as_str())).collect();
+    for seg in segments {
+        if !sections.iter().any(|(id, _)| **id == seg.source) {
+            sections.push((&seg.source, seg.source.as_str()));
+        }
+    }
+    sections
+}
+
+/// Final segments and pauses of one source, in timeline order.
+fn section_entries<'a>(
+    segments: &'a [TranscriptSegment],
+    pauses: &'a [PauseInterval],
+    source: &AudioSource,
+) -> Vec<Entry<'a>> {
+    let mut entries: Vec<_> = segments
         .iter()
-        .filter(|s| s.source == AudioSource::System && s.is_final)
+        .filter(|s| s.source == *source && s.is_final)
+        .map(Entry::Segment)
+        .chain(
+            pauses
+                .iter()
+                .filter(|p| p.source == *source)
+                .map(Entry::Pause),
+        )
         .collect();
+    entries.sort_by(|a, b| a.time().total_cmp(&b.time()));
+    entries
+}
 
-    if !mic_segments.is_empty() {
-        output.push_str("## You\n\n");
-        for seg in &mic_segments {
-            if show_timestamps {
-                output.push_str(&format!(
-                    "**[{}]** {}\n\n",
-                    format_timestamp(seg.timestamp),
-                    seg.text
-                ));
-            } else {
-                output.push_str(&format!("{}\n\n", seg.text));
-            }
+/// A segment's text, led by its speaker if the user named one.
+fn segment_text(seg: &TranscriptSegment) -> String {
+    match &seg.speaker {
+        Some(speaker) => format!("{}: {}", speaker, seg.text),
+        None => seg.text.clone(),
+    }
+}
+
+/// "00:12" or "00:12–00:45", depending on whether the pause has ended.
+fn format_pause(pause: &PauseInterval, separator: &str) -> String {
+    match pause.end {
+        Some(end) => format!(
+            "{}{}{}",
+            format_timestamp(pause.start),
+            separator,
+            format_timestamp(end)
+        ),
+        None => format_timestamp(pause.start),
+    }
+}
+
+/// Labelled details of a session for the top of an export: start time,
+/// duration, participants and tags, where known.
+fn session_details(session: &Session) -> Vec<(&'static str, String)> {
+    let mut details = vec![(
+        "Started",
+        session
+            .started_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+    )];
+    if let Some(duration) = session.duration {
+        details.push(("Duration", format_timestamp(duration)));
+    }
+    if !session.participants.is_empty() {
+        details.push(("Participants", session.participants.join(", ")));
+    }
+    if !session.tags.is_empty() {
+        details.push(("Tags", session.tags.join(", ")));
+    }
+    details
+}
+
+pub fn export_markdown(
+    segments: &[TranscriptSegment],
+    sources: &[SourceConfig],
+    pauses: &[PauseInterval],
+    session: Option<&Session>,
+    show_timestamps: bool,
+) -> String {
+    let mut output = format!(
+        "# {}\n\n",
+        session.map_or(DEFAULT_TITLE, |s| s.title.as_str())
+    );
+    if let Some(session) = session {
+        for (label, value) in session_details(session) {
+            output.push_str(&format!("- **{}:** {}\n", label, value));
+        }
+        output.push('\n');
+        if !session.notes.trim().is_empty() {
+            output.push_str(&format!("{}\n\n", session.notes.trim()));
         }
     }
-    if !sys_segments.is_empty() {
-        output.push_str("## System Audio\n\n");
-        for seg in &sys_segments {
-            if show_timestamps {
-                output.push_str(&format!(
+    for (source, heading) in sections(segments, sources) {
+        let entries = section_entries(segments, pauses, source);
+        if entries.is_empty() {
+            continue;
+        }
+        output.push_str(&format!("## {}\n\n", heading));
+        for entry in &entries {
+            match entry {
+                Entry::Segment(seg) if show_timestamps => output.push_str(&format!(
                     "**[{}]** {}\n\n",
                     format_timestamp(seg.timestamp),
-                    seg.text
-                ));
-            } else {
-                output.push_str(&format!("{}\n\n", seg.text));
+                    segment_text(seg)
+                )),
+                Entry::Segment(seg) => output.push_str(&format!("{}\n\n", segment_text(seg))),
+                Entry::Pause(pause) => {
+                    output.push_str(&format!("*[paused {}]*\n\n", format_pause(pause, "–")))
+                }
             }
         }
     }
     output
 }
 
-pub fn export_plain_text(segments: &[TranscriptSegment], show_timestamps: bool) -> String {
-    let mut output = String::from("DualScribe Transcript\n\n");
-    let mic_segments: Vec<_> = segments
-        .iter()
-        .filter(|s| s.source == AudioSource::Mic && s.is_final)
-        .collect();
-    let sys_segments: Vec<_> = segments
-        .iter()
-        .filter(|s| s.source == AudioSource::System && s.is_final)
-        .collect();
-
-    if !mic_segments.is_empty() {
-        output.push_str("--- You ---\n\n");
-        for seg in &mic_segments {
-            if show_timestamps {
-                output.push_str(&format!(
-                    "[{}] {}\n",
-                    format_timestamp(seg.timestamp),
-                    seg.text
-                ));
-            } else {
-                output.push_str(&format!("{}\n", seg.text));
-            }
+pub fn export_plain_text(
+    segments: &[TranscriptSegment],
+    sources: &[SourceConfig],
+    pauses: &[PauseInterval],
+    session: Option<&Session>,
+    show_timestamps: bool,
+) -> String {
+    let mut output = format!(
+        "{}\n\n",
+        session.map_or(DEFAULT_TITLE, |s| s.title.as_str())
+    );
+    if let Some(session) = session {
+        for (label, value) in session_details(session) {
+            output.push_str(&format!("{}: {}\n", label, value));
         }
         output.push('\n');
+        if !session.notes.trim().is_empty() {
+            output.push_str(&format!("{}\n\n", session.notes.trim()));
+        }
     }
-    if !sys_segments.is_empty() {
-        output.push_str("--- System Audio ---\n\n");
-        for seg in &sys_segments {
-            if show_timestamps {
-                output.push_str(&format!(
+    let mut first = true;
+    for (source, heading) in sections(segments, sources) {
+        let entries = section_entries(segments, pauses, source);
+        if entries.is_empty() {
+            continue;
+        }
+        if !first {
+            output.push('\n');
+        }
+        first = false;
+        output.push_str(&format!("--- {} ---\n\n", heading));
+        for entry in &entries {
+            match entry {
+                Entry::Segment(seg) if show_timestamps => output.push_str(&format!(
                     "[{}] {}\n",
                     format_timestamp(seg.timestamp),
-                    seg.text
-                ));
-            } else {
-                output.push_str(&format!("{}\n", seg.text));
+                    segment_text(seg)
+                )),
+                Entry::Segment(seg) => output.push_str(&format!("{}\n", segment_text(seg))),
+                Entry::Pause(pause) => {
+                    output.push_str(&format!("[paused {}]\n", format_pause(pause, " - ")))
+                }
             }
         }
     }
     output
 }
 
-pub fn export_json(segments: &[TranscriptSegment]) -> Result<String, String> {
+/// Version of the JSON export. Version 1 was a bare array of final segments.
+pub const JSON_EXPORT_VERSION: u32 = 2;
+
+pub fn export_json(
+    segments: &[TranscriptSegment],
+    sources: &[SourceConfig],
+    pauses: &[PauseInterval],
+    session: Option<&Session>,
+) -> Result<String, String> {
     let final_segments: Vec<_> = segments.iter().filter(|s| s.is_final).collect();
-    serde_json::to_string_pretty(&final_segments).map_err(|e| e.to_string())
+    let sources: Vec<_> = sections(segments, sources)
+        .into_iter()
+        .map(|(id, label)| serde_json::json!({ "id": id, "label": label }))
+        .collect();
+    let export = serde_json::json!({
+        "version": JSON_EXPORT_VERSION,
+        "session": session,
+        "sources": sources,
+        "segments": final_segments,
+        "pauses": pauses,
+    });
+    serde_json::to_string_pretty(&export).map_err(|e| e.to_string())
+}
+
+/// Render a transcript as `markdown`, `text` or `json`, headed by its
+/// session's title and details if known.
+pub fn export_as(
+    format: &str,
+    segments: &[TranscriptSegment],
+    sources: &[SourceConfig],
+    pauses: &[PauseInterval],
+    session: Option<&Session>,
+    show_timestamps: bool,
+) -> Result<String, String> {
+    match format {
+        "markdown" => Ok(export_markdown(
+            segments,
+            sources,
+            pauses,
+            session,
+            show_timestamps,
+        )),
+        "text" => Ok(export_plain_text(
+            segments,
+            sources,
+            pauses,
+            session,
+            show_timestamps,
+        )),
+        "json" => export_json(segments, sources, pauses, session),
+        _ => Err(format!("Unknown format: {}", format)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::transcription::types::AudioSource;
+    use crate::transcription::types::{AudioSource, ChannelMap, SourceKind};
+
+    fn default_sources() -> Vec<SourceConfig> {
+        SourceConfig::default_sources(None, None, Vec::new())
+    }
 
     fn sample_segments() -> Vec<TranscriptSegment> {
         vec![
+            TranscriptSegment::final_at("mic", 1.5, "Hello there."),
+            TranscriptSegment::final_at("system", 2.0, "Welcome to the meeting."),
             TranscriptSegment {
-                text: "Hello there.".to_string(),
-                is_final: true,
-                timestamp: 1.5,
-                confidence: 0.98,
-                source: AudioSource::Mic,
-            },
-            TranscriptSegment {
-                text: "Welcome to the meeting.".to_string(),
-                is_final: true,
-                timestamp: 2.0,
-                confidence: 0.95,
-                source: AudioSource::System,
-            },
-            TranscriptSegment {
-                text: "partial".to_string(),
                 is_final: false,
-                timestamp: 3.0,
-                confidence: 0.5,
-                source: AudioSource::Mic,
+                ..TranscriptSegment::final_at("mic", 3.0, "partial")
             },
         ]
     }
@@ -145,7 +291,7 @@ mod tests {
 
     #[test]
     fn test_markdown_with_timestamps() {
-        let md = export_markdown(&sample_segments(), true);
+        let md = export_markdown(&sample_segments(), &default_sources(), &[], None, true);
         assert!(md.contains("## You"));
         assert!(md.contains("**[00:01]** Hello there."));
         assert!(md.contains("## System Audio"));
@@ -155,19 +301,151 @@ mod tests {
 
     #[test]
     fn test_plain_text_without_timestamps() {
-        let txt = export_plain_text(&sample_segments(), false);
+        let txt = export_plain_text(&sample_segments(), &default_sources(), &[], None, false);
         assert!(txt.contains("--- You ---"));
         assert!(txt.contains("Hello there."));
         assert!(!txt.contains("[00:01]"));
         assert!(!txt.contains("partial"));
     }
 
+    #[test]
+    fn test_edited_segments_export_with_speaker() {
+        let mut segments = sample_segments();
+        segments[0].text = "Hello there, Raj.".to_string();
+        segments[0].original_text = Some("Hello there.".to_string());
+        segments[0].speaker = Some("Ana".to_string());
+        let txt = export_plain_text(&segments, &default_sources(), &[], None, true);
+        assert!(txt.contains("[00:01] Ana: Hello there, Raj."));
+        assert!(!txt.contains("Hello there.\n"));
+
+        let json = export_json(&segments, &default_sources(), &[], None).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["segments"][0]["original_text"], "Hello there.");
+    }
+
     #[test]
     fn test_json_export() {
-        let json = export_json(&sample_segments()).unwrap();
-        let parsed: Vec<TranscriptSegment> = serde_json::from_str(&json).unwrap();
+        let json = export_json(&sample_segments(), &default_sources(), &[], None).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let parsed: Vec<TranscriptSegment> =
+            serde_json::from_value(value["segments"].clone()).unwrap();
         assert_eq!(parsed.len(), 2); // only finals
         assert_eq!(parsed[0].text, "Hello there.");
         assert_eq!(parsed[1].text, "Welcome to the meeting.");
     }
+
+    #[test]
+    fn test_json_export_is_versioned_and_names_sources() {
+        let json = export_json(&sample_segments(), &default_sources(), &[], None).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], JSON_EXPORT_VERSION);
+        assert_eq!(value["sources"][0]["id"], "mic");
+        assert_eq!(value["sources"][0]["label"], "You");
+    }
+
+    #[test]
+    fn test_session_heads_export() {
+        let mut session = Session::new(
+            chrono::Utc::now(),
+            "en",
+            "deepgram",
+            "nova-2",
+            &default_sources(),
+        );
+        session.title = "Q3 pricing".to_string();
+        session.participants = vec!["Ana".to_string(), "Raj".to_string()];
+        session.notes = "Decide on discounts.".to_string();
+        session.end(session.started_at + chrono::Duration::seconds(125));
+
+        let md = export_markdown(
+            &sample_segments(),
+            &default_sources(),
+            &[],
+            Some(&session),
+            false,
+        );
+        assert!(md.starts_with("# Q3 pricing\n\n- **Started:** "));
+        assert!(md.contains("- **Duration:** 02:05\n- **Participants:** Ana, Raj\n\n"));
+        assert!(md.find("Decide on discounts.").unwrap() < md.find("## You").unwrap());
+        assert!(!md.contains("Tags"));
+
+        let txt = export_plain_text(&[], &default_sources(), &[], Some(&session), false);
+        assert!(txt.starts_with("Q3 pricing\n\nStarted: "));
+
+        let json = export_json(&[], &default_sources(), &[], Some(&session)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["session"]["title"], "Q3 pricing");
+        assert_eq!(value["session"]["duration"], 125.0);
+        assert_eq!(value["session"]["devices"][1]["source"], "system");
+    }
+
+    #[test]
+    fn test_pauses_render_as_gaps() {
+        let pauses = vec![
+            PauseInterval {
+                source: AudioSource::mic(),
+                start: 12.0,
+                end: Some(45.0),
+            },
+            PauseInterval {
+                source: AudioSource::system(),
+                start: 0.5,
+                end: None,
+            },
+        ];
+        let mut segments = sample_segments();
+        segments.push(TranscriptSegment::final_at("mic", 46.0, "Back again."));
+
+        let md = export_markdown(&segments, &default_sources(), &pauses, None, true);
+        let gap = md.find("*[paused 00:12–00:45]*").unwrap();
+        assert!(md.find("Hello there.").unwrap() < gap);
+        assert!(gap < md.find("Back again.").unwrap());
+        assert!(md.contains("*[paused 00:00]*"));
+
+        let txt = export_plain_text(&segments, &default_sources(), &pauses, None, false);
+        assert!(txt.contains("[paused 00:12 - 00:45]"));
+
+        let json = export_json(&segments, &default_sources(), &pauses, None).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["pauses"][0]["end"], 45.0);
+        assert!(parsed["pauses"][1]["end"].is_null());
+    }
+
+    #[test]
+    fn test_sections_follow_source_configs() {
+        let mut sources = default_sources();
+        sources.insert(
+            1,
+            SourceConfig {
+                id: AudioSource::from("guest"),
+                label: "Guest".to_string(),
+                kind: SourceKind::Input,
+                device_id: Some("alsa:usb-mic".to_string()),
+                app_ids: Vec::new(),
+                language: None,
+                model: None,
+                stall_timeout_secs: None,
+                rewind_secs: None,
+                channel_map: ChannelMap::Mix,
+                capture_format: Default::default(),
+                external: None,
+            },
+        );
+        let mut segments = sample_segments();
+        for (id, text) in [
+            ("guest", "Thanks for having me."),
+            ("panel-2", "Next question."),
+        ] {
+            segments.push(TranscriptSegment::final_at(id, 5.0, text));
+        }
+
+        let md = export_markdown(&segments, &sources, &[], None, false);
+        let you = md.find("## You").unwrap();
+        let guest = md.find("## Guest").unwrap();
+        let system = md.find("## System Audio").unwrap();
+        // Sources without a config are appended under their ID
+        let unknown = md.find("## panel-2").unwrap();
+        assert!(you < guest && guest < system && system < unknown);
+        assert!(md[guest..system].contains("Thanks for having me."));
+    }
 }
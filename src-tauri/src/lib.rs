@@ -2,32 +2,134 @@ mod audio;
 mod commands;
 mod export;
 mod settings;
+mod storage;
 mod transcription;
 
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+
 use commands::AppState;
+use storage::encryption;
+use storage::journal::JOURNAL_DIR;
+use storage::session_store::{SessionStore, DATABASE_FILE};
 use transcription::stream_manager::StreamManager;
+use transcription::transcript_store::TranscriptStore;
+
+/// The saved settings, or the defaults if they cannot be read.
+fn load_settings(dir: Option<&Path>) -> settings::AppSettings {
+    let Some(dir) = dir else {
+        return settings::AppSettings::default();
+    };
+    settings::load(dir).unwrap_or_else(|e| {
+        log::error!("Using default settings: {}", e);
+        settings::AppSettings::default()
+    })
+}
+
+/// Open the session history in the app data dir.
+fn open_session_store(dir: Option<&Path>) -> Result<SessionStore, String> {
+    let dir = dir.ok_or_else(|| "App data dir is unknown".to_string())?;
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    SessionStore::open(&dir.join(DATABASE_FILE), None)
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_fs::init())
-        .manage(AppState {
-            stream_manager: std::sync::Mutex::new(StreamManager::new()),
-            settings: std::sync::Mutex::new(settings::AppSettings::default()),
+        .setup(|app| {
+            audio::device_monitor::spawn(app.handle().clone());
+            #[cfg(target_os = "linux")]
+            std::thread::spawn(audio::system_linux::remove_stale_routing);
+            let data_dir = app.path().app_data_dir().ok();
+            // Encrypted data is opened once the user unlocks it
+            let locked = data_dir.as_deref().is_some_and(encryption::is_enabled);
+            let session_store = if locked {
+                None
+            } else {
+                // The app runs without history rather than not at all
+                match open_session_store(data_dir.as_deref()) {
+                    Ok(store) => Some(Arc::new(store)),
+                    Err(e) => {
+                        log::error!("Session history unavailable: {}", e);
+                        None
+                    }
+                }
+            };
+            let settings = load_settings(data_dir.as_deref());
+            let credentials = if locked {
+                None
+            } else {
+                commands::open_credential_vault(data_dir.as_deref(), None)
+            };
+            if let (Some(dir), Some(vault)) = (&data_dir, &credentials) {
+                commands::move_legacy_api_key(dir, vault, &settings);
+            }
+            let journal_dir = data_dir.as_ref().map(|d| d.join(JOURNAL_DIR));
+            let transcript = Arc::new(TranscriptStore::new());
+            app.manage(AppState {
+                stream_manager: Mutex::new(StreamManager::new(
+                    session_store.clone(),
+                    journal_dir,
+                    transcript.clone(),
+                )),
+                settings: Mutex::new(settings),
+                session_store: Mutex::new(session_store),
+                credentials: Mutex::new(credentials),
+                transcript,
+                data_dir,
+                locked: AtomicBool::new(locked),
+            });
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::list_input_devices,
+            commands::list_audio_hosts,
+            commands::get_device_capabilities,
             commands::list_output_devices,
+            commands::list_audio_applications,
+            commands::subscribe_audio_levels,
+            commands::unsubscribe_audio_levels,
             commands::start_transcription,
+            commands::transcribe_file,
             commands::stop_transcription,
+            commands::pause_source,
+            commands::resume_source,
+            commands::get_pipeline_stats,
+            commands::get_audio_clip,
+            commands::retranscribe_range,
+            commands::get_transcript,
+            commands::edit_transcript,
+            commands::undo_transcript_edit,
+            commands::redo_transcript_edit,
+            commands::get_edit_history,
             commands::export_transcript,
-            commands::auto_save_transcript,
+            commands::list_sessions,
+            commands::open_session,
+            commands::rename_session,
+            commands::update_session,
+            commands::get_current_session,
+            commands::update_current_session,
+            commands::delete_session,
+            commands::export_session,
+            commands::search_transcripts,
+            commands::rebuild_search_index,
             commands::check_autosave,
             commands::clear_autosave,
+            commands::get_encryption_status,
+            commands::unlock_app_data,
+            commands::rekey_app_data,
+            commands::list_credentials,
+            commands::set_credential,
+            commands::test_credential,
+            commands::delete_credential,
+            commands::get_settings,
+            commands::save_settings,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,26 +1,353 @@
+use log::warn;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::transcription::types::{CaptureFormat, SourceConfig};
+
+/// File in the app data dir holding the settings. Versions before 1 were
+/// written there by the frontend's store plugin.
+pub const SETTINGS_FILE: &str = "settings.json";
+const SETTINGS_VERSION: u32 = 1;
+const FONT_SIZES: RangeInclusive<u32> = 10..=24;
+const THEMES: &[&str] = &["light", "dark", "system"];
+const MAX_DEVICE_ID_LEN: usize = 512;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
-    pub api_key: Option<String>,
     pub language: String,
     pub mic_device_id: Option<String>,
     pub system_device_id: Option<String>,
     pub font_size: u32,
     pub theme: String,
     pub timestamps_enabled: bool,
+    /// Host, sample rate and buffer size of the default mic source.
+    pub mic_capture_format: CaptureFormat,
+    /// Capture sources with their channel maps; the default mic and system
+    /// sources when unset.
+    pub sources: Option<Vec<SourceConfig>>,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
-            api_key: None,
             language: "en".to_string(),
             mic_device_id: None,
             system_device_id: None,
             font_size: 14,
             theme: "system".to_string(),
             timestamps_enabled: true,
+            mic_capture_format: CaptureFormat::default(),
+            sources: None,
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        check_language(&self.language)?;
+        if !FONT_SIZES.contains(&self.font_size) {
+            return Err(format!(
+                "Font size must be between {} and {}",
+                FONT_SIZES.start(),
+                FONT_SIZES.end()
+            ));
+        }
+        if !THEMES.contains(&self.theme.as_str()) {
+            return Err(format!("Unknown theme {}", self.theme));
+        }
+        check_device_id(self.mic_device_id.as_deref())?;
+        check_device_id(self.system_device_id.as_deref())?;
+        for source in self.sources.iter().flatten() {
+            check_device_id(source.device_id.as_deref())?;
+            if let Some(language) = &source.language {
+                check_language(language)?;
+            }
         }
+        Ok(())
+    }
+}
+
+/// A language tag such as `en` or `pt-BR`, or `multi` for code-switching.
+fn check_language(language: &str) -> Result<(), String> {
+    let mut subtags = language.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    let valid = language == "multi"
+        || ((2..=3).contains(&primary.len())
+            && primary.chars().all(|c| c.is_ascii_lowercase())
+            && subtags.all(|s| {
+                (2..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric())
+            }));
+    if !valid {
+        return Err(format!("Invalid language code {:?}", language));
+    }
+    Ok(())
+}
+
+fn check_device_id(device_id: Option<&str>) -> Result<(), String> {
+    let Some(device_id) = device_id else {
+        return Ok(());
+    };
+    if device_id.is_empty()
+        || device_id.len() > MAX_DEVICE_ID_LEN
+        || device_id.chars().any(char::is_control)
+    {
+        return Err(format!("Invalid device ID {:?}", device_id));
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SettingsFile<'a> {
+    version: u32,
+    settings: &'a AppSettings,
+}
+
+fn version_of(file: &Value) -> Result<u32, String> {
+    match file.get("version") {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("Invalid settings version {}", version)),
+    }
+}
+
+/// Bring a settings file of any earlier version up to `SETTINGS_VERSION`.
+fn migrate(mut file: Value) -> Result<Value, String> {
+    loop {
+        let version = version_of(&file)?;
+        file = match version {
+            0 => migrate_v0(file),
+            SETTINGS_VERSION => return Ok(file),
+            _ => return Err("Settings are from a newer version of the app".to_string()),
+        };
+    }
+}
+
+/// The store plugin kept the settings, API key included, under `settings`.
+fn migrate_v0(file: Value) -> Value {
+    let mut settings = file.get("settings").cloned().unwrap_or(Value::Null);
+    if let Some(settings) = settings.as_object_mut() {
+        settings.remove("api_key");
+    }
+    serde_json::json!({ "version": 1, "settings": settings })
+}
+
+fn read_file(dir: &Path) -> Result<Option<Value>, String> {
+    let path = dir.join(SETTINGS_FILE);
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// The settings saved in `dir`, or the defaults if there are none. Fields
+/// that are missing or invalid keep their defaults; an out of range font
+/// size is clamped.
+pub fn load(dir: &Path) -> Result<AppSettings, String> {
+    let Some(file) = read_file(dir)? else {
+        return Ok(AppSettings::default());
+    };
+    let mut file = migrate(file)?;
+    Ok(match file.get_mut("settings").map(Value::take) {
+        Some(Value::Object(fields)) => read_fields(fields),
+        Some(Value::Null) | None => AppSettings::default(),
+        Some(other) => {
+            warn!("Ignoring settings that are not an object: {}", other);
+            AppSettings::default()
+        }
+    })
+}
+
+/// Apply the saved fields one at a time over the defaults, skipping any that
+/// fail to parse or validate.
+fn read_fields(fields: Map<String, Value>) -> AppSettings {
+    let Ok(Value::Object(mut current)) = serde_json::to_value(AppSettings::default()) else {
+        return AppSettings::default();
+    };
+    for (name, mut value) in fields {
+        if !current.contains_key(&name) {
+            continue;
+        }
+        if name == "font_size" {
+            if let Some(size) = value.as_u64() {
+                let (min, max) = (*FONT_SIZES.start() as u64, *FONT_SIZES.end() as u64);
+                value = size.clamp(min, max).into();
+            }
+        }
+        let mut candidate = current.clone();
+        candidate.insert(name.clone(), value);
+        match serde_json::from_value::<AppSettings>(Value::Object(candidate.clone()))
+            .map_err(|e| e.to_string())
+            .and_then(|settings| settings.validate())
+        {
+            Ok(()) => current = candidate,
+            Err(e) => warn!("Ignoring saved setting {}: {}", name, e),
+        }
+    }
+    serde_json::from_value(Value::Object(current)).unwrap_or_default()
+}
+
+/// Save `settings` over the file in `dir`. A file from a newer version of the
+/// app is left alone, and one that cannot be read is kept aside first.
+pub fn save(dir: &Path, settings: &AppSettings) -> Result<(), String> {
+    settings.validate()?;
+    let path = dir.join(SETTINGS_FILE);
+    match read_file(dir) {
+        Ok(Some(file)) if version_of(&file).map_or(true, |v| v > SETTINGS_VERSION) => {
+            return Err("Settings were saved by a newer version of the app".to_string());
+        }
+        Ok(_) => {}
+        Err(e) => {
+            let unreadable = path.with_extension("json.unreadable");
+            warn!("{}; keeping it as {}", e, unreadable.display());
+            fs::rename(&path, &unreadable)
+                .map_err(|e| format!("Failed to keep {}: {}", path.display(), e))?;
+        }
+    }
+    let file = SettingsFile {
+        version: SETTINGS_VERSION,
+        settings,
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, json)
+        .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+    fs::rename(&partial, &path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// The API key settings from before version 1 kept in plain text, until
+/// they are saved again.
+pub fn legacy_api_key(dir: &Path) -> Option<String> {
+    let file = read_file(dir).ok()??;
+    if version_of(&file).ok()? != 0 {
+        return None;
+    }
+    file.get("settings")?
+        .get("api_key")?
+        .as_str()
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrates_store_plugin_settings() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let legacy = serde_json::json!({
+            "settings": {
+                "api_key": "secret-api-key",
+                "language": "de",
+                "mic_device_id": "alsa:hw:1,0",
+                "system_device_id": null,
+                "font_size": 18,
+                "theme": "dark",
+                "timestamps_enabled": false
+            }
+        });
+        fs::write(dir.join(SETTINGS_FILE), legacy.to_string()).unwrap();
+        assert_eq!(legacy_api_key(dir).as_deref(), Some("secret-api-key"));
+
+        let settings = load(dir).unwrap();
+        assert_eq!(settings.language, "de");
+        assert_eq!(settings.mic_device_id.as_deref(), Some("alsa:hw:1,0"));
+        assert_eq!(settings.font_size, 18);
+        assert!(!settings.timestamps_enabled);
+        // Fields added since fall back to their defaults
+        assert_eq!(settings.sources, None);
+
+        save(dir, &settings).unwrap();
+        let json = fs::read_to_string(dir.join(SETTINGS_FILE)).unwrap();
+        assert!(!json.contains("secret-api-key"));
+        assert_eq!(legacy_api_key(dir), None);
+        assert_eq!(load(dir).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_rejects_newer_and_invalid_settings() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        assert_eq!(load(dir).unwrap(), AppSettings::default());
+
+        let newer = serde_json::json!({ "version": SETTINGS_VERSION + 1, "settings": {} });
+        fs::write(dir.join(SETTINGS_FILE), newer.to_string()).unwrap();
+        assert!(load(dir).is_err());
+        // The newer file is not overwritten
+        assert!(save(dir, &AppSettings::default()).is_err());
+        assert_eq!(
+            fs::read_to_string(dir.join(SETTINGS_FILE)).unwrap(),
+            newer.to_string()
+        );
+        fs::remove_file(dir.join(SETTINGS_FILE)).unwrap();
+
+        for invalid in [
+            AppSettings {
+                language: "english".to_string(),
+                ..Default::default()
+            },
+            AppSettings {
+                font_size: 64,
+                ..Default::default()
+            },
+            AppSettings {
+                theme: "blue".to_string(),
+                ..Default::default()
+            },
+            AppSettings {
+                system_device_id: Some(String::new()),
+                ..Default::default()
+            },
+        ] {
+            assert!(save(dir, &invalid).is_err());
+        }
+        for language in ["en", "pt-BR", "zh-Hant-TW", "multi"] {
+            assert!(check_language(language).is_ok(), "{}", language);
+        }
+    }
+
+    #[test]
+    fn test_keeps_valid_fields_of_damaged_settings() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let file = serde_json::json!({
+            "version": SETTINGS_VERSION,
+            "settings": {
+                "language": "english",
+                "theme": "dark",
+                "font_size": 64,
+                "timestamps_enabled": "no",
+                "mic_device_id": "alsa:hw:1,0"
+            }
+        });
+        fs::write(dir.join(SETTINGS_FILE), file.to_string()).unwrap();
+        let settings = load(dir).unwrap();
+        assert_eq!(settings.language, "en");
+        assert_eq!(settings.theme, "dark");
+        assert_eq!(settings.font_size, *FONT_SIZES.end());
+        assert!(settings.timestamps_enabled);
+        assert_eq!(settings.mic_device_id.as_deref(), Some("alsa:hw:1,0"));
+
+        // A file that is not JSON at all is kept aside rather than lost
+        fs::write(dir.join(SETTINGS_FILE), "{ not json").unwrap();
+        assert!(load(dir).is_err());
+        save(dir, &settings).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("settings.json.unreadable")).unwrap(),
+            "{ not json"
+        );
+        assert_eq!(load(dir).unwrap(), settings);
     }
 }
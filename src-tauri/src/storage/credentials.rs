@@ -0,0 +1,353 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+use crate::storage::encryption::DataKey;
+
+/// File in the app data dir holding provider API keys, each sealed with the
+/// vault key.
+pub const CREDENTIALS_FILE: &str = "credentials.json";
+/// Fallback for the vault key where the OS keyring is unavailable. Sealed
+/// with the app data key if the app data is encrypted; otherwise it is kept
+/// plain next to the credentials and only guards them against a copy of
+/// `CREDENTIALS_FILE` alone, not against anyone who can read the app data dir.
+const VAULT_KEY_FILE: &str = "credentials.key";
+const CREDENTIALS_VERSION: u32 = 1;
+const KEYRING_SERVICE: &str = "DualScribe";
+const KEYRING_USER: &str = "credentials-vault-key";
+
+#[derive(Serialize, Deserialize)]
+struct CredentialsFile {
+    version: u32,
+    /// Provider ID to its sealed API key.
+    credentials: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VaultKeyFile {
+    /// Whether `key` is sealed with the app data key.
+    sealed: bool,
+    key: String,
+}
+
+/// Provider API keys, encrypted at rest and only ever handed to the
+/// backend's own clients.
+pub struct CredentialVault {
+    path: PathBuf,
+    key: DataKey,
+    /// Set if the vault key lives in `VAULT_KEY_FILE` rather than the OS
+    /// keyring, so re-keying the app data re-seals it.
+    key_file: Option<PathBuf>,
+    credentials: Mutex<BTreeMap<String, String>>,
+}
+
+impl CredentialVault {
+    /// Open the vault in `dir`. Its key comes from the OS keyring, or from a
+    /// key file sealed with `data_key` if the app data is encrypted. A key
+    /// file is only created for a new vault; credentials stored under a
+    /// keyring key are an error while the keyring is unavailable.
+    pub fn open(dir: &Path, data_key: Option<&DataKey>) -> Result<Self, String> {
+        Self::open_with(dir, data_key, keyring_key)
+    }
+
+    fn open_with(
+        dir: &Path,
+        data_key: Option<&DataKey>,
+        keyring_key: impl FnOnce() -> Result<DataKey, String>,
+    ) -> Result<Self, String> {
+        let key_path = dir.join(VAULT_KEY_FILE);
+        if let Some(key) = load_key_file(&key_path, data_key)? {
+            return Self::with_key(dir, key, Some(key_path));
+        }
+        match keyring_key() {
+            Ok(key) => Self::with_key(dir, key, None),
+            Err(e) if dir.join(CREDENTIALS_FILE).exists() => Err(format!(
+                "OS keyring unavailable, so the stored credentials cannot be read: {}",
+                e
+            )),
+            Err(e) => {
+                warn!(
+                    "OS keyring unavailable, keeping the credentials key in {}{}: {}",
+                    key_path.display(),
+                    if data_key.is_some() {
+                        ""
+                    } else {
+                        " unencrypted"
+                    },
+                    e
+                );
+                let key = DataKey::generate();
+                save_key_file(&key_path, &key, data_key)?;
+                Self::with_key(dir, key, Some(key_path))
+            }
+        }
+    }
+
+    fn with_key(dir: &Path, key: DataKey, key_file: Option<PathBuf>) -> Result<Self, String> {
+        let path = dir.join(CREDENTIALS_FILE);
+        let credentials = match fs::read_to_string(&path) {
+            Ok(json) => {
+                let file: CredentialsFile = serde_json::from_str(&json)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                if file.version > CREDENTIALS_VERSION {
+                    return Err(format!(
+                        "{} is from a newer version of the app",
+                        path.display()
+                    ));
+                }
+                file.credentials
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        Ok(Self {
+            path,
+            key,
+            key_file,
+            credentials: Mutex::new(credentials),
+        })
+    }
+
+    /// Providers with a stored API key.
+    pub fn providers(&self) -> Result<Vec<String>, String> {
+        let credentials = self.credentials.lock().map_err(|e| e.to_string())?;
+        Ok(credentials.keys().cloned().collect())
+    }
+
+    pub fn get(&self, provider: &str) -> Result<Option<String>, String> {
+        let credentials = self.credentials.lock().map_err(|e| e.to_string())?;
+        let Some(sealed) = credentials.get(provider) else {
+            return Ok(None);
+        };
+        let sealed = BASE64.decode(sealed).map_err(|e| e.to_string())?;
+        let api_key = self.key.open(&sealed)?;
+        String::from_utf8(api_key)
+            .map(Some)
+            .map_err(|_| format!("Stored API key for {} is corrupt", provider))
+    }
+
+    pub fn set(&self, provider: &str, api_key: &str) -> Result<(), String> {
+        let sealed = BASE64.encode(self.key.seal(api_key.as_bytes())?);
+        let mut credentials = self.credentials.lock().map_err(|e| e.to_string())?;
+        let mut updated = credentials.clone();
+        updated.insert(provider.to_string(), sealed);
+        self.save(&updated)?;
+        *credentials = updated;
+        Ok(())
+    }
+
+    pub fn delete(&self, provider: &str) -> Result<(), String> {
+        let mut credentials = self.credentials.lock().map_err(|e| e.to_string())?;
+        if !credentials.contains_key(provider) {
+            return Ok(());
+        }
+        let mut updated = credentials.clone();
+        updated.remove(provider);
+        self.save(&updated)?;
+        *credentials = updated;
+        Ok(())
+    }
+
+    /// Re-seal a key file vault key with the new app data key, or store it
+    /// plain if the app data is no longer encrypted.
+    pub fn rekey(&self, data_key: Option<&DataKey>) -> Result<(), String> {
+        match &self.key_file {
+            Some(path) => save_key_file(path, &self.key, data_key),
+            None => Ok(()),
+        }
+    }
+
+    fn save(&self, credentials: &BTreeMap<String, String>) -> Result<(), String> {
+        let file = CredentialsFile {
+            version: CREDENTIALS_VERSION,
+            credentials: credentials.clone(),
+        };
+        let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        write_private(&self.path, json.as_bytes())
+    }
+}
+
+/// The vault key from the OS keyring, created on first use.
+fn keyring_key() -> Result<DataKey, String> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(|e| e.to_string())?;
+    match entry.get_password() {
+        Ok(encoded) => {
+            let bytes = Zeroizing::new(BASE64.decode(encoded).map_err(|e| e.to_string())?);
+            DataKey::from_bytes(&bytes)
+        }
+        Err(keyring::Error::NoEntry) => {
+            let key = DataKey::generate();
+            let encoded = Zeroizing::new(BASE64.encode(key.as_bytes()));
+            entry.set_password(&encoded).map_err(|e| e.to_string())?;
+            Ok(key)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Seal the vault key file, if the vault keeps its key in one, with
+/// `data_key` instead of whichever of `keys` it is sealed with.
+pub fn reseal_key_file(
+    dir: &Path,
+    keys: &[&DataKey],
+    data_key: Option<&DataKey>,
+) -> Result<(), String> {
+    let path = dir.join(VAULT_KEY_FILE);
+    let mut result = Ok(None);
+    for key in keys.iter().copied().map(Some).chain([None]) {
+        result = load_key_file(&path, key);
+        if result.is_ok() {
+            break;
+        }
+    }
+    match result? {
+        Some(key) => save_key_file(&path, &key, data_key),
+        None => Ok(()),
+    }
+}
+
+fn load_key_file(path: &Path, data_key: Option<&DataKey>) -> Result<Option<DataKey>, String> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let file: VaultKeyFile = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut key = Zeroizing::new(BASE64.decode(&file.key).map_err(|e| e.to_string())?);
+    if file.sealed {
+        let data_key =
+            data_key.ok_or_else(|| "App data is encrypted; unlock it first".to_string())?;
+        key = Zeroizing::new(data_key.open(&key)?);
+    }
+    DataKey::from_bytes(&key).map(Some)
+}
+
+fn save_key_file(path: &Path, key: &DataKey, data_key: Option<&DataKey>) -> Result<(), String> {
+    let file = match data_key {
+        Some(data_key) => VaultKeyFile {
+            sealed: true,
+            key: BASE64.encode(data_key.seal(key.as_bytes())?),
+        },
+        None => VaultKeyFile {
+            sealed: false,
+            key: BASE64.encode(key.as_bytes()),
+        },
+    };
+    let json = Zeroizing::new(serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?);
+    write_private(path, json.as_bytes())
+}
+
+/// Write `contents` readable by the current user only, replacing `path`
+/// atomically.
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+
+    let partial = path.with_extension("partial");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&partial)
+        .and_then(|mut file| file.write_all(contents))
+        .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+    fs::rename(&partial, path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stores_sealed_credentials() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let key = DataKey::generate();
+        let vault = CredentialVault::with_key(dir, key.clone(), None).unwrap();
+        assert_eq!(vault.get("deepgram").unwrap(), None);
+        vault.set("deepgram", "secret-api-key").unwrap();
+        assert_eq!(
+            vault.get("deepgram").unwrap().as_deref(),
+            Some("secret-api-key")
+        );
+
+        let json = fs::read_to_string(dir.join(CREDENTIALS_FILE)).unwrap();
+        assert!(!json.contains("secret-api-key"));
+
+        let reopened = CredentialVault::with_key(dir, key, None).unwrap();
+        assert_eq!(reopened.providers().unwrap(), vec!["deepgram".to_string()]);
+        assert_eq!(
+            reopened.get("deepgram").unwrap().as_deref(),
+            Some("secret-api-key")
+        );
+        reopened.delete("deepgram").unwrap();
+        assert!(reopened.providers().unwrap().is_empty());
+
+        let other = CredentialVault::with_key(dir, DataKey::generate(), None).unwrap();
+        assert!(other.providers().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_key_file_follows_app_data_key() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join(VAULT_KEY_FILE);
+        let key = DataKey::generate();
+        save_key_file(&path, &key, None).unwrap();
+        let vault = CredentialVault::with_key(
+            dir,
+            load_key_file(&path, None).unwrap().unwrap(),
+            Some(path.clone()),
+        )
+        .unwrap();
+        vault.set("deepgram", "secret-api-key").unwrap();
+
+        let data_key = DataKey::generate();
+        vault.rekey(Some(&data_key)).unwrap();
+        assert!(load_key_file(&path, None).is_err());
+        assert!(load_key_file(&path, Some(&DataKey::generate())).is_err());
+        let unsealed = load_key_file(&path, Some(&data_key)).unwrap().unwrap();
+        assert_eq!(unsealed.as_bytes(), key.as_bytes());
+
+        let reopened = CredentialVault::with_key(dir, unsealed, Some(path)).unwrap();
+        assert_eq!(
+            reopened.get("deepgram").unwrap().as_deref(),
+            Some("secret-api-key")
+        );
+    }
+
+    #[test]
+    fn test_keeps_credentials_without_keyring() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let no_keyring = || Err("no keyring".to_string());
+        let keyring = DataKey::generate();
+        let vault = CredentialVault::open_with(dir, None, || Ok(keyring.clone())).unwrap();
+        vault.set("deepgram", "secret-api-key").unwrap();
+        let stored = fs::read(dir.join(CREDENTIALS_FILE)).unwrap();
+
+        assert!(CredentialVault::open_with(dir, None, no_keyring).is_err());
+        assert!(!dir.join(VAULT_KEY_FILE).exists());
+        assert_eq!(fs::read(dir.join(CREDENTIALS_FILE)).unwrap(), stored);
+
+        // A new vault keeps its key in a file instead
+        fs::remove_file(dir.join(CREDENTIALS_FILE)).unwrap();
+        let vault = CredentialVault::open_with(dir, None, no_keyring).unwrap();
+        vault.set("deepgram", "secret-api-key").unwrap();
+        let reopened = CredentialVault::open_with(dir, None, no_keyring).unwrap();
+        assert_eq!(
+            reopened.get("deepgram").unwrap().as_deref(),
+            Some("secret-api-key")
+        );
+    }
+}
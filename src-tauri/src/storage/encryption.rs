@@ -0,0 +1,254 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use zeroize::Zeroizing;
+
+/// File in the app data dir holding the sealed data key. The data dir is
+/// encrypted if and only if it exists, or a re-key to a new key was
+/// interrupted.
+pub const KEY_FILE: &str = "encryption.json";
+/// The new key while the data is re-keyed. It is saved before anything is
+/// encrypted with it and takes `KEY_FILE`'s place once everything is.
+const PENDING_KEY_FILE: &str = "encryption.pending.json";
+
+const KEY_FILE_VERSION: u32 = 1;
+const MIN_PASSPHRASE_LEN: usize = 8;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    version: u32,
+    /// Argon2id settings the passphrase key was derived with.
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    /// The data key, sealed with the passphrase key.
+    sealed_key: String,
+}
+
+/// Random key the session database and journals are encrypted with. It is
+/// kept on disk only sealed with a key derived from the user's passphrase.
+#[derive(Clone)]
+pub struct DataKey(Arc<Zeroizing<[u8; KEY_LEN]>>);
+
+impl DataKey {
+    pub fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        OsRng.fill_bytes(key.as_mut_slice());
+        Self(Arc::new(key))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let key: [u8; KEY_LEN] = bytes
+            .try_into()
+            .map_err(|_| format!("Key must be {} bytes", KEY_LEN))?;
+        Ok(Self(Arc::new(Zeroizing::new(key))))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(Key::from_slice(self.0.as_slice()))
+    }
+
+    /// Encrypt `plaintext` with XChaCha20-Poly1305 under a random nonce,
+    /// which leads the result.
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Failed to encrypt data".to_string())?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        if sealed.len() < NONCE_LEN {
+            return Err("Encrypted data is truncated".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt data".to_string())
+    }
+
+    /// The key as a SQLCipher raw key, so SQLCipher skips its own key
+    /// derivation.
+    pub fn sqlcipher_key(&self) -> Zeroizing<String> {
+        let mut key = Zeroizing::new(String::from("x'"));
+        for byte in self.0.iter() {
+            let _ = write!(key, "{:02x}", byte);
+        }
+        key.push('\'');
+        key
+    }
+}
+
+fn passphrase_key(passphrase: &str, salt: &[u8], params: Params) -> Result<DataKey, String> {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut_slice())
+        .map_err(|e| format!("Failed to derive key: {}", e))?;
+    Ok(DataKey(Arc::new(key)))
+}
+
+/// Whether the app data in `dir` is encrypted.
+pub fn is_enabled(dir: &Path) -> bool {
+    dir.join(KEY_FILE).exists() || dir.join(PENDING_KEY_FILE).exists()
+}
+
+/// The data key of `dir`, unsealed with the user's passphrase.
+pub fn unlock(dir: &Path, passphrase: &str) -> Result<DataKey, String> {
+    read_key(&dir.join(KEY_FILE), passphrase)
+}
+
+/// The new key of an interrupted re-key, if there is one and it unseals
+/// with `passphrase`.
+pub fn unlock_pending(dir: &Path, passphrase: &str) -> Option<DataKey> {
+    let path = dir.join(PENDING_KEY_FILE);
+    if !path.exists() {
+        return None;
+    }
+    read_key(&path, passphrase).ok()
+}
+
+fn read_key(path: &Path, passphrase: &str) -> Result<DataKey, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let file: KeyFile = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if file.version > KEY_FILE_VERSION {
+        return Err(format!(
+            "{} is from a newer version of the app",
+            path.display()
+        ));
+    }
+    let params = Params::new(file.m_cost, file.t_cost, file.p_cost, Some(KEY_LEN))
+        .map_err(|e| format!("Invalid key parameters: {}", e))?;
+    let salt = BASE64.decode(&file.salt).map_err(|e| e.to_string())?;
+    let sealed = BASE64.decode(&file.sealed_key).map_err(|e| e.to_string())?;
+    let key = Zeroizing::new(
+        passphrase_key(passphrase, &salt, params)?
+            .open(&sealed)
+            .map_err(|_| "Wrong passphrase".to_string())?,
+    );
+    DataKey::from_bytes(&key).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Store `key` in `dir` as the pending key of a re-key, sealed with
+/// `passphrase`.
+pub fn save_pending_key(dir: &Path, key: &DataKey, passphrase: &str) -> Result<(), String> {
+    write_key(&dir.join(PENDING_KEY_FILE), key, passphrase)
+}
+
+/// Make the pending key the data key, once everything is encrypted with it.
+pub fn commit_pending_key(dir: &Path) -> Result<(), String> {
+    let path = dir.join(KEY_FILE);
+    fs::rename(dir.join(PENDING_KEY_FILE), &path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Drop the pending key of a re-key that was rolled back.
+pub fn discard_pending_key(dir: &Path) -> Result<(), String> {
+    let path = dir.join(PENDING_KEY_FILE);
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to remove {}: {}", path.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn write_key(path: &Path, key: &DataKey, passphrase: &str) -> Result<(), String> {
+    check_passphrase(passphrase)?;
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let params = Params::default();
+    let sealed_key = passphrase_key(passphrase, &salt, params.clone())?.seal(key.as_bytes())?;
+    let file = KeyFile {
+        version: KEY_FILE_VERSION,
+        m_cost: params.m_cost(),
+        t_cost: params.t_cost(),
+        p_cost: params.p_cost(),
+        salt: BASE64.encode(salt),
+        sealed_key: BASE64.encode(sealed_key),
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    // A crash while writing must not lose the current key
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, json)
+        .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+    fs::rename(&partial, path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Forget the key of `dir` once its data was decrypted.
+pub fn remove_key(dir: &Path) -> Result<(), String> {
+    let path = dir.join(KEY_FILE);
+    fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seals_and_opens() {
+        let key = DataKey::generate();
+        let sealed = key.seal(b"hello there").unwrap();
+        assert_eq!(key.open(&sealed).unwrap(), b"hello there");
+        assert!(DataKey::generate().open(&sealed).is_err());
+        assert!(key.open(&sealed[..10]).is_err());
+        assert_eq!(key.sqlcipher_key().len(), 2 * KEY_LEN + 3);
+    }
+
+    #[test]
+    fn test_unlocks_with_passphrase_only() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let key = DataKey::generate();
+        assert!(save_pending_key(dir, &key, "short").is_err());
+        assert!(!is_enabled(dir));
+
+        save_pending_key(dir, &key, "correct horse").unwrap();
+        assert!(is_enabled(dir));
+        assert!(unlock_pending(dir, "wrong horse").is_none());
+        assert!(unlock_pending(dir, "correct horse").is_some());
+        commit_pending_key(dir).unwrap();
+        assert!(unlock_pending(dir, "correct horse").is_none());
+        assert_eq!(
+            unlock(dir, "wrong horse").err().as_deref(),
+            Some("Wrong passphrase")
+        );
+        let unlocked = unlock(dir, "correct horse").unwrap();
+        assert_eq!(*unlocked.sqlcipher_key(), *key.sqlcipher_key());
+
+        remove_key(dir).unwrap();
+        assert!(!is_enabled(dir));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,615 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::storage::encryption::DataKey;
+use crate::transcription::types::{
+    AudioSource, RecoveredJournal, Session, SourceConfig, TranscriptChange, TranscriptSegment,
+};
+
+/// Directory in the app data dir holding the journals of running sessions.
+pub const JOURNAL_DIR: &str = "journals";
+
+/// Most time between two fsyncs of a journal while segments arrive. Lines
+/// are written as they come, so a crash of the app loses none of them; only
+/// a power loss can lose those not synced yet.
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One line of a journal.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JournalEntry {
+    Start {
+        session: Session,
+        sources: Vec<SourceConfig>,
+    },
+    /// The session's metadata was edited.
+    Session {
+        session: Session,
+    },
+    Segment {
+        segment: TranscriptSegment,
+    },
+    /// A source's segments in `[start, end)` were re-transcribed.
+    Replace {
+        source: AudioSource,
+        start: f64,
+        end: f64,
+        segments: Vec<TranscriptSegment>,
+    },
+    /// The user edited the transcript: segments with the `removed`
+    /// utterance IDs are gone and `segments` took their place.
+    Change {
+        removed: Vec<u64>,
+        segments: Vec<TranscriptSegment>,
+    },
+    /// All of an edited transcript's segments, as journaled before edits
+    /// were journaled as changes.
+    Transcript {
+        segments: Vec<TranscriptSegment>,
+    },
+}
+
+/// A journal line as written before sessions had metadata; it only ever
+/// starts a journal.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum LegacyEntry {
+    Start {
+        started_at: DateTime<Utc>,
+        language: String,
+        sources: Vec<SourceConfig>,
+    },
+}
+
+impl From<LegacyEntry> for JournalEntry {
+    fn from(entry: LegacyEntry) -> Self {
+        let LegacyEntry::Start {
+            started_at,
+            language,
+            sources,
+        } = entry;
+        JournalEntry::Start {
+            // Deepgram was the only provider then; its model went unrecorded
+            session: Session::new(started_at, &language, "deepgram", "", &sources),
+            sources,
+        }
+    }
+}
+
+fn parse_entry(json: &[u8]) -> Result<JournalEntry, String> {
+    serde_json::from_slice(json).or_else(|e| {
+        serde_json::from_slice::<LegacyEntry>(json)
+            .map(JournalEntry::from)
+            .map_err(|_| e.to_string())
+    })
+}
+
+/// A decoded journal line, or the bytes of one that cannot be read.
+type JournalLine = Result<JournalEntry, Vec<u8>>;
+
+struct JournalFile {
+    file: File,
+    last_sync: Instant,
+}
+
+/// One journal line: the entry as JSON, or sealed with `key` and base64
+/// encoded if the app data is encrypted.
+fn encode_line(entry: &JournalEntry, key: Option<&DataKey>) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_vec(entry).map_err(|e| e.to_string())?;
+    let mut line = match key {
+        Some(key) => BASE64.encode(key.seal(&json)?).into_bytes(),
+        None => json,
+    };
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// Decode a line written plain or sealed with any of `keys`.
+fn decode_line(line: &[u8], keys: &[&DataKey]) -> Result<JournalEntry, String> {
+    if line.first() == Some(&b'{') {
+        return parse_entry(line);
+    }
+    if keys.is_empty() {
+        return Err("Journal is encrypted".to_string());
+    }
+    let sealed = BASE64.decode(line).map_err(|e| e.to_string())?;
+    let json = keys
+        .iter()
+        .find_map(|key| key.open(&sealed).ok())
+        .ok_or_else(|| "Failed to decrypt data".to_string())?;
+    parse_entry(&json)
+}
+
+/// Append-only JSONL log of a running session's final segments, so its
+/// transcript survives a crash of the app or the webview. The journal is
+/// removed once the session ends cleanly.
+#[derive(Clone)]
+pub struct SessionJournal {
+    path: PathBuf,
+    /// Unset once the journal was finished.
+    file: Arc<Mutex<Option<JournalFile>>>,
+    /// Encrypts each line, if the app data is encrypted.
+    key: Option<DataKey>,
+}
+
+impl SessionJournal {
+    /// Create the journal of a session in `dir`.
+    pub fn create(
+        dir: &Path,
+        session: &Session,
+        sources: &[SourceConfig],
+        key: Option<DataKey>,
+    ) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let path = dir.join(format!(
+            "session-{}.jsonl",
+            session.started_at.format("%Y%m%d-%H%M%S-%3f")
+        ));
+        let file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to create journal {}: {}", path.display(), e))?;
+        let journal = Self {
+            path,
+            file: Arc::new(Mutex::new(Some(JournalFile {
+                file,
+                last_sync: Instant::now(),
+            }))),
+            key,
+        };
+        journal.write(
+            &JournalEntry::Start {
+                session: session.clone(),
+                sources: sources.to_vec(),
+            },
+            true,
+        )?;
+        Ok(journal)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a segment if it is final.
+    pub fn append(&self, segment: &TranscriptSegment) {
+        if !segment.is_final {
+            return;
+        }
+        let entry = JournalEntry::Segment {
+            segment: segment.clone(),
+        };
+        if let Err(e) = self.write(&entry, false) {
+            warn!("Failed to journal segment: {}", e);
+        }
+    }
+
+    pub fn update_session(&self, session: &Session) {
+        let entry = JournalEntry::Session {
+            session: session.clone(),
+        };
+        if let Err(e) = self.write(&entry, true) {
+            warn!("Failed to journal session metadata: {}", e);
+        }
+    }
+
+    pub fn replace_range(
+        &self,
+        source: &AudioSource,
+        start: f64,
+        end: f64,
+        segments: &[TranscriptSegment],
+    ) {
+        let entry = JournalEntry::Replace {
+            source: source.clone(),
+            start,
+            end,
+            segments: segments.iter().filter(|s| s.is_final).cloned().collect(),
+        };
+        if let Err(e) = self.write(&entry, true) {
+            warn!("Failed to journal re-transcribed segments: {}", e);
+        }
+    }
+
+    pub fn apply_change(&self, change: &TranscriptChange) {
+        let entry = JournalEntry::Change {
+            removed: change.removed.clone(),
+            segments: change
+                .segments
+                .iter()
+                .filter(|s| s.is_final)
+                .cloned()
+                .collect(),
+        };
+        if let Err(e) = self.write(&entry, true) {
+            warn!("Failed to journal transcript edits: {}", e);
+        }
+    }
+
+    /// Remove the journal, e.g. once the session ended cleanly. Later
+    /// writes are dropped.
+    pub fn finish(&self) {
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        if file.take().is_some() {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!("Failed to remove journal {}: {}", self.path.display(), e);
+            }
+        }
+    }
+
+    /// Write one line, syncing it to disk right away with `sync` or once
+    /// `SYNC_INTERVAL` has passed since the last sync.
+    fn write(&self, entry: &JournalEntry, sync: bool) -> Result<(), String> {
+        let line = encode_line(entry, self.key.as_ref())?;
+        let mut file = self.file.lock().map_err(|e| e.to_string())?;
+        let Some(journal) = file.as_mut() else {
+            return Ok(());
+        };
+        journal.file.write_all(&line).map_err(|e| e.to_string())?;
+        if sync || journal.last_sync.elapsed() >= SYNC_INTERVAL {
+            journal.file.sync_data().map_err(|e| e.to_string())?;
+            journal.last_sync = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+/// Journals in `dir` other than `active`, oldest first.
+fn journal_paths(dir: &Path, active: Option<&Path>) -> Result<Vec<PathBuf>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter(|path| Some(path.as_path()) != active)
+        .collect();
+    // Names start with the session's start time
+    paths.sort();
+    Ok(paths)
+}
+
+/// Replay the journals of sessions that did not end cleanly, skipping
+/// `active`, the running session's.
+pub fn recover_journals(
+    dir: &Path,
+    active: Option<&Path>,
+    key: Option<&DataKey>,
+) -> Result<Vec<RecoveredJournal>, String> {
+    let mut recovered = Vec::new();
+    for path in journal_paths(dir, active)? {
+        match recover_journal(&path, key.as_slice()) {
+            Ok(Some(journal)) => recovered.push(journal),
+            Ok(None) => {}
+            Err(e) => warn!("Skipping unreadable journal {}: {}", path.display(), e),
+        }
+    }
+    Ok(recovered)
+}
+
+/// Remove the journals of sessions that did not end cleanly, or only the
+/// one named `id`, skipping `active`.
+pub fn remove_journals(dir: &Path, active: Option<&Path>, id: Option<&str>) -> Result<(), String> {
+    for path in journal_paths(dir, active)? {
+        if id.is_none() || id == path.file_stem().and_then(|s| s.to_str()) {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Encrypt the journals in `dir` other than `active` with `new` instead
+/// of any of `old`, or decrypt them without a new key. Lines already under
+/// `new` are kept, so an interrupted re-key can be repeated or rolled back.
+pub fn rekey_journals(
+    dir: &Path,
+    active: Option<&Path>,
+    old: &[&DataKey],
+    new: Option<&DataKey>,
+) -> Result<(), String> {
+    let keys: Vec<&DataKey> = old.iter().copied().chain(new).collect();
+    for path in journal_paths(dir, active)? {
+        let (lines, _) = read_lines(&path, &keys)?;
+        let mut data = Vec::new();
+        for line in &lines {
+            match line {
+                Ok(entry) => data.extend(encode_line(entry, new)?),
+                // Kept for a closer look, sealed if it was plain
+                Err(raw) => {
+                    match new.filter(|_| raw.first() == Some(&b'{')) {
+                        Some(key) => data.extend(BASE64.encode(key.seal(raw)?).into_bytes()),
+                        None => data.extend(raw),
+                    }
+                    data.push(b'\n');
+                }
+            }
+        }
+        let rekeyed = path.with_extension("jsonl.rekeyed");
+        fs::write(&rekeyed, data)
+            .and_then(|_| fs::rename(&rekeyed, &path))
+            .map_err(|e| format!("Failed to rewrite journal {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// A journal's lines, each an entry or the bytes of a line that cannot be
+/// read, and the length of its intact part, which ends before a torn line
+/// left by a crash mid-write. Only the last line can be torn, as it is the
+/// only one without a newline. A journal whose first line cannot be read,
+/// e.g. because it was encrypted with another key, is an error.
+fn read_lines(path: &Path, keys: &[&DataKey]) -> Result<(Vec<JournalLine>, usize), String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let mut lines = Vec::new();
+    let mut valid = 0;
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        let Some(line) = line.strip_suffix(b"\n") else {
+            break;
+        };
+        valid += line.len() + 1;
+        match decode_line(line, keys) {
+            Ok(entry) => lines.push(Ok(entry)),
+            Err(e) if lines.is_empty() => return Err(e),
+            Err(e) => {
+                warn!(
+                    "Skipping unreadable line {} of journal {}: {}",
+                    lines.len() + 1,
+                    path.display(),
+                    e
+                );
+                lines.push(Err(line.to_vec()));
+            }
+        }
+    }
+    Ok((lines, valid))
+}
+
+/// Replay one journal. A torn line at its end is truncated away. `None` if
+/// nothing was journaled.
+fn recover_journal(path: &Path, keys: &[&DataKey]) -> Result<Option<RecoveredJournal>, String> {
+    let (lines, valid) = read_lines(path, keys)?;
+    let len = fs::metadata(path).map_err(|e| e.to_string())?.len() as usize;
+    if valid < len {
+        warn!(
+            "Truncating torn journal {} to {} bytes",
+            path.display(),
+            valid
+        );
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_len(valid as u64))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let mut entries = lines.into_iter().flatten();
+    let Some(JournalEntry::Start {
+        mut session,
+        sources,
+    }) = entries.next()
+    else {
+        return Ok(None);
+    };
+    let mut segments: Vec<TranscriptSegment> = Vec::new();
+    for entry in entries {
+        match entry {
+            JournalEntry::Segment { segment } => segments.push(segment),
+            JournalEntry::Replace {
+                source,
+                start,
+                end,
+                segments: replacements,
+            } => {
+                segments
+                    .retain(|s| s.source != source || s.timestamp < start || s.timestamp >= end);
+                segments.extend(replacements);
+            }
+            JournalEntry::Change {
+                removed,
+                segments: added,
+            } => {
+                segments.retain(|s| !s.utterance_id.is_some_and(|id| removed.contains(&id)));
+                segments.extend(added);
+            }
+            JournalEntry::Transcript { segments: edited } => segments = edited,
+            JournalEntry::Session { session: edited } => session = edited,
+            JournalEntry::Start { .. } => {}
+        }
+    }
+    segments.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    Ok(Some(RecoveredJournal {
+        id: path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        session,
+        sources,
+        segments,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::types::SessionUpdate;
+    use chrono::{DateTime, Utc};
+
+    fn new_journal(dir: &Path, started_at: DateTime<Utc>) -> SessionJournal {
+        let sources = SourceConfig::default_sources(None, None, Vec::new());
+        let session = Session::new(started_at, "en", "deepgram", "nova-2", &sources);
+        SessionJournal::create(dir, &session, &sources, None).unwrap()
+    }
+
+    #[test]
+    fn test_recovers_unfinished_sessions() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let first = new_journal(dir, Utc::now() - chrono::Duration::minutes(5));
+        first.append(&TranscriptSegment::final_at("mic", 1.0, "hello"));
+        first.append(&TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("mic", 2.0, "wor")
+        });
+        first.append(&TranscriptSegment::final_at("system", 3.0, "hi there"));
+        first.append(&TranscriptSegment::final_at("mic", 4.0, "world"));
+        first.replace_range(
+            &AudioSource::mic(),
+            3.5,
+            5.0,
+            &[TranscriptSegment::final_at("mic", 4.2, "word")],
+        );
+        let second = new_journal(dir, Utc::now());
+        second.append(&TranscriptSegment {
+            utterance_id: Some(7),
+            ..TranscriptSegment::final_at("system", 1.0, "second")
+        });
+        second.append(&TranscriptSegment::final_at("system", 2.0, "kept"));
+        let mut session = Session::new(Utc::now(), "en", "deepgram", "nova-2", &[]);
+        session
+            .apply(&SessionUpdate {
+                tags: Some(vec!["standup".to_string()]),
+                ..Default::default()
+            })
+            .unwrap();
+        second.update_session(&session);
+        second.apply_change(&TranscriptChange {
+            removed: vec![7],
+            segments: vec![TranscriptSegment {
+                utterance_id: Some(7),
+                speaker: Some("Raj".to_string()),
+                ..TranscriptSegment::final_at("system", 1.0, "second take")
+            }],
+        });
+        let finished = new_journal(dir, Utc::now() + chrono::Duration::minutes(1));
+        finished.append(&TranscriptSegment::final_at("mic", 1.0, "done"));
+        finished.finish();
+        finished.append(&TranscriptSegment::final_at("mic", 2.0, "late"));
+
+        let recovered = recover_journals(dir, None, None).unwrap();
+        assert_eq!(recovered.len(), 2);
+        let texts: Vec<_> = recovered[0].segments.iter().map(|s| &s.text).collect();
+        assert_eq!(texts, ["hello", "hi there", "word"]);
+        assert_eq!(recovered[0].sources.len(), 2);
+        assert_eq!(recovered[1].segments.len(), 2);
+        assert_eq!(recovered[1].segments[0].text, "second take");
+        assert_eq!(recovered[1].segments[0].speaker.as_deref(), Some("Raj"));
+        assert!(recovered[0].session.tags.is_empty());
+        assert_eq!(recovered[1].session.tags, ["standup"]);
+
+        // The running session's journal is left alone
+        let recovered = recover_journals(dir, Some(second.path()), None).unwrap();
+        assert_eq!(recovered.len(), 1);
+        remove_journals(dir, Some(second.path()), None).unwrap();
+        assert!(!first.path().exists());
+        assert!(second.path().exists());
+    }
+
+    #[test]
+    fn test_truncates_torn_trailing_line() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let journal = new_journal(dir, Utc::now());
+        journal.append(&TranscriptSegment::final_at("mic", 1.0, "kept"));
+        let intact = fs::metadata(journal.path()).unwrap().len();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(journal.path())
+            .unwrap();
+        file.write_all(br#"{"type":"segment","segment":{"te"#)
+            .unwrap();
+
+        let recovered = recover_journals(dir, None, None).unwrap();
+        assert_eq!(recovered[0].segments.len(), 1);
+        assert_eq!(fs::metadata(journal.path()).unwrap().len(), intact);
+
+        // Appending after a truncation leaves a readable journal
+        journal.append(&TranscriptSegment::final_at("mic", 2.0, "after"));
+        let recovered = recover_journals(dir, None, None).unwrap();
+        assert_eq!(recovered[0].segments.len(), 2);
+        remove_journals(dir, None, Some(&recovered[0].id)).unwrap();
+        assert!(recover_journals(dir, None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_skips_unreadable_lines_of_legacy_journal() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("session-legacy.jsonl");
+        let sources = SourceConfig::default_sources(None, None, Vec::new());
+        let mut data = serde_json::json!({
+            "type": "start",
+            "started_at": Utc::now(),
+            "language": "de",
+            "sources": sources,
+        })
+        .to_string();
+        data.push('\n');
+        for (text, line) in [
+            ("before", None),
+            ("", Some("{\"type\":\"segm")),
+            ("after", None),
+        ] {
+            let entry = JournalEntry::Segment {
+                segment: TranscriptSegment::final_at("mic", 1.0, text),
+            };
+            data += &line.map_or_else(|| serde_json::to_string(&entry).unwrap(), str::to_string);
+            data.push('\n');
+        }
+        fs::write(&path, &data).unwrap();
+
+        let recovered = recover_journal(&path, &[]).unwrap().unwrap();
+        assert_eq!(recovered.session.language, "de");
+        assert_eq!(recovered.sources.len(), 2);
+        let texts: Vec<_> = recovered.segments.iter().map(|s| &s.text).collect();
+        assert_eq!(texts, ["before", "after"]);
+        // Only a torn last line is truncated
+        assert_eq!(fs::read_to_string(&path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_encrypted_journals_rekey() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let sources = SourceConfig::default_sources(None, None, Vec::new());
+        let session = Session::new(Utc::now(), "en", "deepgram", "nova-2", &sources);
+        let key = DataKey::generate();
+        let journal = SessionJournal::create(dir, &session, &sources, Some(key.clone())).unwrap();
+        journal.append(&TranscriptSegment::final_at("mic", 1.0, "confidential"));
+        let data = fs::read(journal.path()).unwrap();
+        assert!(!data.windows(12).any(|w| w == b"confidential"));
+
+        // Without the key the journal is skipped, not truncated
+        assert!(recover_journals(dir, None, None).unwrap().is_empty());
+        assert!(recover_journals(dir, None, Some(&DataKey::generate()))
+            .unwrap()
+            .is_empty());
+        assert_eq!(fs::read(journal.path()).unwrap(), data);
+        let recovered = recover_journals(dir, None, Some(&key)).unwrap();
+        assert_eq!(recovered[0].segments[0].text, "confidential");
+
+        let new_key = DataKey::generate();
+        rekey_journals(dir, None, &[&key], Some(&new_key)).unwrap();
+        // Repeating an interrupted re-key keeps lines already under the new key
+        rekey_journals(dir, None, &[&key], Some(&new_key)).unwrap();
+        assert!(recover_journals(dir, None, Some(&key)).unwrap().is_empty());
+        assert_eq!(
+            recover_journals(dir, None, Some(&new_key)).unwrap()[0]
+                .segments
+                .len(),
+            1
+        );
+        rekey_journals(dir, None, &[&new_key], None).unwrap();
+        assert_eq!(recover_journals(dir, None, None).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod credentials;
+pub mod encryption;
+pub mod journal;
+pub mod search;
+pub mod session_store;
+pub mod writer;
@@ -0,0 +1,251 @@
+use rusqlite::params;
+
+use crate::storage::session_store::{sql_error, SessionStore};
+use crate::transcription::types::{AudioSource, SearchHit, SearchQuery};
+
+/// FTS5 index over segment text, kept in step with `segments` by triggers.
+pub(super) const SCHEMA: &str = "
+    CREATE VIRTUAL TABLE segments_fts USING fts5(
+        text,
+        content = 'segments',
+        content_rowid = 'id',
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+    CREATE TRIGGER segments_fts_insert AFTER INSERT ON segments BEGIN
+        INSERT INTO segments_fts (rowid, text) VALUES (new.id, new.text);
+    END;
+    CREATE TRIGGER segments_fts_delete AFTER DELETE ON segments BEGIN
+        INSERT INTO segments_fts (segments_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    END;
+    CREATE TRIGGER segments_fts_update AFTER UPDATE OF text ON segments BEGIN
+        INSERT INTO segments_fts (segments_fts, rowid, text) VALUES ('delete', old.id, old.text);
+        INSERT INTO segments_fts (rowid, text) VALUES (new.id, new.text);
+    END;
+    INSERT INTO segments_fts (segments_fts) VALUES ('rebuild');
+";
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 500;
+
+/// Turn user input into an FTS5 query matching segments with every word.
+/// Words are quoted, so punctuation and FTS5 operators match literally.
+fn match_expression(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+impl SessionStore {
+    /// Segments matching `query`, ranked by BM25.
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, String> {
+        let Some(expression) = match_expression(&query.text) else {
+            return Ok(Vec::new());
+        };
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.session_id, sessions.title, sessions.started_at, s.source,
+                     COALESCE(s.speaker, json_extract(src.config, '$.label'), s.source)
+                         AS speaker_name,
+                     s.timestamp, s.text,
+                     snippet(segments_fts, 0, '**', '**', '…', 16), bm25(segments_fts)
+                 FROM segments_fts
+                 JOIN segments s ON s.id = segments_fts.rowid
+                 JOIN sessions ON sessions.id = s.session_id
+                 LEFT JOIN session_sources src ON src.session_id = s.session_id
+                     AND json_extract(src.config, '$.id') = s.source
+                 WHERE segments_fts MATCH ?1
+                     AND (?2 IS NULL OR s.session_id = ?2)
+                     AND (?3 IS NULL OR s.source = ?3)
+                     AND (?4 IS NULL OR lower(speaker_name) = lower(?4))
+                     AND (?5 IS NULL OR s.timestamp >= ?5)
+                     AND (?6 IS NULL OR s.timestamp < ?6)
+                     AND (?7 IS NULL OR sessions.started_at >= ?7)
+                     AND (?8 IS NULL OR sessions.started_at < ?8)
+                 ORDER BY bm25(segments_fts), sessions.started_at DESC, s.timestamp
+                 LIMIT ?9",
+            )
+            .map_err(sql_error)?;
+        let hits = stmt
+            .query_map(
+                params![
+                    expression,
+                    query.session_id,
+                    query.source.as_ref().map(|s| s.as_str()),
+                    query.speaker,
+                    query.start,
+                    query.end,
+                    query.started_after,
+                    query.started_before,
+                    limit
+                ],
+                |row| {
+                    Ok(SearchHit {
+                        session_id: row.get(0)?,
+                        session_title: row.get(1)?,
+                        session_started_at: row.get(2)?,
+                        source: AudioSource(row.get(3)?),
+                        speaker: row.get(4)?,
+                        timestamp: row.get(5)?,
+                        text: row.get(6)?,
+                        snippet: row.get(7)?,
+                        rank: row.get(8)?,
+                    })
+                },
+            )
+            .map_err(sql_error)?
+            .collect::<Result<_, _>>()
+            .map_err(sql_error)?;
+        Ok(hits)
+    }
+
+    /// Rebuild the search index from the stored segments, e.g. after it
+    /// was damaged.
+    pub fn rebuild_search_index(&self) -> Result<(), String> {
+        self.conn()?
+            .execute(
+                "INSERT INTO segments_fts (segments_fts) VALUES ('rebuild')",
+                [],
+            )
+            .map_err(sql_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::types::{Session, SourceConfig, TranscriptSegment};
+    use chrono::Utc;
+
+    fn store_with_meetings() -> (SessionStore, i64, i64) {
+        let store = SessionStore::open_in_memory().unwrap();
+        let sources = SourceConfig::default_sources(None, None, Vec::new());
+        let mut ids = Vec::new();
+        for lines in [
+            [
+                ("mic", 10.0, "Let's review the Q3 pricing change"),
+                ("system", 20.0, "The pricing change starts in July"),
+            ],
+            [
+                ("mic", 5.0, "Café budget for next quarter"),
+                ("system", 8.0, "No pricing updates today"),
+            ],
+        ] {
+            let session = Session::new(Utc::now(), "en", "deepgram", "nova-2", &sources);
+            let id = store
+                .create_session(&session, &sources, &serde_json::json!({}))
+                .unwrap();
+            for (source, timestamp, text) in lines {
+                let segment = TranscriptSegment::final_at(source, timestamp, text);
+                store.add_segment(id, &segment).unwrap();
+            }
+            ids.push(id);
+        }
+        (store, ids[0], ids[1])
+    }
+
+    fn search(store: &SessionStore, query: SearchQuery) -> Vec<SearchHit> {
+        store.search(&query).unwrap()
+    }
+
+    #[test]
+    fn test_ranks_hits_with_snippets() {
+        let (store, first, _) = store_with_meetings();
+        let hits = search(
+            &store,
+            SearchQuery {
+                text: "pricing change".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|h| h.session_id == first));
+        assert!(
+            hits[0].snippet.contains("**pricing**"),
+            "{}",
+            hits[0].snippet
+        );
+
+        // Accents and FTS5 syntax in the input are matched literally
+        let hits = search(
+            &store,
+            SearchQuery {
+                text: "cafe \"budget".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(hits.len(), 1);
+        assert!(store
+            .search(&SearchQuery {
+                text: "NEAR(".to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_filters_by_session_speaker_and_time() {
+        let (store, first, second) = store_with_meetings();
+        let pricing = |query: SearchQuery| {
+            search(
+                &store,
+                SearchQuery {
+                    text: "pricing".to_string(),
+                    ..query
+                },
+            )
+        };
+        assert_eq!(pricing(SearchQuery::default()).len(), 3);
+        let hits = pricing(SearchQuery {
+            session_id: Some(second),
+            ..Default::default()
+        });
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].speaker, "System Audio");
+
+        let hits = pricing(SearchQuery {
+            speaker: Some("you".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, first);
+
+        let hits = pricing(SearchQuery {
+            source: Some(AudioSource::system()),
+            start: Some(10.0),
+            ..Default::default()
+        });
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].timestamp, 20.0);
+    }
+
+    #[test]
+    fn test_index_follows_deletes_and_rebuilds() {
+        let (store, first, _) = store_with_meetings();
+        store.delete_session(first).unwrap();
+        let query = SearchQuery {
+            text: "pricing".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(store.search(&query).unwrap().len(), 1);
+
+        // Lose the index while the segments stay
+        store
+            .conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO segments_fts (segments_fts) VALUES ('delete-all')",
+                [],
+            )
+            .unwrap();
+        assert!(store.search(&query).unwrap().is_empty());
+
+        store.rebuild_search_index().unwrap();
+        assert_eq!(store.search(&query).unwrap().len(), 1);
+    }
+}
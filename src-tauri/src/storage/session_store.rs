@@ -0,0 +1,948 @@
+use chrono::{DateTime, Utc};
+use log::warn;
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::storage::encryption::DataKey;
+use crate::storage::search;
+use crate::transcription::types::{
+    AudioSource, EditRecord, PauseInterval, Session, SessionSummary, SessionUpdate, SourceConfig,
+    StoredSession, TranscriptChange, TranscriptSegment,
+};
+
+/// File name of the session database in the app data dir.
+pub const DATABASE_FILE: &str = "sessions.db";
+
+/// Schema changes in order; `user_version` counts how many were applied.
+const MIGRATIONS: &[&str] = &[
+    SESSIONS_SCHEMA,
+    search::SCHEMA,
+    SESSION_METADATA,
+    SEGMENT_EDITS,
+    EDIT_HISTORY,
+    UNKNOWN_LEGACY_MODEL,
+];
+
+const SESSIONS_SCHEMA: &str = "
+    CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        ended_at TEXT,
+        language TEXT NOT NULL,
+        settings TEXT NOT NULL
+    );
+    CREATE TABLE session_sources (
+        session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        config TEXT NOT NULL,
+        PRIMARY KEY (session_id, position)
+    );
+    CREATE TABLE segments (
+        id INTEGER PRIMARY KEY,
+        session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+        source TEXT NOT NULL,
+        timestamp REAL NOT NULL,
+        text TEXT NOT NULL,
+        confidence REAL NOT NULL
+    );
+    CREATE INDEX segments_by_session ON segments(session_id, timestamp);
+    CREATE TABLE pauses (
+        session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+        source TEXT NOT NULL,
+        start_time REAL NOT NULL,
+        end_time REAL
+    );
+";
+
+/// How a session was captured and what the user noted about it. Sessions
+/// recorded before this have no devices.
+const SESSION_METADATA: &str = "
+    ALTER TABLE sessions ADD COLUMN provider TEXT NOT NULL DEFAULT 'deepgram';
+    ALTER TABLE sessions ADD COLUMN model TEXT NOT NULL DEFAULT 'nova-2';
+    ALTER TABLE sessions ADD COLUMN devices TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE sessions ADD COLUMN participants TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE sessions ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE sessions ADD COLUMN notes TEXT NOT NULL DEFAULT '';
+";
+
+/// Segments the user edited keep the recognizer's text; `speaker` is who
+/// they named as speaking it.
+const SEGMENT_EDITS: &str = "
+    ALTER TABLE segments ADD COLUMN speaker TEXT;
+    ALTER TABLE segments ADD COLUMN original_text TEXT;
+";
+
+/// Segments keep their utterance ID so later edits can find them, and each
+/// edit, undo and redo is kept as the session's audit trail.
+const EDIT_HISTORY: &str = "
+    ALTER TABLE segments ADD COLUMN utterance_id INTEGER;
+    CREATE INDEX segments_by_utterance ON segments(session_id, utterance_id);
+    CREATE TABLE segment_edits (
+        id INTEGER PRIMARY KEY,
+        session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+        action TEXT NOT NULL,
+        edit TEXT NOT NULL,
+        author TEXT NOT NULL,
+        at TEXT NOT NULL
+    );
+    CREATE INDEX segment_edits_by_session ON segment_edits(session_id, id);
+";
+
+/// `SESSION_METADATA` gave sessions recorded before it a model they may not
+/// have used. Those are the sessions without devices; their model is unknown.
+const UNKNOWN_LEGACY_MODEL: &str = "
+    UPDATE sessions SET model = '' WHERE devices = '[]';
+";
+
+const SUMMARY_COLUMNS: &str = "id, title, started_at, ended_at, language,
+    (SELECT COUNT(*) FROM segments WHERE session_id = sessions.id)";
+
+const SESSION_COLUMNS: &str = "id, title, started_at, ended_at, language, provider, model,
+    devices, participants, tags, notes";
+
+pub(super) fn sql_error(e: rusqlite::Error) -> String {
+    format!("Session database error: {}", e)
+}
+
+fn json_error(e: serde_json::Error) -> String {
+    format!("Session database error: {}", e)
+}
+
+/// `value` as an SQL string literal.
+fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// A column holding JSON, such as a session's tags.
+fn json_column<T: DeserializeOwned>(row: &Row, index: usize) -> rusqlite::Result<T> {
+    let json: String = row.get(index)?;
+    serde_json::from_str(&json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+}
+
+fn session_from_row(row: &Row) -> rusqlite::Result<Session> {
+    let mut session = Session {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        started_at: row.get(2)?,
+        ended_at: None,
+        duration: None,
+        language: row.get(4)?,
+        provider: row.get(5)?,
+        model: row.get(6)?,
+        devices: json_column(row, 7)?,
+        participants: json_column(row, 8)?,
+        tags: json_column(row, 9)?,
+        notes: row.get(10)?,
+    };
+    if let Some(ended_at) = row.get(3)? {
+        session.end(ended_at);
+    }
+    Ok(session)
+}
+
+fn insert_segment(
+    conn: &Connection,
+    session_id: i64,
+    segment: &TranscriptSegment,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT INTO segments (session_id, source, timestamp, text, confidence, speaker,
+             original_text, utterance_id)
+         SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8
+         WHERE EXISTS (SELECT 1 FROM sessions WHERE id = ?1)",
+        params![
+            session_id,
+            segment.source.as_str(),
+            segment.timestamp,
+            segment.text,
+            segment.confidence,
+            segment.speaker,
+            segment.original_text,
+            segment.utterance_id.map(|id| id as i64)
+        ],
+    )
+}
+
+fn summary_from_row(row: &Row) -> rusqlite::Result<SessionSummary> {
+    Ok(SessionSummary {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        started_at: row.get(2)?,
+        ended_at: row.get(3)?,
+        language: row.get(4)?,
+        segment_count: row.get(5)?,
+    })
+}
+
+/// Past and running sessions with their sources and final segments, kept in
+/// SQLite in the app data dir. With a key, the database is encrypted by
+/// SQLCipher.
+pub struct SessionStore {
+    conn: Mutex<Connection>,
+    /// Unset for an in-memory database.
+    path: Option<PathBuf>,
+    /// The key the database is encrypted with, to reopen it if re-keying
+    /// fails.
+    key: Mutex<Option<DataKey>>,
+}
+
+impl SessionStore {
+    pub fn open(path: &Path, key: Option<&DataKey>) -> Result<Self, String> {
+        Ok(Self {
+            conn: Mutex::new(connect(path, key)?),
+            path: Some(path.to_path_buf()),
+            key: Mutex::new(key.cloned()),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        let mut conn = Connection::open_in_memory().map_err(sql_error)?;
+        init(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            path: None,
+            key: Mutex::new(None),
+        })
+    }
+
+    pub(super) fn conn(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|e| e.to_string())
+    }
+
+    /// Record the start of a session and return its ID.
+    pub fn create_session(
+        &self,
+        session: &Session,
+        sources: &[SourceConfig],
+        settings: &serde_json::Value,
+    ) -> Result<i64, String> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(sql_error)?;
+        tx.execute(
+            "INSERT INTO sessions (title, started_at, language, settings, provider, model,
+                 devices, participants, tags, notes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                session.title,
+                session.started_at,
+                session.language,
+                settings.to_string(),
+                session.provider,
+                session.model,
+                serde_json::to_string(&session.devices).map_err(json_error)?,
+                serde_json::to_string(&session.participants).map_err(json_error)?,
+                serde_json::to_string(&session.tags).map_err(json_error)?,
+                session.notes
+            ],
+        )
+        .map_err(sql_error)?;
+        let id = tx.last_insert_rowid();
+        for (position, source) in sources.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO session_sources (session_id, position, config) VALUES (?1, ?2, ?3)",
+                params![
+                    id,
+                    position as i64,
+                    serde_json::to_string(source).map_err(json_error)?
+                ],
+            )
+            .map_err(sql_error)?;
+        }
+        tx.commit().map_err(sql_error)?;
+        Ok(id)
+    }
+
+    /// Record a final segment. Segments of a deleted session are dropped.
+    pub fn add_segment(&self, session_id: i64, segment: &TranscriptSegment) -> Result<(), String> {
+        insert_segment(&*self.conn()?, session_id, segment).map_err(sql_error)?;
+        Ok(())
+    }
+
+    /// Swap a source's segments in `[start, end)` for re-transcribed ones.
+    pub fn replace_segments(
+        &self,
+        session_id: i64,
+        source: &AudioSource,
+        start: f64,
+        end: f64,
+        segments: &[TranscriptSegment],
+    ) -> Result<(), String> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(sql_error)?;
+        tx.execute(
+            "DELETE FROM segments
+             WHERE session_id = ?1 AND source = ?2 AND timestamp >= ?3 AND timestamp < ?4",
+            params![session_id, source.as_str(), start, end],
+        )
+        .map_err(sql_error)?;
+        for segment in segments.iter().filter(|s| s.is_final) {
+            insert_segment(&tx, session_id, segment).map_err(sql_error)?;
+        }
+        tx.commit().map_err(sql_error)
+    }
+
+    /// Apply an edit, undo or redo of a session's transcript, and add it to
+    /// the session's audit trail.
+    pub fn apply_change(
+        &self,
+        session_id: i64,
+        change: &TranscriptChange,
+        record: &EditRecord,
+    ) -> Result<(), String> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(sql_error)?;
+        for &id in &change.removed {
+            tx.execute(
+                "DELETE FROM segments WHERE session_id = ?1 AND utterance_id = ?2",
+                params![session_id, id as i64],
+            )
+            .map_err(sql_error)?;
+        }
+        for segment in change.segments.iter().filter(|s| s.is_final) {
+            insert_segment(&tx, session_id, segment).map_err(sql_error)?;
+        }
+        let action = serde_json::to_value(record.action).map_err(json_error)?;
+        let edit = serde_json::to_string(&record.edit).map_err(json_error)?;
+        tx.execute(
+            "INSERT INTO segment_edits (session_id, action, edit, author, at)
+             SELECT ?1, ?2, ?3, ?4, ?5 WHERE EXISTS (SELECT 1 FROM sessions WHERE id = ?1)",
+            params![session_id, action.as_str(), edit, record.author, record.at],
+        )
+        .map_err(sql_error)?;
+        tx.commit().map_err(sql_error)
+    }
+
+    /// A session's edits, undos and redos, oldest first.
+    pub fn edit_history(&self, session_id: i64) -> Result<Vec<EditRecord>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT action, edit, author, at FROM segment_edits
+                 WHERE session_id = ?1 ORDER BY id",
+            )
+            .map_err(sql_error)?;
+        let records = stmt
+            .query_map([session_id], |row| {
+                let action: String = row.get(0)?;
+                Ok(EditRecord {
+                    action: serde_json::from_value(serde_json::Value::String(action)).map_err(
+                        |e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)),
+                    )?,
+                    edit: json_column(row, 1)?,
+                    author: row.get(2)?,
+                    at: row.get(3)?,
+                })
+            })
+            .map_err(sql_error)?
+            .collect::<Result<_, _>>()
+            .map_err(sql_error);
+        records
+    }
+
+    /// Mark a session ended and record its pauses. A session that already
+    /// ended is left as it is.
+    pub fn finish_session(
+        &self,
+        session_id: i64,
+        ended_at: DateTime<Utc>,
+        pauses: &[PauseInterval],
+    ) -> Result<(), String> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(sql_error)?;
+        let updated = tx
+            .execute(
+                "UPDATE sessions SET ended_at = ?2 WHERE id = ?1 AND ended_at IS NULL",
+                params![session_id, ended_at],
+            )
+            .map_err(sql_error)?;
+        if updated > 0 {
+            for pause in pauses {
+                tx.execute(
+                    "INSERT INTO pauses (session_id, source, start_time, end_time)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![session_id, pause.source.as_str(), pause.start, pause.end],
+                )
+                .map_err(sql_error)?;
+            }
+        }
+        tx.commit().map_err(sql_error)
+    }
+
+    /// All sessions, newest first.
+    pub fn list_sessions(&self) -> Result<Vec<SessionSummary>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM sessions ORDER BY started_at DESC, id DESC",
+                SUMMARY_COLUMNS
+            ))
+            .map_err(sql_error)?;
+        let sessions = stmt
+            .query_map([], summary_from_row)
+            .map_err(sql_error)?
+            .collect::<Result<_, _>>()
+            .map_err(sql_error)?;
+        Ok(sessions)
+    }
+
+    pub fn session(&self, id: i64) -> Result<StoredSession, String> {
+        let conn = self.conn()?;
+        let (session, settings) = conn
+            .query_row(
+                &format!(
+                    "SELECT {}, settings FROM sessions WHERE id = ?1",
+                    SESSION_COLUMNS
+                ),
+                [id],
+                |row| Ok((session_from_row(row)?, row.get::<_, String>(11)?)),
+            )
+            .optional()
+            .map_err(sql_error)?
+            .ok_or_else(|| format!("Session {} not found", id))?;
+
+        let sources = conn
+            .prepare("SELECT config FROM session_sources WHERE session_id = ?1 ORDER BY position")
+            .and_then(|mut stmt| {
+                stmt.query_map([id], |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(sql_error)?
+            .iter()
+            .map(|config| serde_json::from_str(config))
+            .collect::<Result<_, _>>()
+            .map_err(json_error)?;
+        let segments = conn
+            .prepare(
+                "SELECT source, timestamp, text, confidence, speaker, original_text, utterance_id
+                 FROM segments WHERE session_id = ?1 ORDER BY timestamp, id",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([id], |row| {
+                    Ok(TranscriptSegment {
+                        source: AudioSource(row.get(0)?),
+                        timestamp: row.get(1)?,
+                        text: row.get(2)?,
+                        confidence: row.get(3)?,
+                        is_final: true,
+                        utterance_id: row.get::<_, Option<i64>>(6)?.map(|id| id as u64),
+                        speaker: row.get(4)?,
+                        original_text: row.get(5)?,
+                    })
+                })?
+                .collect::<Result<_, _>>()
+            })
+            .map_err(sql_error)?;
+        let pauses = conn
+            .prepare(
+                "SELECT source, start_time, end_time FROM pauses
+                 WHERE session_id = ?1 ORDER BY start_time",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([id], |row| {
+                    Ok(PauseInterval {
+                        source: AudioSource(row.get(0)?),
+                        start: row.get(1)?,
+                        end: row.get(2)?,
+                    })
+                })?
+                .collect::<Result<_, _>>()
+            })
+            .map_err(sql_error)?;
+
+        Ok(StoredSession {
+            session,
+            sources,
+            settings: serde_json::from_str(&settings).map_err(json_error)?,
+            segments,
+            pauses,
+        })
+    }
+
+    /// Edit a session's title, participants, tags or notes.
+    pub fn update_session(&self, id: i64, update: &SessionUpdate) -> Result<Session, String> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(sql_error)?;
+        let mut session = tx
+            .query_row(
+                &format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS),
+                [id],
+                session_from_row,
+            )
+            .optional()
+            .map_err(sql_error)?
+            .ok_or_else(|| format!("Session {} not found", id))?;
+        session.apply(update)?;
+        tx.execute(
+            "UPDATE sessions SET title = ?2, participants = ?3, tags = ?4, notes = ?5
+             WHERE id = ?1",
+            params![
+                id,
+                session.title,
+                serde_json::to_string(&session.participants).map_err(json_error)?,
+                serde_json::to_string(&session.tags).map_err(json_error)?,
+                session.notes
+            ],
+        )
+        .map_err(sql_error)?;
+        tx.commit().map_err(sql_error)?;
+        Ok(session)
+    }
+
+    /// Encrypt the database with `key` instead of its current key, or
+    /// decrypt it without one. It is copied under the new key and then
+    /// takes the original's place; if that fails, the original is reopened.
+    pub fn rekey(&self, key: Option<&DataKey>) -> Result<(), String> {
+        let path = self
+            .path
+            .as_deref()
+            .ok_or_else(|| "Session database is not stored on disk".to_string())?;
+        let rekeyed = path.with_extension("db.rekeyed");
+        let _ = fs::remove_file(&rekeyed);
+        let mut conn = self.conn()?;
+        let mut current_key = self.key.lock().map_err(|e| e.to_string())?;
+        let secret = key.map(|k| k.sqlcipher_key()).unwrap_or_default();
+        conn.execute_batch(&format!(
+            "ATTACH DATABASE {} AS rekeyed KEY {};",
+            sql_string(&rekeyed.to_string_lossy()),
+            sql_string(&secret)
+        ))
+        .map_err(sql_error)?;
+        let exported = conn
+            .query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()))
+            .and_then(|_| {
+                conn.execute_batch(&format!(
+                    "PRAGMA rekeyed.user_version = {};",
+                    MIGRATIONS.len()
+                ))
+            });
+        conn.execute_batch("DETACH DATABASE rekeyed;")
+            .map_err(sql_error)?;
+        if let Err(e) = exported {
+            let _ = fs::remove_file(&rekeyed);
+            return Err(sql_error(e));
+        }
+
+        // The original is closed only to be replaced, and kept aside until
+        // the copy opens under the new key
+        let placeholder = Connection::open_in_memory().map_err(sql_error)?;
+        let old = std::mem::replace(&mut *conn, placeholder);
+        if let Err((old, e)) = old.close() {
+            *conn = old;
+            let _ = fs::remove_file(&rekeyed);
+            return Err(sql_error(e));
+        }
+        let original = path.with_extension("db.original");
+        let mut moved = false;
+        let swapped = fs::rename(path, &original)
+            .and_then(|_| {
+                moved = true;
+                fs::rename(&rekeyed, path).inspect_err(|_| {
+                    if fs::rename(&original, path).is_ok() {
+                        moved = false;
+                    }
+                })
+            })
+            .map_err(|e| format!("Failed to replace '{}': {}", path.display(), e))
+            .and_then(|_| connect(path, key));
+        match swapped {
+            Ok(rekeyed_conn) => {
+                *conn = rekeyed_conn;
+                *current_key = key.cloned();
+                let _ = fs::remove_file(&original);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&rekeyed);
+                if moved {
+                    let _ = fs::rename(path, &rekeyed);
+                    let _ = fs::rename(&original, path);
+                }
+                *conn = connect(path, current_key.as_ref()).map_err(|reopen| {
+                    format!("{}; reopening the original also failed: {}", e, reopen)
+                })?;
+                let _ = fs::remove_file(&rekeyed);
+                Err(e)
+            }
+        }
+    }
+
+    /// Delete a session with its sources, segments and pauses.
+    pub fn delete_session(&self, id: i64) -> Result<(), String> {
+        let deleted = self
+            .conn()?
+            .execute("DELETE FROM sessions WHERE id = ?1", [id])
+            .map_err(sql_error)?;
+        if deleted == 0 {
+            return Err(format!("Session {} not found", id));
+        }
+        Ok(())
+    }
+}
+
+fn connect(path: &Path, key: Option<&DataKey>) -> Result<Connection, String> {
+    let mut conn = Connection::open(path)
+        .map_err(|e| format!("Failed to open '{}': {}", path.display(), e))?;
+    if let Some(key) = key {
+        // Must come first; a wrong key fails on the first read below
+        conn.execute_batch(&format!(
+            "PRAGMA key = {};",
+            sql_string(&key.sqlcipher_key())
+        ))
+        .map_err(sql_error)?;
+    }
+    init(&mut conn)?;
+    Ok(conn)
+}
+
+fn init(conn: &mut Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;",
+    )
+    .map_err(sql_error)?;
+    migrate(conn)
+}
+
+/// Bring the schema up to date, refusing a database from a newer version.
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let version: usize = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(sql_error)?;
+    if version > MIGRATIONS.len() {
+        return Err(format!(
+            "Session database has schema version {}, newer than this app supports",
+            version
+        ));
+    }
+    let tx = conn.transaction().map_err(sql_error)?;
+    for migration in &MIGRATIONS[version..] {
+        tx.execute_batch(migration).map_err(sql_error)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())
+        .map_err(sql_error)?;
+    tx.commit().map_err(sql_error)
+}
+
+/// Records one session's segments from its pipelines. Failures are logged
+/// rather than returned, so transcription goes on without history.
+#[derive(Clone)]
+pub struct SessionRecorder {
+    store: Arc<SessionStore>,
+    session_id: i64,
+}
+
+impl SessionRecorder {
+    pub fn new(store: Arc<SessionStore>, session_id: i64) -> Self {
+        Self { store, session_id }
+    }
+
+    pub fn session_id(&self) -> i64 {
+        self.session_id
+    }
+
+    /// Record a segment if it is final.
+    pub fn record(&self, segment: &TranscriptSegment) {
+        if !segment.is_final {
+            return;
+        }
+        if let Err(e) = self.store.add_segment(self.session_id, segment) {
+            warn!("Failed to record segment: {}", e);
+        }
+    }
+
+    pub fn replace_range(
+        &self,
+        source: &AudioSource,
+        start: f64,
+        end: f64,
+        segments: &[TranscriptSegment],
+    ) {
+        if let Err(e) = self
+            .store
+            .replace_segments(self.session_id, source, start, end, segments)
+        {
+            warn!("Failed to record re-transcribed segments: {}", e);
+        }
+    }
+
+    /// Record an edit, undo or redo of the transcript.
+    pub fn apply_change(&self, change: &TranscriptChange, record: &EditRecord) {
+        if let Err(e) = self.store.apply_change(self.session_id, change, record) {
+            warn!("Failed to record transcript edit: {}", e);
+        }
+    }
+
+    /// Drop the session from the history, e.g. when it failed to start.
+    pub fn discard(&self) {
+        if let Err(e) = self.store.delete_session(self.session_id) {
+            warn!("Failed to discard session: {}", e);
+        }
+    }
+
+    /// Apply the user's edits to the recorded session.
+    pub fn update(&self, update: &SessionUpdate) -> Result<Session, String> {
+        self.store.update_session(self.session_id, update)
+    }
+
+    pub fn finish(&self, ended_at: DateTime<Utc>, pauses: &[PauseInterval]) {
+        if let Err(e) = self.store.finish_session(self.session_id, ended_at, pauses) {
+            warn!("Failed to record session end: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::types::{EditAction, TranscriptEdit};
+
+    fn new_session(store: &SessionStore) -> i64 {
+        let mut sources = SourceConfig::default_sources(Some("usb".to_string()), None, Vec::new());
+        sources[1].model = Some("nova-3".to_string());
+        let session = Session::new(Utc::now(), "en", "deepgram", "nova-2", &sources);
+        store
+            .create_session(&session, &sources, &serde_json::json!({ "font_size": 14 }))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_records_and_reopens_session() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let id = new_session(&store);
+        store
+            .add_segment(id, &TranscriptSegment::final_at("system", 2.0, "Hi there"))
+            .unwrap();
+        store
+            .add_segment(id, &TranscriptSegment::final_at("mic", 1.0, "Hello"))
+            .unwrap();
+        let pauses = [PauseInterval {
+            source: AudioSource::mic(),
+            start: 3.0,
+            end: Some(4.0),
+        }];
+        store.finish_session(id, Utc::now(), &pauses).unwrap();
+        // A second stop does not record the pauses again
+        store.finish_session(id, Utc::now(), &pauses).unwrap();
+
+        let session = store.session(id).unwrap();
+        assert!(session.session.ended_at.is_some());
+        assert!(session.session.duration.is_some());
+        assert_eq!(session.session.devices[0].device.as_deref(), Some("usb"));
+        assert_eq!(session.session.devices[0].model, "nova-2");
+        assert_eq!(session.session.devices[1].model, "nova-3");
+        assert_eq!(session.segments.len(), 2);
+        assert_eq!(session.segments[0].text, "Hello");
+        assert_eq!(session.sources.len(), 2);
+        assert_eq!(session.sources[0].device_id.as_deref(), Some("usb"));
+        assert_eq!(session.settings["font_size"], 14);
+        assert_eq!(session.pauses.len(), 1);
+    }
+
+    #[test]
+    fn test_sessions_from_before_metadata_have_no_model() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SESSIONS_SCHEMA).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        conn.execute(
+            "INSERT INTO sessions (title, started_at, language, settings)
+             VALUES ('Old', ?1, 'en', '{}')",
+            params![Utc::now()],
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+        let model: String = conn
+            .query_row("SELECT model FROM sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(model, "");
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let first = new_session(&store);
+        let second = new_session(&store);
+        let update = SessionUpdate {
+            title: Some("  Q3 pricing  ".to_string()),
+            participants: Some(vec!["Ana".to_string(), "Raj".to_string()]),
+            ..Default::default()
+        };
+        store.update_session(first, &update).unwrap();
+        let blank_title = SessionUpdate {
+            title: Some(" ".to_string()),
+            notes: Some("Dropped with the title".to_string()),
+            ..Default::default()
+        };
+        assert!(store.update_session(first, &blank_title).is_err());
+
+        let sessions = store.list_sessions().unwrap();
+        assert_eq!(sessions[0].id, second);
+        assert_eq!(sessions[1].title, "Q3 pricing");
+        let session = store.session(first).unwrap().session;
+        assert_eq!(session.participants, ["Ana", "Raj"]);
+        assert!(session.notes.is_empty());
+
+        store
+            .add_segment(first, &TranscriptSegment::final_at("mic", 1.0, "Hello"))
+            .unwrap();
+        store.delete_session(first).unwrap();
+        assert!(store.session(first).is_err());
+        assert!(store.delete_session(first).is_err());
+        // Late segments of a deleted session are dropped
+        store
+            .add_segment(first, &TranscriptSegment::final_at("mic", 2.0, "Bye"))
+            .unwrap();
+        assert_eq!(store.list_sessions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_replace_segments_in_range() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let id = new_session(&store);
+        for (t, text) in [(1.0, "one"), (2.0, "too"), (3.0, "three")] {
+            store
+                .add_segment(id, &TranscriptSegment::final_at("mic", t, text))
+                .unwrap();
+        }
+        store
+            .add_segment(id, &TranscriptSegment::final_at("system", 2.5, "other"))
+            .unwrap();
+
+        store
+            .replace_segments(
+                id,
+                &AudioSource::mic(),
+                1.5,
+                3.0,
+                &[TranscriptSegment::final_at("mic", 2.0, "two")],
+            )
+            .unwrap();
+        let texts: Vec<_> = store
+            .session(id)
+            .unwrap()
+            .segments
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(texts, ["one", "two", "other", "three"]);
+    }
+
+    #[test]
+    fn test_edited_transcript_keeps_original_text() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let id = new_session(&store);
+        let spoken = |utterance_id, timestamp, text| TranscriptSegment {
+            utterance_id: Some(utterance_id),
+            ..TranscriptSegment::final_at("mic", timestamp, text)
+        };
+        store.add_segment(id, &spoken(1, 1.0, "helo")).unwrap();
+        store.add_segment(id, &spoken(2, 2.0, "there")).unwrap();
+        let edited = TranscriptSegment {
+            speaker: Some("Ana".to_string()),
+            original_text: Some("helo".to_string()),
+            ..spoken(1, 1.0, "hello")
+        };
+        let record = EditRecord {
+            action: EditAction::Edit,
+            edit: TranscriptEdit::Edit {
+                id: 1,
+                text: "hello".to_string(),
+            },
+            author: "Ana".to_string(),
+            at: Utc::now(),
+        };
+        let change = TranscriptChange {
+            removed: vec![1],
+            segments: vec![edited],
+        };
+        store.apply_change(id, &change, &record).unwrap();
+
+        let segments = store.session(id).unwrap().segments;
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].text, "hello");
+        assert_eq!(segments[0].utterance_id, Some(1));
+        assert_eq!(segments[0].speaker.as_deref(), Some("Ana"));
+        assert_eq!(segments[0].original_text.as_deref(), Some("helo"));
+        assert_eq!(segments[1].text, "there");
+        let history = store.edit_history(id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].action, EditAction::Edit);
+        assert_eq!(history[0].author, "Ana");
+    }
+
+    #[test]
+    fn test_reopening_keeps_schema_and_data() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(DATABASE_FILE);
+        let id = new_session(&SessionStore::open(&path, None).unwrap());
+        let store = SessionStore::open(&path, None).unwrap();
+        assert_eq!(store.session(id).unwrap().session.language, "en");
+    }
+
+    #[test]
+    fn test_rekeys_database() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(DATABASE_FILE);
+        let store = SessionStore::open(&path, None).unwrap();
+        let id = new_session(&store);
+        store
+            .add_segment(
+                id,
+                &TranscriptSegment::final_at("mic", 1.0, "confidential pricing"),
+            )
+            .unwrap();
+
+        let key = DataKey::generate();
+        store.rekey(Some(&key)).unwrap();
+        assert_eq!(store.session(id).unwrap().segments.len(), 1);
+        drop(store);
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(!bytes.windows(12).any(|w| w == b"confidential"));
+        assert!(SessionStore::open(&path, None).is_err());
+        assert!(SessionStore::open(&path, Some(&DataKey::generate())).is_err());
+
+        let store = SessionStore::open(&path, Some(&key)).unwrap();
+        let query = crate::transcription::types::SearchQuery {
+            text: "pricing".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(store.search(&query).unwrap().len(), 1);
+        store.rekey(None).unwrap();
+        drop(store);
+        let store = SessionStore::open(&path, None).unwrap();
+        assert_eq!(store.list_sessions().unwrap().len(), 1);
+        // Nothing is left behind by the copy
+        let files: Vec<_> = std::fs::read_dir(temp.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| !name.to_string_lossy().ends_with("-wal"))
+            .filter(|name| !name.to_string_lossy().ends_with("-shm"))
+            .collect();
+        assert_eq!(files, [DATABASE_FILE]);
+    }
+
+    #[test]
+    fn test_failed_rekey_reopens_original() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(DATABASE_FILE);
+        let store = SessionStore::open(&path, None).unwrap();
+        let id = new_session(&store);
+        // Blocks moving the original aside
+        let blocker = path.with_extension("db.original");
+        std::fs::create_dir_all(blocker.join("taken")).unwrap();
+
+        assert!(store.rekey(Some(&DataKey::generate())).is_err());
+        store
+            .add_segment(id, &TranscriptSegment::final_at("mic", 1.0, "kept"))
+            .unwrap();
+        drop(store);
+        let store = SessionStore::open(&path, None).unwrap();
+        assert_eq!(store.session(id).unwrap().segments[0].text, "kept");
+    }
+}
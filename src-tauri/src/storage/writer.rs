@@ -0,0 +1,213 @@
+use chrono::{DateTime, Utc};
+use std::sync::mpsc;
+
+use crate::storage::journal::SessionJournal;
+use crate::storage::session_store::SessionRecorder;
+use crate::transcription::types::{
+    AudioSource, EditRecord, PauseInterval, TranscriptChange, TranscriptSegment,
+};
+
+enum SessionWrite {
+    Segment(TranscriptSegment),
+    Replace {
+        source: AudioSource,
+        start: f64,
+        end: f64,
+        segments: Vec<TranscriptSegment>,
+    },
+    Change(TranscriptChange, EditRecord),
+    FinishSession(DateTime<Utc>, Vec<PauseInterval>),
+    DiscardSession,
+    FinishJournal,
+    #[cfg(test)]
+    Flush(mpsc::Sender<()>),
+}
+
+/// Writes a session's transcript to its history record and journal on a
+/// thread of its own, in the order the writes were queued, so the blocking
+/// SQLite and file I/O stays off the async runtime. The thread ends once
+/// every clone of the writer is dropped.
+#[derive(Clone)]
+pub struct SessionWriter {
+    tx: mpsc::Sender<SessionWrite>,
+}
+
+impl SessionWriter {
+    pub fn spawn(recorder: Option<SessionRecorder>, journal: Option<SessionJournal>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for write in rx {
+                apply(write, recorder.as_ref(), journal.as_ref());
+            }
+        });
+        Self { tx }
+    }
+
+    /// Queue a segment, which is written if it is final.
+    pub fn segment(&self, segment: &TranscriptSegment) {
+        if segment.is_final {
+            self.send(SessionWrite::Segment(segment.clone()));
+        }
+    }
+
+    pub fn replace_range(
+        &self,
+        source: &AudioSource,
+        start: f64,
+        end: f64,
+        segments: &[TranscriptSegment],
+    ) {
+        self.send(SessionWrite::Replace {
+            source: source.clone(),
+            start,
+            end,
+            segments: segments.to_vec(),
+        });
+    }
+
+    /// Queue an edit, undo or redo of the transcript with its audit record.
+    pub fn change(&self, change: &TranscriptChange, record: &EditRecord) {
+        self.send(SessionWrite::Change(change.clone(), record.clone()));
+    }
+
+    /// Record the session's end and pauses.
+    pub fn finish_session(&self, ended_at: DateTime<Utc>, pauses: &[PauseInterval]) {
+        self.send(SessionWrite::FinishSession(ended_at, pauses.to_vec()));
+    }
+
+    /// Delete the session from the history, for one that failed to start.
+    pub fn discard_session(&self) {
+        self.send(SessionWrite::DiscardSession);
+    }
+
+    /// Remove the journal once the writes queued before are done.
+    pub fn finish_journal(&self) {
+        self.send(SessionWrite::FinishJournal);
+    }
+
+    /// Wait for the writes queued before.
+    #[cfg(test)]
+    pub fn flush(&self) {
+        let (tx, rx) = mpsc::channel();
+        self.send(SessionWrite::Flush(tx));
+        let _ = rx.recv();
+    }
+
+    fn send(&self, write: SessionWrite) {
+        // The thread only ends with the last sender
+        let _ = self.tx.send(write);
+    }
+}
+
+fn apply(
+    write: SessionWrite,
+    recorder: Option<&SessionRecorder>,
+    journal: Option<&SessionJournal>,
+) {
+    match write {
+        SessionWrite::Segment(segment) => {
+            if let Some(recorder) = recorder {
+                recorder.record(&segment);
+            }
+            if let Some(journal) = journal {
+                journal.append(&segment);
+            }
+        }
+        SessionWrite::Replace {
+            source,
+            start,
+            end,
+            segments,
+        } => {
+            if let Some(recorder) = recorder {
+                recorder.replace_range(&source, start, end, &segments);
+            }
+            if let Some(journal) = journal {
+                journal.replace_range(&source, start, end, &segments);
+            }
+        }
+        SessionWrite::Change(change, record) => {
+            if let Some(recorder) = recorder {
+                recorder.apply_change(&change, &record);
+            }
+            if let Some(journal) = journal {
+                journal.apply_change(&change);
+            }
+        }
+        SessionWrite::FinishSession(ended_at, pauses) => {
+            if let Some(recorder) = recorder {
+                recorder.finish(ended_at, &pauses);
+            }
+        }
+        SessionWrite::DiscardSession => {
+            if let Some(recorder) = recorder {
+                recorder.discard();
+            }
+        }
+        SessionWrite::FinishJournal => {
+            if let Some(journal) = journal {
+                journal.finish();
+            }
+        }
+        #[cfg(test)]
+        SessionWrite::Flush(done) => {
+            let _ = done.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::journal::recover_journals;
+    use crate::storage::session_store::SessionStore;
+    use crate::transcription::types::{Session, SourceConfig};
+    use chrono::Utc;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_writes_in_queue_order() {
+        let temp = tempfile::tempdir().unwrap();
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let sources = SourceConfig::default_sources(None, None, Vec::new());
+        let session = Session::new(Utc::now(), "en", "deepgram", "nova-2", &sources);
+        let id = store
+            .create_session(&session, &sources, &serde_json::Value::Null)
+            .unwrap();
+        let journal = SessionJournal::create(temp.path(), &session, &sources, None).unwrap();
+        let writer =
+            SessionWriter::spawn(Some(SessionRecorder::new(store.clone(), id)), Some(journal));
+
+        writer.segment(&TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("mic", 1.0, "hel")
+        });
+        writer.segment(&TranscriptSegment::final_at("mic", 1.0, "hello"));
+        writer.segment(&TranscriptSegment::final_at("mic", 2.0, "world"));
+        writer.replace_range(
+            &AudioSource::mic(),
+            1.5,
+            3.0,
+            &[TranscriptSegment::final_at("mic", 2.0, "word")],
+        );
+        writer.flush();
+        let texts: Vec<_> = store
+            .session(id)
+            .unwrap()
+            .segments
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(texts, ["hello", "word"]);
+        let recovered = recover_journals(temp.path(), None, None).unwrap();
+        assert_eq!(recovered[0].segments.len(), 2);
+
+        writer.finish_session(Utc::now(), &[]);
+        writer.finish_journal();
+        writer.flush();
+        assert!(store.session(id).unwrap().session.ended_at.is_some());
+        assert!(recover_journals(temp.path(), None, None)
+            .unwrap()
+            .is_empty());
+    }
+}
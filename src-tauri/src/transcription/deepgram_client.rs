@@ -1,18 +1,64 @@
 use futures_util::{SinkExt, StreamExt};
 use log::{error, info, warn};
 use std::collections::VecDeque;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::time;
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite;
 
+use crate::transcription::pipeline_stats::PipelineStats;
 use crate::transcription::types::{
     AudioSource, ConnectionStatus, ConnectionStatusEvent, DeepgramResponse, TranscriptSegment,
 };
 
+/// Provider name recorded with sessions.
+pub const PROVIDER: &str = "deepgram";
+
 const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+/// How long to keep reading results after CloseStream before giving up.
+const CLOSE_TIMEOUT_SECS: u64 = 10;
 const KEEPALIVE_INTERVAL_SECS: u64 = 10;
+// Deepgram closes a stream after ~10s without audio, so a paused source must
+// keep-alive well inside that window.
+const PAUSED_KEEPALIVE_INTERVAL_SECS: u64 = 3;
+/// How long to wait for Deepgram when checking an API key.
+const CHECK_TIMEOUT_SECS: u64 = 10;
+
+/// Control messages for a running stream.
+#[derive(Debug, Clone, Copy)]
+pub enum StreamCommand {
+    /// Flush buffered audio so pending words come back as final results,
+    /// then keep the idle stream alive until `Resume`.
+    Pause,
+    Resume,
+}
+
+fn keepalive_interval(paused: bool) -> time::Interval {
+    let secs = if paused {
+        PAUSED_KEEPALIVE_INTERVAL_SECS
+    } else {
+        KEEPALIVE_INTERVAL_SECS
+    };
+    time::interval(Duration::from_secs(secs))
+}
+
+/// 16 kHz mono PCM on its way to the provider.
+pub struct PcmChunk {
+    pub samples: Vec<i16>,
+    /// When the audio was captured, for latency stats.
+    pub captured_at: Instant,
+}
+
+/// Channels connecting a stream to its pipeline.
+pub struct StreamChannels {
+    pub audio_rx: mpsc::Receiver<PcmChunk>,
+    pub transcript_tx: mpsc::Sender<TranscriptSegment>,
+    pub status_tx: mpsc::Sender<ConnectionStatusEvent>,
+    pub command_rx: mpsc::Receiver<StreamCommand>,
+    pub shutdown_rx: mpsc::Receiver<()>,
+}
 
 pub struct DeepgramConfig {
     pub api_key: String,
@@ -49,23 +95,64 @@ fn build_ws_url(config: &DeepgramConfig) -> String {
     )
 }
 
+/// Check that Deepgram accepts `api_key` by opening a stream and closing it
+/// right away.
+pub async fn check_api_key(api_key: &str) -> Result<(), String> {
+    let config = DeepgramConfig {
+        api_key: api_key.to_string(),
+        ..Default::default()
+    };
+    let uri: tungstenite::http::Uri = build_ws_url(&config)
+        .parse()
+        .map_err(|e| format!("Invalid Deepgram URL: {}", e))?;
+    let request = tungstenite::ClientRequestBuilder::new(uri)
+        .with_header("Authorization", format!("Token {}", config.api_key));
+    let connected = time::timeout(
+        Duration::from_secs(CHECK_TIMEOUT_SECS),
+        connect_async(request),
+    )
+    .await
+    .map_err(|_| "Deepgram did not respond".to_string())?;
+    let mut stream = match connected {
+        Ok((stream, _)) => stream,
+        Err(tungstenite::Error::Http(response))
+            if matches!(response.status().as_u16(), 401 | 403) =>
+        {
+            return Err("Deepgram rejected the API key".to_string());
+        }
+        Err(e) => return Err(format!("Failed to reach Deepgram: {}", e)),
+    };
+    let close_msg = serde_json::json!({"type": "CloseStream"});
+    let _ = stream
+        .send(tungstenite::Message::text(close_msg.to_string()))
+        .await;
+    let _ = stream.close(None).await;
+    Ok(())
+}
+
 pub async fn run_deepgram_stream(
     source: AudioSource,
     config: DeepgramConfig,
-    mut audio_rx: mpsc::Receiver<Vec<i16>>,
-    transcript_tx: mpsc::Sender<TranscriptSegment>,
-    status_tx: mpsc::Sender<ConnectionStatusEvent>,
-    mut shutdown_rx: mpsc::Receiver<()>,
+    channels: StreamChannels,
+    stats: Arc<PipelineStats>,
 ) {
+    let StreamChannels {
+        mut audio_rx,
+        transcript_tx,
+        status_tx,
+        mut command_rx,
+        mut shutdown_rx,
+    } = channels;
     let url_str = build_ws_url(&config);
     let mut reconnect_attempts: u32 = 0;
-    let mut audio_buffer: VecDeque<Vec<i16>> = VecDeque::new();
+    let mut audio_buffer: VecDeque<PcmChunk> = VecDeque::new();
+    let mut paused = false;
     let max_buffer_chunks = 30 * 1000 / 200; // ~150 chunks of 200ms
 
     'outer: loop {
         let _ = status_tx
             .send(ConnectionStatusEvent {
-                source,
+                source: source.clone(),
                 status: if reconnect_attempts > 0 {
                     ConnectionStatus::Reconnecting
                 } else {
@@ -84,7 +171,7 @@ pub async fn run_deepgram_stream(
                 error!("Invalid Deepgram URL: {}", e);
                 let _ = status_tx
                     .send(ConnectionStatusEvent {
-                        source,
+                        source: source.clone(),
                         status: ConnectionStatus::Failed,
                         error: Some(format!("Invalid URL: {}", e)),
                     })
@@ -98,11 +185,11 @@ pub async fn run_deepgram_stream(
 
         let ws_stream = match connect_async(request).await {
             Ok((stream, _)) => {
-                info!("{:?} connected to Deepgram", source);
+                info!("{} connected to Deepgram", source);
                 reconnect_attempts = 0;
                 let _ = status_tx
                     .send(ConnectionStatusEvent {
-                        source,
+                        source: source.clone(),
                         status: ConnectionStatus::Connected,
                         error: None,
                     })
@@ -110,12 +197,12 @@ pub async fn run_deepgram_stream(
                 stream
             }
             Err(e) => {
-                error!("{:?} Deepgram connection failed: {}", source, e);
+                error!("{} Deepgram connection failed: {}", source, e);
                 reconnect_attempts += 1;
                 if reconnect_attempts >= MAX_RECONNECT_ATTEMPTS {
                     let _ = status_tx
                         .send(ConnectionStatusEvent {
-                            source,
+                            source: source.clone(),
                             status: ConnectionStatus::Failed,
                             error: Some(format!(
                                 "Failed after {} attempts: {}",
@@ -129,7 +216,7 @@ pub async fn run_deepgram_stream(
                     2u64.pow(reconnect_attempts.min(5)).min(30),
                 );
                 warn!(
-                    "{:?} reconnecting in {:?} (attempt {})",
+                    "{} reconnecting in {:?} (attempt {})",
                     source, backoff, reconnect_attempts
                 );
                 time::sleep(backoff).await;
@@ -141,41 +228,49 @@ pub async fn run_deepgram_stream(
 
         // Flush buffered audio
         while let Some(chunk) = audio_buffer.pop_front() {
-            let bytes: Vec<u8> = chunk.iter().flat_map(|s| s.to_le_bytes()).collect();
+            let bytes: Vec<u8> = chunk.samples.iter().flat_map(|s| s.to_le_bytes()).collect();
             if ws_sender
                 .send(tungstenite::Message::binary(bytes))
                 .await
                 .is_err()
             {
+                stats.record_dropped_frames(chunk.samples.len());
                 break;
             }
+            stats.record_sent(chunk.samples.len(), chunk.captured_at.elapsed());
         }
 
-        let mut keepalive_interval = time::interval(Duration::from_secs(KEEPALIVE_INTERVAL_SECS));
+        let mut keepalive = keepalive_interval(paused);
         let mut last_audio_sent = time::Instant::now();
+        // Set once CloseStream has been sent. Results still in flight (e.g. a
+        // file sent faster than real time) are read until Deepgram closes the
+        // socket or the deadline passes.
+        let mut close_deadline: Option<time::Instant> = None;
 
         loop {
             tokio::select! {
-                audio = audio_rx.recv() => {
+                audio = audio_rx.recv(), if close_deadline.is_none() => {
                     match audio {
                         Some(pcm) => {
-                            let bytes: Vec<u8> = pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+                            let bytes: Vec<u8> = pcm.samples.iter().flat_map(|s| s.to_le_bytes()).collect();
                             if let Err(e) = ws_sender.send(tungstenite::Message::binary(bytes)).await {
-                                warn!("{:?} WS send error: {}", source, e);
+                                warn!("{} WS send error: {}", source, e);
                                 audio_buffer.push_back(pcm);
                                 if audio_buffer.len() > max_buffer_chunks {
-                                    audio_buffer.pop_front();
+                                    if let Some(dropped) = audio_buffer.pop_front() {
+                                        stats.record_dropped_frames(dropped.samples.len());
+                                    }
                                 }
                                 continue 'outer;
                             }
+                            stats.record_sent(pcm.samples.len(), pcm.captured_at.elapsed());
                             last_audio_sent = time::Instant::now();
                         }
                         None => {
-                            // Audio channel closed — send CloseStream and exit
+                            // Audio channel closed — send CloseStream and drain
                             let close_msg = serde_json::json!({"type": "CloseStream"});
                             let _ = ws_sender.send(tungstenite::Message::text(close_msg.to_string())).await;
-                            time::sleep(Duration::from_secs(2)).await;
-                            break 'outer;
+                            close_deadline = Some(time::Instant::now() + Duration::from_secs(CLOSE_TIMEOUT_SECS));
                         }
                     }
                 }
@@ -190,35 +285,58 @@ pub async fn run_deepgram_stream(
                                             is_final,
                                             timestamp: resp.start_timestamp(),
                                             confidence,
-                                            source,
+                                            source: source.clone(),
+                                            utterance_id: None,
+                                            speaker: None,
+                                            original_text: None,
                                         };
                                         let _ = transcript_tx.send(segment).await;
                                     }
                                 }
-                                Err(e) => warn!("{:?} parse error: {}", source, e),
+                                Err(e) => warn!("{} parse error: {}", source, e),
                             }
                         }
                         Some(Ok(tungstenite::Message::Close(_))) | None => {
-                            warn!("{:?} Deepgram WS closed", source);
+                            if close_deadline.is_some() {
+                                break 'outer;
+                            }
+                            warn!("{} Deepgram WS closed", source);
                             continue 'outer;
                         }
                         Some(Err(e)) => {
-                            error!("{:?} Deepgram WS error: {}", source, e);
+                            error!("{} Deepgram WS error: {}", source, e);
+                            if close_deadline.is_some() {
+                                break 'outer;
+                            }
                             continue 'outer;
                         }
                         _ => {}
                     }
                 }
-                _ = keepalive_interval.tick() => {
-                    if last_audio_sent.elapsed() > Duration::from_secs(KEEPALIVE_INTERVAL_SECS) {
+                _ = keepalive.tick(), if close_deadline.is_none() => {
+                    if last_audio_sent.elapsed() > keepalive.period() {
                         let keepalive = serde_json::json!({"type": "KeepAlive"});
                         let _ = ws_sender.send(tungstenite::Message::text(keepalive.to_string())).await;
                     }
                 }
-                _ = shutdown_rx.recv() => {
+                Some(command) = command_rx.recv() => {
+                    match command {
+                        StreamCommand::Pause => {
+                            let finalize = serde_json::json!({"type": "Finalize"});
+                            let _ = ws_sender.send(tungstenite::Message::text(finalize.to_string())).await;
+                            paused = true;
+                        }
+                        StreamCommand::Resume => paused = false,
+                    }
+                    keepalive = keepalive_interval(paused);
+                }
+                _ = shutdown_rx.recv(), if close_deadline.is_none() => {
                     let close_msg = serde_json::json!({"type": "CloseStream"});
                     let _ = ws_sender.send(tungstenite::Message::text(close_msg.to_string())).await;
-                    time::sleep(Duration::from_secs(2)).await;
+                    close_deadline = Some(time::Instant::now() + Duration::from_secs(CLOSE_TIMEOUT_SECS));
+                }
+                _ = time::sleep_until(close_deadline.unwrap_or_else(time::Instant::now)), if close_deadline.is_some() => {
+                    warn!("{} Deepgram did not close the stream in time", source);
                     break 'outer;
                 }
             }
@@ -1,3 +1,7 @@
 pub mod deepgram_client;
+pub mod pipeline_stats;
+pub mod retranscribe;
 pub mod stream_manager;
+pub mod timeline;
+pub mod transcript_store;
 pub mod types;
@@ -0,0 +1,139 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::transcription::types::{AudioSource, PipelineStatsSnapshot};
+
+/// Capacity of the channel from a capture callback to its pipeline thread.
+pub const CAPTURE_QUEUE_CAPACITY: usize = 50;
+/// Capacity of the PCM channel from a pipeline thread to its provider stream.
+pub const PCM_QUEUE_CAPACITY: usize = 100;
+
+/// Counters for one source's audio path, shared between the capture
+/// callback, the pipeline thread and the provider stream.
+#[derive(Debug, Default)]
+pub struct PipelineStats {
+    chunks_captured: AtomicU64,
+    /// Chunks discarded because the capture queue was full.
+    chunks_dropped: AtomicU64,
+    /// 16 kHz frames handed to the provider.
+    frames_sent: AtomicU64,
+    /// 16 kHz frames discarded on the way to the provider, e.g. when the
+    /// reconnect buffer overflows.
+    frames_dropped: AtomicU64,
+    capture_queue: AtomicUsize,
+    capture_queue_peak: AtomicUsize,
+    pcm_queue: AtomicUsize,
+    pcm_queue_peak: AtomicUsize,
+    latency_us: AtomicU64,
+    latency_max_us: AtomicU64,
+    latency_sum_us: AtomicU64,
+    latency_count: AtomicU64,
+}
+
+impl PipelineStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a chunk leaving a capture callback, and the capture queue
+    /// length after the attempt.
+    pub fn record_captured(&self, dropped: bool, queue_len: usize) {
+        self.chunks_captured.fetch_add(1, Ordering::Relaxed);
+        if dropped {
+            self.chunks_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.record_capture_queue(queue_len);
+    }
+
+    /// Chunks produced by capture so far, including dropped ones.
+    pub fn chunks_captured(&self) -> u64 {
+        self.chunks_captured.load(Ordering::Relaxed)
+    }
+
+    pub fn record_capture_queue(&self, len: usize) {
+        self.capture_queue.store(len, Ordering::Relaxed);
+        self.capture_queue_peak.fetch_max(len, Ordering::Relaxed);
+    }
+
+    pub fn record_pcm_queue(&self, len: usize) {
+        self.pcm_queue.store(len, Ordering::Relaxed);
+        self.pcm_queue_peak.fetch_max(len, Ordering::Relaxed);
+    }
+
+    /// Record frames sent to the provider, `latency` after they were captured.
+    pub fn record_sent(&self, frames: usize, latency: Duration) {
+        let us = latency.as_micros() as u64;
+        self.frames_sent.fetch_add(frames as u64, Ordering::Relaxed);
+        self.latency_us.store(us, Ordering::Relaxed);
+        self.latency_max_us.fetch_max(us, Ordering::Relaxed);
+        self.latency_sum_us.fetch_add(us, Ordering::Relaxed);
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped_frames(&self, frames: usize) {
+        self.frames_dropped
+            .fetch_add(frames as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, source: AudioSource) -> PipelineStatsSnapshot {
+        let fill = |len: &AtomicUsize, capacity: usize| {
+            len.load(Ordering::Relaxed) as f32 / capacity as f32
+        };
+        let ms = |us: u64| us as f64 / 1000.0;
+        let latency_count = self.latency_count.load(Ordering::Relaxed);
+        let has_latency = latency_count > 0;
+
+        PipelineStatsSnapshot {
+            source,
+            chunks_captured: self.chunks_captured.load(Ordering::Relaxed),
+            chunks_dropped: self.chunks_dropped.load(Ordering::Relaxed),
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            frames_dropped: self.frames_dropped.load(Ordering::Relaxed),
+            capture_queue_fill: fill(&self.capture_queue, CAPTURE_QUEUE_CAPACITY),
+            capture_queue_peak: fill(&self.capture_queue_peak, CAPTURE_QUEUE_CAPACITY),
+            pcm_queue_fill: fill(&self.pcm_queue, PCM_QUEUE_CAPACITY),
+            pcm_queue_peak: fill(&self.pcm_queue_peak, PCM_QUEUE_CAPACITY),
+            latency_ms: has_latency.then(|| ms(self.latency_us.load(Ordering::Relaxed))),
+            avg_latency_ms: has_latency
+                .then(|| ms(self.latency_sum_us.load(Ordering::Relaxed)) / latency_count as f64),
+            max_latency_ms: has_latency.then(|| ms(self.latency_max_us.load(Ordering::Relaxed))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_counts_drops_and_peaks() {
+        let stats = PipelineStats::new();
+        stats.record_captured(false, 10);
+        stats.record_captured(true, CAPTURE_QUEUE_CAPACITY);
+        stats.record_capture_queue(5);
+        stats.record_pcm_queue(25);
+        stats.record_dropped_frames(3200);
+
+        let snapshot = stats.snapshot(AudioSource::mic());
+        assert_eq!(snapshot.chunks_captured, 2);
+        assert_eq!(snapshot.chunks_dropped, 1);
+        assert_eq!(snapshot.frames_dropped, 3200);
+        assert_eq!(snapshot.capture_queue_fill, 0.1);
+        assert_eq!(snapshot.capture_queue_peak, 1.0);
+        assert_eq!(snapshot.pcm_queue_fill, 0.25);
+        assert!(snapshot.latency_ms.is_none());
+    }
+
+    #[test]
+    fn test_snapshot_latency() {
+        let stats = PipelineStats::new();
+        stats.record_sent(1600, Duration::from_millis(40));
+        stats.record_sent(1600, Duration::from_millis(20));
+
+        let snapshot = stats.snapshot(AudioSource::system());
+        assert_eq!(snapshot.frames_sent, 3200);
+        assert_eq!(snapshot.latency_ms, Some(20.0));
+        assert_eq!(snapshot.avg_latency_ms, Some(30.0));
+        assert_eq!(snapshot.max_latency_ms, Some(40.0));
+    }
+}
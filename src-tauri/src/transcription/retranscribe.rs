@@ -0,0 +1,92 @@
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use crate::audio::resampler::{to_linear16, AudioResampler};
+use crate::audio::ring_buffer::AudioClip;
+use crate::transcription::deepgram_client::{
+    self, DeepgramConfig, PcmChunk, StreamChannels, StreamCommand,
+};
+use crate::transcription::pipeline_stats::{PipelineStats, PCM_QUEUE_CAPACITY};
+use crate::transcription::types::{
+    AudioSource, ConnectionStatus, ConnectionStatusEvent, TranscriptSegment,
+};
+
+/// Frames per chunk sent to the provider, 100ms at 16 kHz.
+const CHUNK_FRAMES: usize = 1600;
+
+/// Transcribe a clip on its own provider connection, separate from the live
+/// session. Returns the final segments, timestamped in session time.
+pub async fn retranscribe(
+    source: AudioSource,
+    config: DeepgramConfig,
+    clip: AudioClip,
+) -> Result<Vec<TranscriptSegment>, String> {
+    let mut resampler =
+        AudioResampler::new(clip.sample_rate, config.sample_rate, clip.channels as usize)?;
+    let samples: Vec<f32> = clip.samples.iter().map(|&s| s as f32 / 32768.0).collect();
+    let pcm = to_linear16(&resampler.process(&samples)?);
+
+    let (audio_tx, audio_rx) = mpsc::channel::<PcmChunk>(PCM_QUEUE_CAPACITY);
+    let (transcript_tx, mut transcript_rx) = mpsc::channel::<TranscriptSegment>(100);
+    let (status_tx, mut status_rx) = mpsc::channel::<ConnectionStatusEvent>(10);
+    // Held until the stream ends; it stops once its audio channel closes
+    let (_command_tx, command_rx) = mpsc::channel::<StreamCommand>(1);
+    let (_shutdown_tx, shutdown_rx) = mpsc::channel::<()>(1);
+    let channels = StreamChannels {
+        audio_rx,
+        transcript_tx,
+        status_tx,
+        command_rx,
+        shutdown_rx,
+    };
+    let stream = deepgram_client::run_deepgram_stream(
+        source,
+        config,
+        channels,
+        Arc::new(PipelineStats::new()),
+    );
+
+    // Sent as fast as the provider accepts it; dropping the sender at the
+    // end closes the stream, which flushes the last results.
+    let feed = async move {
+        for samples in pcm.chunks(CHUNK_FRAMES) {
+            let chunk = PcmChunk {
+                samples: samples.to_vec(),
+                captured_at: Instant::now(),
+            };
+            if audio_tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+    };
+    let collect = async {
+        let mut segments = Vec::new();
+        while let Some(mut segment) = transcript_rx.recv().await {
+            if segment.is_final {
+                segment.timestamp += clip.start;
+                segments.push(segment);
+            }
+        }
+        segments
+    };
+    let failure = async {
+        let mut error = None;
+        while let Some(status) = status_rx.recv().await {
+            if status.status == ConnectionStatus::Failed {
+                error = Some(
+                    status
+                        .error
+                        .unwrap_or_else(|| "Transcription failed".to_string()),
+                );
+            }
+        }
+        error
+    };
+
+    let ((), (), segments, error) = tokio::join!(stream, feed, collect, failure);
+    match error {
+        Some(e) => Err(e),
+        None => Ok(segments),
+    }
+}
@@ -1,257 +1,1396 @@
-use crossbeam_channel::Receiver as CbReceiver;
-use log::{error, info};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use cpal::traits::DeviceTrait;
+use crossbeam_channel::{
+    Receiver as CbReceiver, RecvTimeoutError, Sender as CbSender, TrySendError,
+};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::async_runtime::JoinHandle;
+use tauri::ipc::Channel;
 use tauri::{AppHandle, Emitter};
 use tokio::sync::mpsc;
 
-use crate::audio::mic_capture::{AudioChunk, MicCapture};
+use crate::audio::channel_map::apply_channel_map;
+use crate::audio::external_input;
+use crate::audio::file_decoder::{extract_channel, FileDecoder};
+use crate::audio::meter::{LevelMeter, METER_INTERVAL};
+use crate::audio::mic_capture::{AudioChunk, AudioSender, MicCapture, StreamHealth};
 use crate::audio::resampler::{to_linear16, AudioResampler};
+use crate::audio::ring_buffer::{AudioClip, AudioRingBuffer, DEFAULT_REWIND_SECS};
 use crate::audio::system_capture;
-use crate::transcription::deepgram_client::{self, DeepgramConfig};
+use crate::audio::watchdog::{
+    CaptureWatchdog, WatchdogEvent, DEFAULT_STALL_TIMEOUT, MAX_CAPTURE_RESTARTS,
+};
+use crate::settings::AppSettings;
+use crate::storage::encryption::DataKey;
+use crate::storage::journal::SessionJournal;
+use crate::storage::session_store::{SessionRecorder, SessionStore};
+use crate::storage::writer::SessionWriter;
+use crate::transcription::deepgram_client::{
+    self, DeepgramConfig, PcmChunk, StreamChannels, StreamCommand, PROVIDER,
+};
+use crate::transcription::pipeline_stats::{
+    PipelineStats, CAPTURE_QUEUE_CAPACITY, PCM_QUEUE_CAPACITY,
+};
+use crate::transcription::timeline::SourceTimeline;
+use crate::transcription::transcript_store::TranscriptStore;
 use crate::transcription::types::{
-    AudioLevelEvent, AudioSource, ConnectionStatus, ConnectionStatusEvent, TranscriptSegment,
+    AudioDeviceInfo, AudioLevelEvent, AudioSource, CaptureFormat, CaptureStatus,
+    CaptureStatusEvent, ChannelMap, ConnectionStatus, ConnectionStatusEvent, DeviceFallbackEvent,
+    FileProgressEvent, PauseInterval, PipelineHealthEvent, PipelineStatsSnapshot,
+    RetranscribeRequest, Session, SessionUpdate, SourceConfig, SourceKind, SourcePausedEvent,
+    TranscriptSegment,
 };
 
-/// Manages the lifecycle of mic and system audio capture streams,
-/// resampling, and Deepgram WebSocket connections.
+/// Sample rate of the PCM sent to the transcription provider.
+const TARGET_SAMPLE_RATE: u32 = 16000;
+
+/// How often to check whether the preferred mic has come back while running
+/// on a fallback device.
+const MIC_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+/// How often `pipeline-health` is emitted while a session runs.
+const HEALTH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Manages the lifecycle of a session's capture sources, each with its own
+/// resampling pipeline and Deepgram WebSocket connection.
 ///
-/// Because `cpal::Stream` is !Send on most platforms, the mic capture
-/// stream handle is kept on the thread that created it. We use an
+/// Because `cpal::Stream` is !Send on most platforms, an input source's
+/// capture stream handle is kept on the thread that created it. We use an
 /// `AtomicBool` stop flag to signal the capture thread to drop it.
 pub struct StreamManager {
-    mic_shutdown_tx: Option<mpsc::Sender<()>>,
-    system_shutdown_tx: Option<mpsc::Sender<()>>,
-    mic_stop_flag: Option<Arc<AtomicBool>>,
-    system_capture_handle: Option<Box<dyn system_capture::CaptureHandle>>,
+    sources: Vec<ActiveSource>,
+    /// Set while a file is being transcribed instead of live capture.
+    file_stop_flag: Option<Arc<AtomicBool>>,
+    session_start: Option<Instant>,
+    /// Transcription language of the session, unless a source overrides it.
+    language: String,
+    pauses: Vec<PauseInterval>,
+    levels: LevelSubscribers,
+    /// Stops the `pipeline-health` emitter of the running session.
+    health_stop_flag: Option<Arc<AtomicBool>>,
+    /// Session history, if its database could be opened.
+    session_store: Option<Arc<SessionStore>>,
+    /// The current (or last) session.
+    session: Option<Session>,
+    /// Records the current (or last) session in the history.
+    recorder: Option<SessionRecorder>,
+    /// Where session journals are kept, if the app data dir is known.
+    journal_dir: Option<PathBuf>,
+    /// Journal of the running session.
+    journal: Option<SessionJournal>,
+    /// Writes the current (or last) session's transcript to its record and
+    /// journal.
+    writer: Option<SessionWriter>,
+    /// Encrypts journals, if the app data is encrypted.
+    data_key: Option<DataKey>,
+    /// Transcript of the current (or last) session.
+    transcript: Arc<TranscriptStore>,
+}
+
+struct LevelSubscriber {
+    channel: Channel<AudioLevelEvent>,
+    spectrum: bool,
+}
+
+/// Frontend subscribers to level events. Metering only runs while someone
+/// is subscribed, and the spectrum only while a subscriber wants it.
+#[derive(Clone, Default)]
+pub struct LevelSubscribers {
+    inner: Arc<Mutex<HashMap<u32, LevelSubscriber>>>,
+    next_id: Arc<AtomicU32>,
+}
+
+impl LevelSubscribers {
+    pub fn subscribe(&self, channel: Channel<AudioLevelEvent>, spectrum: bool) -> u32 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut subscribers) = self.inner.lock() {
+            subscribers.insert(id, LevelSubscriber { channel, spectrum });
+        }
+        id
+    }
+
+    pub fn unsubscribe(&self, id: u32) {
+        if let Ok(mut subscribers) = self.inner.lock() {
+            subscribers.remove(&id);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.lock().map(|s| s.is_empty()).unwrap_or(true)
+    }
+
+    fn wants_spectrum(&self) -> bool {
+        self.inner
+            .lock()
+            .map(|s| s.values().any(|sub| sub.spectrum))
+            .unwrap_or(false)
+    }
+
+    /// Send to every subscriber, dropping those whose channel is gone
+    /// (e.g. a closed window). The spectrum is stripped for subscribers that
+    /// did not ask for it.
+    fn send(&self, event: AudioLevelEvent) {
+        let Ok(mut subscribers) = self.inner.lock() else {
+            return;
+        };
+        subscribers.retain(|_, sub| {
+            let event = if sub.spectrum || event.spectrum.is_none() {
+                event.clone()
+            } else {
+                AudioLevelEvent {
+                    spectrum: None,
+                    ..event.clone()
+                }
+            };
+            sub.channel.send(event).is_ok()
+        });
+    }
+}
+
+/// What every pipeline of a session shares.
+#[derive(Clone)]
+struct SessionContext {
+    /// Session time zero, when the session started.
+    epoch: Instant,
+    levels: LevelSubscribers,
+    transcript: Arc<TranscriptStore>,
+}
+
+/// A running capture source and its pipeline.
+struct ActiveSource {
+    config: SourceConfig,
+    capture: SourceCapture,
+    pipeline: PipelineControl,
+}
+
+/// What has to be stopped to end a source's capture.
+#[derive(Clone)]
+enum SourceCapture {
+    /// Capture thread owning the mic `cpal::Stream` or the system capture
+    /// handle, stopped through this flag.
+    Thread(Arc<AtomicBool>),
+    /// Fed by the file decoder, which is stopped through `file_stop_flag`.
+    File,
+}
+
+/// Handle to one source's running pipeline.
+struct PipelineControl {
+    shutdown_tx: mpsc::Sender<()>,
+    command_tx: mpsc::Sender<StreamCommand>,
+    /// While set, captured audio is dropped instead of sent to the provider.
+    paused: Arc<AtomicBool>,
+    timeline: Arc<Mutex<SourceTimeline>>,
+    stats: Arc<PipelineStats>,
+    /// Recent captured audio, for replay and re-transcription.
+    rewind: Arc<Mutex<AudioRingBuffer>>,
+    /// Completes once the stream has ended and all its transcripts were emitted.
+    finished: Option<JoinHandle<()>>,
 }
 
 impl StreamManager {
-    pub fn new() -> Self {
+    pub fn new(
+        session_store: Option<Arc<SessionStore>>,
+        journal_dir: Option<PathBuf>,
+        transcript: Arc<TranscriptStore>,
+    ) -> Self {
         Self {
-            mic_shutdown_tx: None,
-            system_shutdown_tx: None,
-            mic_stop_flag: None,
-            system_capture_handle: None,
+            sources: Vec::new(),
+            file_stop_flag: None,
+            session_start: None,
+            language: String::new(),
+            pauses: Vec::new(),
+            levels: LevelSubscribers::default(),
+            health_stop_flag: None,
+            session_store,
+            session: None,
+            recorder: None,
+            journal_dir,
+            journal: None,
+            writer: None,
+            data_key: None,
+            transcript,
+        }
+    }
+
+    pub fn level_subscribers(&self) -> LevelSubscribers {
+        self.levels.clone()
+    }
+
+    /// Seconds since the session started.
+    fn session_time(&self) -> f64 {
+        self.session_start
+            .map(|start| start.elapsed().as_secs_f64())
+            .unwrap_or(0.0)
+    }
+
+    fn pipeline_mut(&mut self, source: &AudioSource) -> Result<&mut PipelineControl, String> {
+        // File timestamps are positions in the file, which a pause would skew.
+        if self.file_stop_flag.is_some() {
+            return Err("Pausing is not supported while transcribing a file".to_string());
+        }
+        self.sources
+            .iter_mut()
+            .find(|s| s.config.id == *source)
+            .map(|s| &mut s.pipeline)
+            .ok_or_else(|| format!("Source '{}' is not running", source))
+    }
+
+    fn active_source(&self, source: &AudioSource) -> Result<&ActiveSource, String> {
+        self.sources
+            .iter()
+            .find(|s| s.config.id == *source)
+            .ok_or_else(|| format!("Unknown source '{}'", source))
+    }
+
+    /// Sources of the current (or last) session, in start order.
+    pub fn sources(&self) -> Vec<SourceConfig> {
+        self.sources.iter().map(|s| s.config.clone()).collect()
+    }
+
+    /// Drop, queue and latency stats of each source in the current (or last)
+    /// session.
+    pub fn pipeline_stats(&self) -> Vec<PipelineStatsSnapshot> {
+        self.sources
+            .iter()
+            .map(|s| s.pipeline.stats.snapshot(s.config.id.clone()))
+            .collect()
+    }
+
+    /// A source's buffered audio between session times `start` and `end`.
+    pub fn audio_clip(
+        &self,
+        source: &AudioSource,
+        start: f64,
+        end: f64,
+    ) -> Result<AudioClip, String> {
+        if end <= start {
+            return Err("Clip end must be after its start".to_string());
+        }
+        let rewind = self
+            .active_source(source)?
+            .pipeline
+            .rewind
+            .lock()
+            .map_err(|e| e.to_string())?;
+        rewind
+            .clip(start, end)
+            .ok_or_else(|| format!("No buffered {} audio in that range", source))
+    }
+
+    /// The clip and provider settings to transcribe a range again with.
+    /// The request's language and model override the source's.
+    pub fn retranscribe_job(
+        &self,
+        api_key: &str,
+        request: &RetranscribeRequest,
+    ) -> Result<(AudioClip, DeepgramConfig), String> {
+        let clip = self.audio_clip(&request.source, request.start, request.end)?;
+        let source = &self.active_source(&request.source)?.config;
+        let config = SourceConfig {
+            language: request.language.clone().or(source.language.clone()),
+            model: request.model.clone().or(source.model.clone()),
+            ..source.clone()
+        };
+        Ok((clip, provider_config(&config, api_key, &self.language)))
+    }
+
+    /// Pause intervals of the current (or last) session.
+    pub fn pauses(&self) -> &[PauseInterval] {
+        &self.pauses
+    }
+
+    /// The current (or last) session.
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// Edit the current (or last) session's metadata, along with its record
+    /// in the history and its journal.
+    pub fn update_session(&mut self, update: &SessionUpdate) -> Result<Session, String> {
+        let session = self
+            .session
+            .as_mut()
+            .ok_or_else(|| "No session has been started".to_string())?;
+        let mut updated = session.clone();
+        updated.apply(update)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.update(update)?;
+        }
+        if let Some(journal) = &self.journal {
+            journal.update_session(&updated);
+        }
+        *session = updated.clone();
+        Ok(updated)
+    }
+
+    /// Start a new session with an empty transcript, recorded in the
+    /// history and journaled.
+    /// Transcription goes on without either if they fail.
+    fn begin_session(&mut self, language: &str, sources: &[SourceConfig], settings: &AppSettings) {
+        let model = DeepgramConfig::default().model;
+        let mut session = Session::new(Utc::now(), language, PROVIDER, &model, sources);
+        self.record_session(&mut session, sources, settings);
+        self.open_journal(&session, sources);
+        self.writer = (self.recorder.is_some() || self.journal.is_some())
+            .then(|| SessionWriter::spawn(self.recorder.clone(), self.journal.clone()));
+        self.session = Some(session);
+        self.transcript.reset(self.writer.clone());
+    }
+
+    fn record_session(
+        &mut self,
+        session: &mut Session,
+        sources: &[SourceConfig],
+        settings: &AppSettings,
+    ) {
+        self.recorder = None;
+        let Some(store) = &self.session_store else {
+            return;
+        };
+        let snapshot = serde_json::to_value(settings).unwrap_or_default();
+        match store.create_session(session, sources, &snapshot) {
+            Ok(id) => {
+                session.id = Some(id);
+                self.recorder = Some(SessionRecorder::new(store.clone(), id));
+            }
+            Err(e) => warn!("Session will not be saved to history: {}", e),
+        }
+    }
+
+    /// Journal of the running session.
+    pub fn journal(&self) -> Option<SessionJournal> {
+        self.journal.clone()
+    }
+
+    /// Whether a session is running, including a file's that has not been
+    /// stopped yet.
+    pub fn is_running(&self) -> bool {
+        self.session.as_ref().is_some_and(|s| s.ended_at.is_none())
+    }
+
+    pub fn data_key(&self) -> Option<DataKey> {
+        self.data_key.clone()
+    }
+
+    /// Use `session_store` and `data_key` for later sessions, e.g. once the
+    /// app data was unlocked or re-keyed.
+    pub fn set_storage(
+        &mut self,
+        session_store: Option<Arc<SessionStore>>,
+        data_key: Option<DataKey>,
+    ) {
+        self.session_store = session_store;
+        self.data_key = data_key;
+    }
+
+    fn open_journal(&mut self, session: &Session, sources: &[SourceConfig]) {
+        self.journal = None;
+        let Some(dir) = &self.journal_dir else {
+            return;
+        };
+        match SessionJournal::create(dir, session, sources, self.data_key.clone()) {
+            Ok(journal) => self.journal = Some(journal),
+            Err(e) => warn!("Session will not be journaled: {}", e),
         }
     }
 
+    fn session_context(&self, epoch: Instant) -> SessionContext {
+        SessionContext {
+            epoch,
+            levels: self.levels.clone(),
+            transcript: self.transcript.clone(),
+        }
+    }
+
+    /// Stop sending one source's audio to the provider while keeping its
+    /// capture and connection alive. Pending audio is finalized so the last
+    /// words before the pause come back as final segments.
+    pub fn pause_source(
+        &mut self,
+        app_handle: &AppHandle,
+        source: AudioSource,
+    ) -> Result<(), String> {
+        let timestamp = self.session_time();
+        let pipeline = self.pipeline_mut(&source)?;
+        if pipeline.paused.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let _ = pipeline.command_tx.try_send(StreamCommand::Pause);
+
+        self.pauses.push(PauseInterval {
+            source: source.clone(),
+            start: timestamp,
+            end: None,
+        });
+        info!("{} paused", source);
+        let _ = app_handle.emit(
+            "source-paused",
+            SourcePausedEvent {
+                source,
+                paused: true,
+                timestamp,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn resume_source(
+        &mut self,
+        app_handle: &AppHandle,
+        source: AudioSource,
+    ) -> Result<(), String> {
+        let timestamp = self.session_time();
+        let pipeline = self.pipeline_mut(&source)?;
+        // Audio captured after this lands after the gap on the source's
+        // timeline, as it is anchored at its capture time.
+        if !pipeline.paused.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let _ = pipeline.command_tx.try_send(StreamCommand::Resume);
+
+        if let Some(pause) = self
+            .pauses
+            .iter_mut()
+            .rev()
+            .find(|p| p.source == source && p.end.is_none())
+        {
+            pause.end = Some(timestamp);
+        }
+        info!("{} resumed", source);
+        let _ = app_handle.emit(
+            "source-paused",
+            SourcePausedEvent {
+                source,
+                paused: false,
+                timestamp,
+            },
+        );
+        Ok(())
+    }
+
+    /// Start a live session with the given sources. Each source's `language`
+    /// and `model` override the session-wide defaults. An input source that
+    /// fails to open aborts the session; a failing system or external source
+    /// is reported through `connection-status` and the session continues
+    /// without it. The session is recorded in the history along with
+    /// `settings`, and journaled until it stops.
     pub fn start(
         &mut self,
         app_handle: AppHandle,
         api_key: String,
         language: String,
-        mic_device_id: Option<String>,
-        _system_device_id: Option<String>,
+        sources: Vec<SourceConfig>,
+        settings: &AppSettings,
     ) -> Result<(), String> {
-        // --- Mic stream ---
-        // cpal::Stream is !Send, so we create and hold the capture on a
-        // dedicated std::thread. The thread exits when the stop flag is set,
-        // which also drops the Stream.
-        let (audio_cb_tx, audio_cb_rx) = crossbeam_channel::bounded::<AudioChunk>(50);
-        let mic_stop_flag = Arc::new(AtomicBool::new(false));
-        let stop_flag_clone = mic_stop_flag.clone();
-
-        // Channel to report mic start errors back to the caller.
-        let (result_tx, result_rx) = crossbeam_channel::bounded::<Result<(), String>>(1);
-
-        let mic_device_id_clone = mic_device_id.clone();
-        std::thread::spawn(move || {
-            let mic_capture = MicCapture::new();
-            let mic_device = match mic_capture.get_device(mic_device_id_clone.as_deref()) {
-                Ok(d) => d,
+        if sources.is_empty() {
+            return Err("No capture sources configured".to_string());
+        }
+        for source in &sources {
+            if matches!(&source.channel_map, ChannelMap::Split { outputs } if outputs.is_empty()) {
+                return Err(format!("{} splits into no channels", source.label));
+            }
+            if source.kind == SourceKind::External && source.external.is_none() {
+                return Err(format!("{} has no external input configured", source.label));
+            }
+        }
+        let routed: Vec<_> = sources.iter().flat_map(|s| s.routed_sources()).collect();
+        for (i, source) in routed.iter().enumerate() {
+            if routed[..i].iter().any(|s| s.id == source.id) {
+                return Err(format!("Duplicate source ID '{}'", source.id));
+            }
+        }
+
+        if !self.sources.is_empty() {
+            self.stop();
+        }
+        let epoch = Instant::now();
+        self.session_start = Some(epoch);
+        self.language = language.clone();
+        self.pauses.clear();
+        self.sources.clear();
+        self.begin_session(&language, &routed, settings);
+        let session = self.session_context(epoch);
+
+        let mut failures = Vec::new();
+        for config in sources {
+            let outputs = config.routed_sources();
+            let stats = Arc::new(PipelineStats::new());
+            let (capture, audio_rx) = match start_capture(&app_handle, &config, &stats) {
+                Ok(started) => started,
+                Err(e) if config.kind == SourceKind::Input => {
+                    self.abandon_session();
+                    return Err(format!("{} capture error: {}", config.label, e));
+                }
                 Err(e) => {
-                    let _ = result_tx.send(Err(e));
-                    return;
+                    error!("{} capture failed: {}", config.label, e);
+                    failures.push(format!("{}: {}", config.label, e));
+                    for output in outputs {
+                        let _ = app_handle.emit(
+                            "connection-status",
+                            ConnectionStatusEvent {
+                                source: output.id,
+                                status: ConnectionStatus::Failed,
+                                error: Some(e.clone()),
+                            },
+                        );
+                    }
+                    continue;
                 }
             };
 
-            let (_stream, _stream_config) =
-                match mic_capture.start_capture(&mic_device, audio_cb_tx) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        let _ = result_tx.send(Err(e));
-                        return;
+            // A split capture feeds one pipeline per output, each with its
+            // own stats; otherwise the pipeline shares the capture's stats.
+            let routes = if outputs.len() > 1 {
+                let (senders, routes): (Vec<_>, Vec<_>) = outputs
+                    .iter()
+                    .map(|_| {
+                        let (tx, rx) = crossbeam_channel::bounded(CAPTURE_QUEUE_CAPACITY);
+                        let stats = Arc::new(PipelineStats::new());
+                        ((tx, stats.clone()), (rx, stats))
+                    })
+                    .unzip();
+                fan_out(audio_rx, senders);
+                routes
+            } else {
+                vec![(audio_rx, stats)]
+            };
+            for (output, (audio_rx, stats)) in outputs.into_iter().zip(routes) {
+                let pipeline = spawn_stream_pipeline(
+                    app_handle.clone(),
+                    &output,
+                    provider_config(&output, &api_key, &language),
+                    audio_rx,
+                    stats,
+                    session.clone(),
+                );
+                self.sources.push(ActiveSource {
+                    config: output,
+                    capture: capture.clone(),
+                    pipeline,
+                });
+            }
+        }
+        if self.sources.is_empty() {
+            self.abandon_session();
+            return Err(format!(
+                "No source could be captured ({})",
+                failures.join("; ")
+            ));
+        }
+
+        self.start_health_monitor(&app_handle);
+        info!("Stream manager started with {} sources", self.sources.len());
+        Ok(())
+    }
+
+    /// Stop a session that failed to start, leaving no history entry or journal.
+    fn abandon_session(&mut self) {
+        self.stop();
+        self.recorder = None;
+        if let Some(writer) = self.writer.take() {
+            writer.discard_session();
+            writer.finish_journal();
+        }
+        self.journal = None;
+    }
+
+    /// Transcribe a media file through the same pipeline as live capture.
+    /// The file is decoded as fast as the provider accepts it and its
+    /// transcripts arrive as normal `transcript` events, timestamped by file
+    /// position. With `split_channels`, the left channel of a stereo file is
+    /// transcribed as the default mic source and the right as the default
+    /// system source; otherwise the whole file is the system source.
+    /// `on_finished` is called with the file's stop flag and end time once
+    /// the transcription ran to its end, to pass them to `finish_file`.
+    pub fn start_file(
+        &mut self,
+        app_handle: AppHandle,
+        api_key: String,
+        path: PathBuf,
+        split_channels: bool,
+        settings: &AppSettings,
+        on_finished: impl FnOnce(Arc<AtomicBool>, DateTime<Utc>) + Send + 'static,
+    ) -> Result<(), String> {
+        let decoder = FileDecoder::open(&path)?;
+        let split = split_channels && decoder.channels.unwrap_or(2) >= 2;
+        if !self.sources.is_empty() {
+            self.stop();
+        }
+        let epoch = Instant::now();
+        self.session_start = Some(epoch);
+        let language = settings.language.clone();
+        self.language = language.clone();
+        self.pauses.clear();
+        self.sources.clear();
+
+        let mut configs = SourceConfig::default_sources(None, None, Vec::new());
+        if !split {
+            configs.retain(|c| c.kind == SourceKind::System);
+        }
+        self.begin_session(&language, &configs, settings);
+        let session = self.session_context(epoch);
+
+        // Senders in channel order: [system] or [left/mic, right/system]
+        let mut senders = Vec::new();
+        let mut tasks = Vec::new();
+        for config in configs {
+            let (tx, rx) = crossbeam_channel::bounded::<AudioChunk>(CAPTURE_QUEUE_CAPACITY);
+            let stats = Arc::new(PipelineStats::new());
+            let mut pipeline = spawn_stream_pipeline(
+                app_handle.clone(),
+                &config,
+                provider_config(&config, &api_key, &language),
+                rx,
+                stats.clone(),
+                session.clone(),
+            );
+            tasks.extend(pipeline.finished.take());
+            senders.push((tx, stats));
+            self.sources.push(ActiveSource {
+                config,
+                capture: SourceCapture::File,
+                pipeline,
+            });
+        }
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        self.file_stop_flag = Some(stop_flag.clone());
+
+        let path = path.display().to_string();
+        let app_for_decode = app_handle.clone();
+        let path_for_decode = path.clone();
+        let file_stop_flag = stop_flag.clone();
+        let decode = tauri::async_runtime::spawn_blocking(move || {
+            decode_file(
+                &app_for_decode,
+                &path_for_decode,
+                decoder,
+                senders,
+                &stop_flag,
+                epoch,
+            )
+        });
+
+        self.start_health_monitor(&app_handle);
+        let health_stop_flag = self.health_stop_flag.clone();
+        let writer = self.writer.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = decode.await.map_err(|e| e.to_string()).and_then(|r| r);
+            for task in tasks {
+                let _ = task.await;
+            }
+            if let Some(flag) = health_stop_flag {
+                flag.store(true, Ordering::Relaxed);
+            }
+            let ended_at = Utc::now();
+            if let Some(writer) = writer {
+                writer.finish_session(ended_at, &[]);
+                writer.finish_journal();
+            }
+            on_finished(file_stop_flag, ended_at);
+            let (processed, duration) = match &result {
+                Ok(progress) => (progress.processed, progress.duration),
+                Err(_) => (0.0, None),
+            };
+            if let Err(e) = &result {
+                error!("File transcription failed: {}", e);
+            }
+            let _ = app_handle.emit(
+                "file-progress",
+                FileProgressEvent {
+                    path,
+                    processed,
+                    duration,
+                    done: true,
+                    error: result.err(),
+                },
+            );
+        });
+
+        info!("File transcription started");
+        Ok(())
+    }
+
+    /// Clear the state of a file transcription that ran to its end, unless
+    /// it was stopped or replaced since. Its task already recorded the end
+    /// and removed the journal.
+    pub fn finish_file(&mut self, stop_flag: &Arc<AtomicBool>, ended_at: DateTime<Utc>) {
+        if !self
+            .file_stop_flag
+            .as_ref()
+            .is_some_and(|flag| Arc::ptr_eq(flag, stop_flag))
+        {
+            return;
+        }
+        self.file_stop_flag = None;
+        self.health_stop_flag = None;
+        self.journal = None;
+        if let Some(session) = self.session.as_mut().filter(|s| s.ended_at.is_none()) {
+            session.end(ended_at);
+        }
+        info!("File transcription finished");
+    }
+
+    pub fn stop(&mut self) {
+        // Close any open pause intervals at the end of the session
+        let end = self.session_time();
+        for pause in self.pauses.iter_mut().filter(|p| p.end.is_none()) {
+            pause.end = Some(end);
+        }
+
+        // Stop decoding a file, if one is being transcribed
+        let file_stop_flag = self.file_stop_flag.take();
+        if let Some(flag) = &file_stop_flag {
+            flag.store(true, Ordering::Relaxed);
+        }
+        if let Some(flag) = self.health_stop_flag.take() {
+            flag.store(true, Ordering::Relaxed);
+        }
+
+        // Segments still in flight are recorded after this
+        if let Some(session) = self.session.as_mut().filter(|s| s.ended_at.is_none()) {
+            let ended_at = Utc::now();
+            session.end(ended_at);
+            if let Some(writer) = &self.writer {
+                writer.finish_session(ended_at, &self.pauses);
+            }
+        }
+
+        // Signal Deepgram WS tasks to shut down and stop capture. Configs are
+        // kept so the last session can still be exported with its labels.
+        for source in &mut self.sources {
+            let _ = source.pipeline.shutdown_tx.try_send(());
+            // The capture thread drops its stream or handle once the flag is set
+            if let SourceCapture::Thread(flag) = &source.capture {
+                flag.store(true, Ordering::Relaxed);
+            }
+        }
+
+        // The journal is removed once the segments still in flight were
+        // written. A file's journal is removed when its transcription ends.
+        let finished: Vec<_> = self
+            .sources
+            .iter_mut()
+            .filter_map(|source| source.pipeline.finished.take())
+            .collect();
+        if self.journal.take().is_some() && file_stop_flag.is_none() {
+            if let Some(writer) = self.writer.clone() {
+                tauri::async_runtime::spawn(async move {
+                    for task in finished {
+                        let _ = task.await;
                     }
-                };
+                    writer.finish_journal();
+                });
+            }
+        }
 
-            let _ = result_tx.send(Ok(()));
+        info!("Stream manager stopped");
+    }
 
-            // Keep _stream alive (it captures audio via its callback).
-            // Wait until stop flag is set, then drop.
-            while !stop_flag_clone.load(Ordering::Relaxed) {
-                std::thread::sleep(std::time::Duration::from_millis(100));
+    /// Emit `pipeline-health` with every source's stats until the session
+    /// stops.
+    fn start_health_monitor(&mut self, app_handle: &AppHandle) {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        self.health_stop_flag = Some(stop_flag.clone());
+        let stats: Vec<_> = self
+            .sources
+            .iter()
+            .map(|s| (s.config.id.clone(), s.pipeline.stats.clone()))
+            .collect();
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_INTERVAL);
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if stop_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                let sources = stats
+                    .iter()
+                    .map(|(source, stats)| stats.snapshot(source.clone()))
+                    .collect();
+                let _ = app_handle.emit("pipeline-health", PipelineHealthEvent { sources });
             }
-            // _stream is dropped here, stopping capture.
         });
+    }
+}
+
+/// Provider settings for one source, falling back to the session defaults.
+fn provider_config(config: &SourceConfig, api_key: &str, language: &str) -> DeepgramConfig {
+    let defaults = DeepgramConfig::default();
+    DeepgramConfig {
+        api_key: api_key.to_string(),
+        language: config
+            .language
+            .clone()
+            .unwrap_or_else(|| language.to_string()),
+        model: config.model.clone().unwrap_or(defaults.model),
+        ..defaults
+    }
+}
 
-        result_rx
-            .recv()
-            .map_err(|e| format!("Mic config channel error: {}", e))?
-            .map_err(|e| format!("Mic capture error: {}", e))?;
+/// Start capturing a source on its own thread, which owns the capture and
+/// restarts it when it stalls. Returns once capture has first opened.
+///
+/// cpal::Stream is !Send, so a mic capture is created and held on the
+/// dedicated std::thread. The thread exits when the stop flag is set, which
+/// also drops the Stream or stops the capture handle.
+fn start_capture(
+    app_handle: &AppHandle,
+    config: &SourceConfig,
+    stats: &Arc<PipelineStats>,
+) -> Result<(SourceCapture, CbReceiver<AudioChunk>), String> {
+    let (audio_tx, audio_rx) = crossbeam_channel::bounded::<AudioChunk>(CAPTURE_QUEUE_CAPACITY);
+    let sender = AudioSender::new(audio_tx, stats.clone());
+    let stop_flag = Arc::new(AtomicBool::new(false));
+
+    // Channel to report capture start errors back to the caller.
+    let (result_tx, result_rx) = crossbeam_channel::bounded::<Result<(), String>>(1);
+
+    let app_for_capture = app_handle.clone();
+    let config = config.clone();
+    let stop_flag_clone = stop_flag.clone();
+    std::thread::spawn(move || match config.kind {
+        SourceKind::Input => {
+            run_mic_capture(app_for_capture, config, sender, stop_flag_clone, result_tx)
+        }
+        SourceKind::System | SourceKind::External => {
+            run_handle_capture(app_for_capture, config, sender, stop_flag_clone, result_tx)
+        }
+    });
 
-        self.mic_stop_flag = Some(mic_stop_flag);
+    result_rx
+        .recv()
+        .map_err(|e| format!("Capture start channel error: {}", e))??;
+    Ok((SourceCapture::Thread(stop_flag), audio_rx))
+}
 
-        let (mic_shutdown_tx, mic_shutdown_rx) = mpsc::channel::<()>(1);
-        self.mic_shutdown_tx = Some(mic_shutdown_tx);
+/// The source's stall timeout, or the default.
+fn stall_timeout(config: &SourceConfig) -> Duration {
+    config
+        .stall_timeout_secs
+        .map(|secs| Duration::from_secs(secs.max(1)))
+        .unwrap_or(DEFAULT_STALL_TIMEOUT)
+}
 
-        let config1 = DeepgramConfig {
-            api_key: api_key.clone(),
-            language: language.clone(),
-            ..Default::default()
+fn emit_capture_status(
+    app_handle: &AppHandle,
+    source: &AudioSource,
+    status: CaptureStatus,
+    error: Option<String>,
+) {
+    let _ = app_handle.emit(
+        "capture-status",
+        CaptureStatusEvent {
+            source: source.clone(),
+            status,
+            error,
+        },
+    );
+}
+
+/// Copy each captured chunk to the pipelines of a split source, which each
+/// select their own channel. A pipeline that falls behind loses chunks
+/// rather than holding up the others.
+fn fan_out(
+    audio_rx: CbReceiver<AudioChunk>,
+    routes: Vec<(CbSender<AudioChunk>, Arc<PipelineStats>)>,
+) {
+    std::thread::spawn(move || {
+        while let Ok(chunk) = audio_rx.recv() {
+            for (tx, stats) in &routes {
+                let dropped = matches!(tx.try_send(chunk.clone()), Err(TrySendError::Full(_)));
+                stats.record_captured(dropped, tx.len());
+            }
+        }
+    });
+}
+
+/// Decode a file into the pipelines until it ends or `stop_flag` is set,
+/// emitting `file-progress` about once per second of decoded audio. With
+/// several senders, channel `i` of the file goes to sender `i`; otherwise
+/// all channels go to the last sender. Returns the final progress.
+///
+/// Chunks are stamped with their file position after `epoch`, so the
+/// session timeline of a file is its own timeline.
+fn decode_file(
+    app_handle: &AppHandle,
+    path: &str,
+    mut decoder: FileDecoder,
+    senders: Vec<(CbSender<AudioChunk>, Arc<PipelineStats>)>,
+    stop_flag: &AtomicBool,
+    epoch: Instant,
+) -> Result<FileProgressEvent, String> {
+    let mut progress = FileProgressEvent {
+        path: path.to_string(),
+        processed: 0.0,
+        duration: decoder.duration,
+        done: false,
+        error: None,
+    };
+    let mut last_emitted = 0.0;
+
+    while !stop_flag.load(Ordering::Relaxed) {
+        let Some(mut chunk) = decoder.next_chunk()? else {
+            break;
         };
-        spawn_stream_pipeline(
-            app_handle.clone(),
-            AudioSource::Mic,
-            config1,
-            audio_cb_rx,
-            mic_shutdown_rx,
-        );
+        chunk.captured_at = epoch + Duration::from_secs_f64(progress.processed);
+        let frames = chunk.samples.len() / chunk.channels.max(1) as usize;
+        progress.processed += frames as f64 / chunk.sample_rate as f64;
 
-        // --- System stream ---
-        let sys_capture = system_capture::create_system_capture();
-        let (sys_cb_tx, sys_cb_rx) = crossbeam_channel::bounded::<AudioChunk>(50);
-        match sys_capture.start_capture(_system_device_id.as_deref(), sys_cb_tx) {
-            Ok(handle) => {
-                self.system_capture_handle = Some(handle);
-                let (sys_shutdown_tx, sys_shutdown_rx) = mpsc::channel::<()>(1);
-                self.system_shutdown_tx = Some(sys_shutdown_tx);
-
-                let config2 = DeepgramConfig {
-                    api_key,
-                    language,
-                    ..Default::default()
-                };
-                spawn_stream_pipeline(
-                    app_handle.clone(),
-                    AudioSource::System,
-                    config2,
-                    sys_cb_rx,
-                    sys_shutdown_rx,
-                );
+        // Blocking sends pace decoding to what the provider accepts, so
+        // nothing is dropped here.
+        let send = |(tx, stats): &(CbSender<AudioChunk>, Arc<PipelineStats>), chunk| {
+            let sent = tx.send(chunk).is_ok();
+            stats.record_captured(false, tx.len());
+            sent
+        };
+        let sent = if senders.len() > 1 && chunk.channels as usize >= senders.len() {
+            senders
+                .iter()
+                .enumerate()
+                .all(|(i, sender)| send(sender, extract_channel(&chunk, i as u16)))
+        } else {
+            senders.last().is_some_and(|sender| send(sender, chunk))
+        };
+        if !sent && !stop_flag.load(Ordering::Relaxed) {
+            return Err("Transcription pipeline closed".to_string());
+        }
+
+        if progress.processed - last_emitted >= 1.0 {
+            last_emitted = progress.processed;
+            let _ = app_handle.emit("file-progress", &progress);
+        }
+    }
+    // Dropping the senders closes the pipelines, which finalize the streams.
+    Ok(progress)
+}
+
+/// Open the preferred mic, or the default one if the preferred mic is missing.
+/// Returns the stream, its health tracker, the device info and whether the
+/// preferred device was the one opened. Fails if the device cannot capture
+/// in the requested `format`.
+fn open_mic(
+    mic_capture: &MicCapture,
+    preferred_id: Option<&str>,
+    format: &CaptureFormat,
+    sender: &AudioSender,
+) -> Result<(cpal::Stream, Arc<StreamHealth>, AudioDeviceInfo, bool), String> {
+    let ((device, id), is_preferred) = match mic_capture.get_device(preferred_id) {
+        Ok(d) => (d, true),
+        Err(e) if preferred_id.is_some() => {
+            warn!("{}; falling back to default mic", e);
+            (mic_capture.get_device(None)?, false)
+        }
+        Err(e) => return Err(e),
+    };
+    let health = Arc::new(StreamHealth::new());
+    let (stream, config) =
+        mic_capture.start_capture(&device, format, sender.clone(), health.clone())?;
+    let info = AudioDeviceInfo {
+        id,
+        name: device.name().unwrap_or_default(),
+        is_default: !is_preferred || preferred_id.is_none(),
+        sample_rate: Some(config.sample_rate.0),
+        channels: Some(config.channels),
+    };
+    Ok((stream, health, info, is_preferred))
+}
+
+/// Owns the mic `cpal::Stream` on its own thread until `stop_flag` is set.
+///
+/// If the stream dies (error callback, or no audio for the stall timeout,
+/// e.g. a Bluetooth headset disconnecting), capture is rebuilt on the
+/// preferred or default device, up to `MAX_CAPTURE_RESTARTS` times in a row.
+/// The same `sender` is reused, so the downstream pipeline and its Deepgram
+/// connection keep running untouched.
+fn run_mic_capture(
+    app_handle: AppHandle,
+    config: SourceConfig,
+    sender: AudioSender,
+    stop_flag: Arc<AtomicBool>,
+    result_tx: CbSender<Result<(), String>>,
+) {
+    let source = &config.id;
+    let preferred_id = config.device_id.as_deref();
+    let format = &config.capture_format;
+    let mic_capture = match MicCapture::with_host(format.host.as_deref()) {
+        Ok(mic_capture) => mic_capture,
+        Err(e) => {
+            let _ = result_tx.send(Err(e));
+            return;
+        }
+    };
+    let open = || {
+        let (stream, health, device, is_preferred) =
+            open_mic(&mic_capture, preferred_id, format, &sender)?;
+        info!("Mic capture running on '{}'", device.name);
+        if !is_preferred {
+            let _ = app_handle.emit(
+                "device-fallback",
+                DeviceFallbackEvent {
+                    source: source.clone(),
+                    requested_device_id: preferred_id.unwrap_or_default().to_string(),
+                    device,
+                },
+            );
+        }
+        Ok::<_, String>(((stream, health), !is_preferred))
+    };
+
+    let (mut current, mut on_fallback) = match open() {
+        Ok((stream, on_fallback)) => (Some(stream), on_fallback),
+        Err(e) => {
+            let _ = result_tx.send(Err(e));
+            return;
+        }
+    };
+    let _ = result_tx.send(Ok(()));
+
+    let stall_timeout = stall_timeout(&config);
+    let mut watchdog =
+        CaptureWatchdog::new(Some(stall_timeout), MAX_CAPTURE_RESTARTS, Instant::now());
+    let mut last_error = None;
+    let mut last_retry = Instant::now();
+
+    while !stop_flag.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(100));
+
+        let failed = current
+            .as_ref()
+            .is_none_or(|(_, health)| health.has_failed());
+        match watchdog.poll(sender.chunks_sent(), failed, Instant::now()) {
+            Some(WatchdogEvent::Stalled) => {
+                warn!("{} mic stream died; rebuilding capture", source);
+                emit_capture_status(&app_handle, source, CaptureStatus::Stalled, None);
+                current = None;
+                continue;
             }
-            Err(e) => {
-                error!("System audio capture failed: {}", e);
-                let _ = app_handle.emit(
-                    "connection-status",
-                    ConnectionStatusEvent {
-                        source: AudioSource::System,
-                        status: ConnectionStatus::Failed,
-                        error: Some(e),
-                    },
-                );
+            Some(WatchdogEvent::Restart(attempt)) => {
+                info!("Restarting {} mic capture (attempt {})", source, attempt);
+                emit_capture_status(&app_handle, source, CaptureStatus::Recovering, None);
+                // Drop the old stream before opening a new one on the same device.
+                current = None;
+                match open() {
+                    Ok((stream, fallback)) => {
+                        current = Some(stream);
+                        on_fallback = fallback;
+                    }
+                    Err(e) => {
+                        warn!("No usable mic ({}); retrying", e);
+                        last_error = Some(e);
+                    }
+                }
+                continue;
+            }
+            Some(WatchdogEvent::Recovered) => {
+                info!("{} mic capture recovered", source);
+                emit_capture_status(&app_handle, source, CaptureStatus::Running, None);
+                last_error = None;
+            }
+            Some(WatchdogEvent::GaveUp) => {
+                error!("{} mic capture could not be restarted", source);
+                let error = last_error
+                    .take()
+                    .unwrap_or_else(|| format!("No audio after {} restarts", MAX_CAPTURE_RESTARTS));
+                emit_capture_status(&app_handle, source, CaptureStatus::Failed, Some(error));
+                return;
             }
+            None => {}
         }
 
-        info!("Stream manager started");
-        Ok(())
+        // While on a fallback device, switch back once the preferred mic returns
+        if !on_fallback || watchdog.is_stalled() || last_retry.elapsed() < MIC_RETRY_INTERVAL {
+            continue;
+        }
+        last_retry = Instant::now();
+        if mic_capture.get_device(preferred_id).is_ok() {
+            info!("Preferred mic is back; switching to it");
+            current = None;
+            match open() {
+                Ok((stream, fallback)) => {
+                    current = Some(stream);
+                    on_fallback = fallback;
+                }
+                // The watchdog restarts capture on the default device
+                Err(e) => warn!("Failed to switch to the preferred mic: {}", e),
+            }
+        }
     }
+    // The stream is dropped here, stopping capture.
+}
 
-    pub fn stop(&mut self) {
-        // Signal Deepgram WS tasks to shut down
-        if let Some(tx) = self.mic_shutdown_tx.take() {
-            let _ = tx.try_send(());
-        }
-        if let Some(tx) = self.system_shutdown_tx.take() {
-            let _ = tx.try_send(());
+/// Owns a system or external source's capture handle on its own thread
+/// until `stop_flag` is set. If capture stops delivering audio (e.g. a
+/// PulseAudio read error), it is restarted up to `MAX_CAPTURE_RESTARTS`
+/// times in a row, reusing the same `sender` like the mic.
+fn run_handle_capture(
+    app_handle: AppHandle,
+    config: SourceConfig,
+    sender: AudioSender,
+    stop_flag: Arc<AtomicBool>,
+    result_tx: CbSender<Result<(), String>>,
+) {
+    let source = &config.id;
+    let system = (config.kind == SourceKind::System).then(system_capture::create_system_capture);
+    let open = || {
+        let handle = match (&system, &config.external) {
+            (Some(capture), _) if config.app_ids.is_empty() => {
+                capture.start_capture(config.device_id.as_deref(), sender.clone())?
+            }
+            (Some(capture), _) => capture.start_app_capture(&config.app_ids, sender.clone())?,
+            (None, Some(input)) => external_input::start_external_capture(input, sender.clone())?,
+            (None, None) => return Err("No external input configured".to_string()),
+        };
+        if let Some(device) = handle.fallback_device() {
+            let _ = app_handle.emit(
+                "device-fallback",
+                DeviceFallbackEvent {
+                    source: source.clone(),
+                    requested_device_id: config.device_id.clone().unwrap_or_default(),
+                    device,
+                },
+            );
         }
+        Ok::<_, String>(handle)
+    };
 
-        // Signal the mic capture thread to stop and drop the cpal::Stream
-        if let Some(flag) = self.mic_stop_flag.take() {
-            flag.store(true, Ordering::Relaxed);
+    let handle = match open() {
+        Ok(handle) => handle,
+        Err(e) => {
+            let _ = result_tx.send(Err(e));
+            return;
         }
+    };
+    let _ = result_tx.send(Ok(()));
+
+    // A backend that goes quiet while nothing plays only stalls on errors
+    let timeout = (!handle.idles_when_silent()).then(|| stall_timeout(&config));
+    let mut watchdog = CaptureWatchdog::new(timeout, MAX_CAPTURE_RESTARTS, Instant::now());
+    let mut current = Some(handle);
+    let mut last_error = None;
 
-        // Stop system capture
-        if let Some(handle) = self.system_capture_handle.take() {
-            handle.stop();
+    while !stop_flag.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(100));
+
+        let failed = current.as_ref().is_none_or(|handle| !handle.is_running());
+        match watchdog.poll(sender.chunks_sent(), failed, Instant::now()) {
+            Some(WatchdogEvent::Stalled) => {
+                warn!("{} capture stalled", source);
+                emit_capture_status(&app_handle, source, CaptureStatus::Stalled, None);
+                if let Some(handle) = current.take() {
+                    handle.stop();
+                }
+            }
+            Some(WatchdogEvent::Restart(attempt)) => {
+                info!("Restarting {} capture (attempt {})", source, attempt);
+                emit_capture_status(&app_handle, source, CaptureStatus::Recovering, None);
+                if let Some(handle) = current.take() {
+                    handle.stop();
+                }
+                match open() {
+                    Ok(handle) => current = Some(handle),
+                    Err(e) => {
+                        warn!("{} capture failed to restart: {}", source, e);
+                        last_error = Some(e);
+                    }
+                }
+            }
+            Some(WatchdogEvent::Recovered) => {
+                info!("{} capture recovered", source);
+                emit_capture_status(&app_handle, source, CaptureStatus::Running, None);
+                last_error = None;
+            }
+            Some(WatchdogEvent::GaveUp) => {
+                error!("{} capture could not be restarted", source);
+                let error = last_error
+                    .take()
+                    .unwrap_or_else(|| format!("No audio after {} restarts", MAX_CAPTURE_RESTARTS));
+                emit_capture_status(&app_handle, source, CaptureStatus::Failed, Some(error));
+                return;
+            }
+            None => {}
         }
+    }
 
-        info!("Stream manager stopped");
+    if let Some(handle) = current {
+        handle.stop();
     }
 }
 
 fn spawn_stream_pipeline(
     app_handle: AppHandle,
-    source: AudioSource,
+    source_config: &SourceConfig,
     config: DeepgramConfig,
     audio_rx: CbReceiver<AudioChunk>,
-    shutdown_rx: mpsc::Receiver<()>,
-) {
-    let (pcm_tx, pcm_rx) = mpsc::channel::<Vec<i16>>(100);
+    stats: Arc<PipelineStats>,
+    session: SessionContext,
+) -> PipelineControl {
+    let SessionContext {
+        epoch,
+        levels,
+        transcript,
+    } = session;
+    let source = source_config.id.clone();
+    let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>(1);
+    let (command_tx, command_rx) = mpsc::channel::<StreamCommand>(10);
+    let paused = Arc::new(AtomicBool::new(false));
+    let timeline = Arc::new(Mutex::new(SourceTimeline::new(epoch)));
+    let rewind = Arc::new(Mutex::new(AudioRingBuffer::new(
+        source_config.rewind_secs.unwrap_or(DEFAULT_REWIND_SECS),
+    )));
+    let (pcm_tx, pcm_rx) = mpsc::channel::<PcmChunk>(PCM_QUEUE_CAPACITY);
     let (transcript_tx, mut transcript_rx) = mpsc::channel::<TranscriptSegment>(100);
     let (status_tx, mut status_rx) = mpsc::channel::<ConnectionStatusEvent>(10);
 
-    // Thread: crossbeam -> resample -> compute level -> tokio channel
-    let app_for_level = app_handle.clone();
+    // Thread: crossbeam -> channel map -> meter -> rewind buffer -> resample -> tokio channel
+    let channel_map = source_config.channel_map.clone();
+    let paused_for_thread = paused.clone();
+    let timeline_for_thread = timeline.clone();
+    let rewind_for_thread = rewind.clone();
+    let source_for_thread = source.clone();
+    let stats_for_thread = stats.clone();
     std::thread::spawn(move || {
-        // Lazy-init: create resampler from first chunk's actual sample rate / channels
+        let stats = stats_for_thread;
+        let source = source_for_thread;
+        // Lazy-init: create resampler from first chunk's actual sample rate / channels.
+        // Re-created if the format changes, e.g. after a mic failover.
         let mut resampler: Option<AudioResampler> = None;
+        let mut meter = LevelMeter::default();
+        let mut warned_missing_channels = false;
+        // Levels are read on a timer rather than per chunk, so they go out at
+        // a steady rate and fall to silence when capture delivers nothing.
+        let mut next_reading = Instant::now() + METER_INTERVAL;
 
-        while let Ok(chunk) = audio_rx.recv() {
-            let r = match resampler.as_mut() {
-                Some(r) => r,
-                None => {
-                    match AudioResampler::new(chunk.sample_rate, 16000, chunk.channels as usize) {
-                        Ok(r) => resampler.insert(r),
-                        Err(e) => {
-                            error!("{:?} resampler init failed: {}", source, e);
-                            return;
-                        }
+        loop {
+            let now = Instant::now();
+            if now >= next_reading {
+                next_reading = (next_reading + METER_INTERVAL).max(now);
+                if !levels.is_empty() {
+                    let reading = meter.reading(now, levels.wants_spectrum());
+                    levels.send(AudioLevelEvent {
+                        source: source.clone(),
+                        level: reading.rms,
+                        rms_db: reading.rms_db,
+                        peak_db: reading.peak_db,
+                        vu_db: reading.vu_db,
+                        clipped: reading.clipped,
+                        spectrum: reading.spectrum,
+                    });
+                }
+            }
+            let chunk = match audio_rx.recv_deadline(next_reading) {
+                Ok(chunk) => chunk,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            stats.record_capture_queue(audio_rx.len());
+            let channel_count = chunk.channels;
+            let Some(chunk) = apply_channel_map(chunk, &channel_map) else {
+                if !warned_missing_channels {
+                    warn!(
+                        "{} capture has {} channels, none of them mapped; dropping its audio",
+                        source, channel_count
+                    );
+                    warned_missing_channels = true;
+                }
+                continue;
+            };
+            let channels = chunk.channels as usize;
+            if !resampler
+                .as_ref()
+                .is_some_and(|r| r.accepts(chunk.sample_rate, channels))
+            {
+                match AudioResampler::new(chunk.sample_rate, TARGET_SAMPLE_RATE, channels) {
+                    Ok(r) => resampler = Some(r),
+                    Err(e) => {
+                        error!("{} resampler init failed: {}", source, e);
+                        return;
                     }
                 }
+            }
+            let Some(r) = resampler.as_mut() else {
+                continue;
             };
-            // Compute RMS audio level
-            let rms = (chunk
-                .samples
-                .iter()
-                .map(|s| s * s)
-                .sum::<f32>()
-                / chunk.samples.len() as f32)
-                .sqrt();
-            let _ = app_for_level.emit(
-                "audio-level",
-                AudioLevelEvent {
-                    source,
-                    level: rms.min(1.0),
-                },
-            );
+            // Meter levels for subscribers, even while paused
+            if !levels.is_empty() {
+                meter.push(
+                    &chunk.samples,
+                    channels,
+                    chunk.sample_rate,
+                    levels.wants_spectrum(),
+                );
+            }
 
-            match r.process(&chunk.samples) {
-                Ok(resampled) => {
+            if paused_for_thread.load(Ordering::Relaxed) {
+                continue;
+            }
+            if let Ok(mut rewind) = rewind_for_thread.lock() {
+                let start = chunk.captured_at.saturating_duration_since(epoch);
+                rewind.push(
+                    &chunk.samples,
+                    chunk.sample_rate,
+                    chunk.channels,
+                    start.as_secs_f64(),
+                );
+            }
+
+            match r.process_captured(&chunk.samples, chunk.captured_at) {
+                Ok((resampled, captured_at)) => {
                     if !resampled.is_empty() {
-                        let linear16 = to_linear16(&resampled);
-                        if pcm_tx.blocking_send(linear16).is_err() {
+                        if let Ok(mut timeline) = timeline_for_thread.lock() {
+                            timeline.record_sent(
+                                resampled.len() as f64 / TARGET_SAMPLE_RATE as f64,
+                                captured_at,
+                            );
+                        }
+                        let pcm = PcmChunk {
+                            samples: to_linear16(&resampled),
+                            captured_at,
+                        };
+                        if pcm_tx.blocking_send(pcm).is_err() {
                             break;
                         }
+                        stats.record_pcm_queue(pcm_tx.max_capacity() - pcm_tx.capacity());
                     }
                 }
-                Err(e) => error!("{:?} resample error: {}", source, e),
+                Err(e) => error!("{} resample error: {}", source, e),
             }
         }
     });
 
     // Tokio task: Deepgram WS
+    let channels = StreamChannels {
+        audio_rx: pcm_rx,
+        transcript_tx,
+        status_tx,
+        command_rx,
+        shutdown_rx,
+    };
+    let stats_for_stream = stats.clone();
     tauri::async_runtime::spawn(async move {
-        deepgram_client::run_deepgram_stream(
-            source,
-            config,
-            pcm_rx,
-            transcript_tx,
-            status_tx,
-            shutdown_rx,
-        )
-        .await;
+        deepgram_client::run_deepgram_stream(source.clone(), config, channels, stats_for_stream)
+            .await;
     });
 
-    // Tokio task: forward transcripts to Tauri events
+    // Tokio task: reconcile transcripts in the store, which records them, and
+    // forward them to Tauri events
     let app_for_transcripts = app_handle.clone();
-    tauri::async_runtime::spawn(async move {
-        let event_name = match source {
-            AudioSource::Mic => "mic-transcript",
-            AudioSource::System => "system-transcript",
-        };
-        while let Some(segment) = transcript_rx.recv().await {
-            let _ = app_for_transcripts.emit(event_name, &segment);
+    let timeline_for_transcripts = timeline.clone();
+    let finished = tauri::async_runtime::spawn(async move {
+        while let Some(mut segment) = transcript_rx.recv().await {
+            if let Ok(timeline) = timeline_for_transcripts.lock() {
+                segment.timestamp = timeline.to_session_time(segment.timestamp);
+            }
+            // Final segments are queued for the session's record and journal
+            let segment = transcript.push(segment);
+            let _ = app_for_transcripts.emit("transcript", &segment);
         }
     });
 
@@ -261,4 +1400,13 @@ fn spawn_stream_pipeline(
             let _ = app_handle.emit("connection-status", &status);
         }
     });
+    PipelineControl {
+        shutdown_tx,
+        command_tx,
+        paused,
+        timeline,
+        stats,
+        rewind,
+        finished: Some(finished),
+    }
 }
@@ -0,0 +1,115 @@
+use std::time::Instant;
+
+/// Capture times further than this from where the previous audio predicts
+/// start a new anchor: a pause, dropped audio, or clock drift.
+const MAX_DRIFT_SECS: f64 = 0.05;
+
+/// Maps provider timestamps onto the session timeline for one source.
+///
+/// Deepgram timestamps count only the audio a connection has received, so
+/// each source's clock starts when its first audio arrives and stands still
+/// while the source is paused or its audio is dropped. Every contiguous
+/// stretch of sent audio is anchored at the capture time of its first frame,
+/// measured from the session epoch shared by all sources, so timestamps from
+/// different sources line up.
+#[derive(Debug)]
+pub struct SourceTimeline {
+    epoch: Instant,
+    /// Seconds of audio sent to the provider so far.
+    audio_sent: f64,
+    /// (audio position, session time) at the start of each stretch.
+    anchors: Vec<(f64, f64)>,
+}
+
+impl SourceTimeline {
+    pub fn new(epoch: Instant) -> Self {
+        Self {
+            epoch,
+            audio_sent: 0.0,
+            anchors: Vec::new(),
+        }
+    }
+
+    /// Record that `seconds` of audio, whose first frame was captured at
+    /// `captured_at`, were sent to the provider.
+    pub fn record_sent(&mut self, seconds: f64, captured_at: Instant) {
+        let session_time = captured_at
+            .saturating_duration_since(self.epoch)
+            .as_secs_f64();
+        let expected = self.to_session_time(self.audio_sent);
+        if self.anchors.is_empty() || (session_time - expected).abs() > MAX_DRIFT_SECS {
+            self.anchors.push((self.audio_sent, session_time));
+        }
+        self.audio_sent += seconds;
+    }
+
+    /// Convert a provider timestamp (seconds of sent audio) to session time.
+    pub fn to_session_time(&self, provider_time: f64) -> f64 {
+        // The last anchor at or before `provider_time`, or the first one
+        let i = self
+            .anchors
+            .partition_point(|(position, _)| *position <= provider_time);
+        match self.anchors.get(i.saturating_sub(1)) {
+            Some((position, session_time)) => session_time + (provider_time - position),
+            None => provider_time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(epoch: Instant, secs: f64) -> Instant {
+        epoch + Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn test_first_audio_sets_offset() {
+        let epoch = Instant::now();
+        let mut timeline = SourceTimeline::new(epoch);
+        // Audio starts 1.5s into the session, in 100ms chunks
+        for i in 0..50 {
+            timeline.record_sent(0.1, at(epoch, 1.5 + i as f64 * 0.1));
+        }
+        assert!((timeline.to_session_time(0.0) - 1.5).abs() < 1e-9);
+        assert!((timeline.to_session_time(4.5) - 6.0).abs() < 1e-9);
+        assert_eq!(timeline.anchors.len(), 1);
+    }
+
+    #[test]
+    fn test_gap_shifts_later_timestamps_only() {
+        let epoch = Instant::now();
+        let mut timeline = SourceTimeline::new(epoch);
+        timeline.record_sent(5.0, at(epoch, 0.0));
+        // Paused for 30 seconds
+        timeline.record_sent(5.0, at(epoch, 35.0));
+
+        assert_eq!(timeline.to_session_time(2.0), 2.0);
+        assert_eq!(timeline.to_session_time(6.0), 36.0);
+    }
+
+    #[test]
+    fn test_jitter_does_not_add_anchors() {
+        let epoch = Instant::now();
+        let mut timeline = SourceTimeline::new(epoch);
+        for i in 0..100 {
+            let jitter = if i % 2 == 0 { 0.01 } else { -0.01 };
+            timeline.record_sent(0.1, at(epoch, 1.0 + i as f64 * 0.1 + jitter));
+        }
+        assert_eq!(timeline.anchors.len(), 1);
+    }
+
+    #[test]
+    fn test_sources_share_the_epoch() {
+        let epoch = Instant::now();
+        let mut mic = SourceTimeline::new(epoch);
+        let mut system = SourceTimeline::new(epoch);
+        mic.record_sent(10.0, at(epoch, 0.2));
+        system.record_sent(10.0, at(epoch, 2.7));
+        // Words spoken at the same moment map to the same session time,
+        // though each provider connection reports a different timestamp
+        assert!((mic.to_session_time(5.0) - system.to_session_time(2.5)).abs() < 1e-9);
+    }
+}
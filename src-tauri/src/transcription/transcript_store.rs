@@ -0,0 +1,667 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::storage::writer::SessionWriter;
+use crate::transcription::types::{
+    AudioSource, EditAction, EditHistory, EditRecord, TranscriptChange, TranscriptEdit,
+    TranscriptPage, TranscriptSegment,
+};
+
+/// Most segments a page of the transcript holds.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Default)]
+struct Transcript {
+    /// Final segments and the latest interim of each open utterance, in
+    /// timeline order.
+    segments: Vec<TranscriptSegment>,
+    /// Per source, the utterance its interim results belong to until one
+    /// is final.
+    open: HashMap<AudioSource, u64>,
+    next_id: u64,
+    undo: Vec<AppliedEdit>,
+    redo: Vec<AppliedEdit>,
+    /// Every edit, undo and redo, oldest first.
+    audit: Vec<EditRecord>,
+    /// Records final segments and edits of the session, queued while the
+    /// transcript is locked so they are written in the order they happened.
+    writer: Option<SessionWriter>,
+}
+
+/// An edit with the segments it removed and added, so it can be undone and
+/// redone.
+struct AppliedEdit {
+    edit: TranscriptEdit,
+    before: Vec<TranscriptSegment>,
+    after: Vec<TranscriptSegment>,
+}
+
+impl Transcript {
+    fn new_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn remove(&mut self, utterance_id: u64) {
+        // Open utterances are the most recent ones
+        if let Some(i) = self
+            .segments
+            .iter()
+            .rposition(|s| s.utterance_id == Some(utterance_id))
+        {
+            self.segments.remove(i);
+        }
+    }
+
+    fn insert(&mut self, segment: TranscriptSegment) {
+        let i = self
+            .segments
+            .partition_point(|s| s.timestamp <= segment.timestamp);
+        self.segments.insert(i, segment);
+    }
+
+    fn final_segment(&self, utterance_id: u64) -> Result<&TranscriptSegment, String> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|s| s.utterance_id == Some(utterance_id))
+            .ok_or_else(|| format!("Segment {} not found", utterance_id))?;
+        if !segment.is_final {
+            return Err(format!(
+                "Segment {} is still being transcribed",
+                utterance_id
+            ));
+        }
+        Ok(segment)
+    }
+
+    /// The segments `edit` removes and the ones it adds in their place.
+    fn plan(
+        &mut self,
+        edit: &TranscriptEdit,
+    ) -> Result<(Vec<TranscriptSegment>, Vec<TranscriptSegment>), String> {
+        match edit {
+            TranscriptEdit::Edit { id, text } => {
+                let segment = self.final_segment(*id)?.clone();
+                let text = text.trim();
+                if text.is_empty() {
+                    return Err("Segment text cannot be empty".to_string());
+                }
+                let mut edited = segment.clone();
+                edited
+                    .original_text
+                    .get_or_insert_with(|| segment.text.clone());
+                edited.text = text.to_string();
+                Ok((vec![segment], vec![edited]))
+            }
+            TranscriptEdit::Split { id, at, timestamp } => {
+                let segment = self.final_segment(*id)?.clone();
+                let at = segment
+                    .text
+                    .char_indices()
+                    .nth(*at)
+                    .map_or(segment.text.len(), |(i, _)| i);
+                let (head, tail) = segment.text.split_at(at);
+                let (head, tail) = (head.trim_end(), tail.trim_start());
+                if head.is_empty() || tail.is_empty() {
+                    return Err("Split point must fall inside the segment's text".to_string());
+                }
+                let timestamp = timestamp.unwrap_or(segment.timestamp);
+                if timestamp < segment.timestamp {
+                    return Err("The second part cannot start before the first".to_string());
+                }
+                // Both parts keep the whole recognizer output
+                let original = segment
+                    .original_text
+                    .clone()
+                    .unwrap_or_else(|| segment.text.clone());
+                let first = TranscriptSegment {
+                    text: head.to_string(),
+                    original_text: Some(original.clone()),
+                    ..segment.clone()
+                };
+                let second = TranscriptSegment {
+                    text: tail.to_string(),
+                    timestamp,
+                    utterance_id: Some(self.new_id()),
+                    original_text: Some(original),
+                    ..segment.clone()
+                };
+                Ok((vec![segment], vec![first, second]))
+            }
+            TranscriptEdit::Merge { id, other } => {
+                if id == other {
+                    return Err("Cannot merge a segment with itself".to_string());
+                }
+                let mut first = self.final_segment(*id)?.clone();
+                let mut second = self.final_segment(*other)?.clone();
+                if first.source != second.source {
+                    return Err("Only segments from the same source can be merged".to_string());
+                }
+                if second.timestamp < first.timestamp {
+                    std::mem::swap(&mut first, &mut second);
+                }
+                // Parts of a split segment share its original text
+                let original_text = match (&first.original_text, &second.original_text) {
+                    (Some(a), Some(b)) if a == b => Some(a.clone()),
+                    (None, None) => None,
+                    (a, b) => Some(format!(
+                        "{} {}",
+                        a.as_ref().unwrap_or(&first.text),
+                        b.as_ref().unwrap_or(&second.text)
+                    )),
+                };
+                let merged = TranscriptSegment {
+                    text: format!("{} {}", first.text, second.text),
+                    confidence: first.confidence.min(second.confidence),
+                    original_text,
+                    ..first.clone()
+                };
+                Ok((vec![first, second], vec![merged]))
+            }
+            TranscriptEdit::Delete { id } => Ok((vec![self.final_segment(*id)?.clone()], vec![])),
+            TranscriptEdit::Reassign {
+                id,
+                source,
+                speaker,
+            } => {
+                if source.is_none() && speaker.is_none() {
+                    return Err("Nothing to reassign".to_string());
+                }
+                let segment = self.final_segment(*id)?.clone();
+                let mut reassigned = segment.clone();
+                if let Some(source) = source {
+                    reassigned.source = source.clone();
+                }
+                if let Some(speaker) = speaker {
+                    let speaker = speaker.trim();
+                    reassigned.speaker = (!speaker.is_empty()).then(|| speaker.to_string());
+                }
+                Ok((vec![segment], vec![reassigned]))
+            }
+        }
+    }
+
+    /// Swap `before` for `after`, unless part of `before` is gone, e.g.
+    /// because it was transcribed again.
+    fn swap(
+        &mut self,
+        before: &[TranscriptSegment],
+        after: &[TranscriptSegment],
+    ) -> Result<TranscriptChange, String> {
+        let removed: Vec<u64> = before.iter().filter_map(|s| s.utterance_id).collect();
+        if let Some(id) = removed
+            .iter()
+            .find(|&&id| !self.segments.iter().any(|s| s.utterance_id == Some(id)))
+        {
+            return Err(format!("Segment {} has been replaced since", id));
+        }
+        for &id in &removed {
+            self.remove(id);
+        }
+        for segment in after {
+            self.insert(segment.clone());
+        }
+        Ok(TranscriptChange {
+            removed,
+            segments: after.to_vec(),
+        })
+    }
+
+    /// Add an edit, undo or redo to the audit trail and queue it for the
+    /// session's record.
+    fn record(
+        &mut self,
+        action: EditAction,
+        edit: &TranscriptEdit,
+        author: &str,
+        change: &TranscriptChange,
+    ) {
+        let record = EditRecord {
+            action,
+            edit: edit.clone(),
+            author: author.to_string(),
+            at: Utc::now(),
+        };
+        if let Some(writer) = &self.writer {
+            writer.change(change, &record);
+        }
+        self.audit.push(record);
+    }
+}
+
+/// The current (or last) session's transcript, fed by every pipeline. The
+/// interim results of an utterance share its ID, and each replaces the one
+/// before it until the final segment does.
+#[derive(Default)]
+pub struct TranscriptStore {
+    transcript: Mutex<Transcript>,
+}
+
+impl TranscriptStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Transcript> {
+        // Segments stay valid even if a holder panicked
+        self.transcript
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Forget the transcript when a new session starts, whose segments and
+    /// edits are written through `writer`.
+    pub fn reset(&self, writer: Option<SessionWriter>) {
+        *self.lock() = Transcript {
+            writer,
+            ..Transcript::default()
+        };
+    }
+
+    /// Add a segment from a provider stream and return it with its
+    /// utterance ID. A final segment closes its source's open utterance.
+    pub fn push(&self, mut segment: TranscriptSegment) -> TranscriptSegment {
+        let mut transcript = self.lock();
+        let id = match transcript.open.get(&segment.source) {
+            Some(&id) => {
+                transcript.remove(id);
+                id
+            }
+            None => transcript.new_id(),
+        };
+        if segment.is_final {
+            transcript.open.remove(&segment.source);
+        } else {
+            transcript.open.insert(segment.source.clone(), id);
+        }
+        segment.utterance_id = Some(id);
+        transcript.insert(segment.clone());
+        if let Some(writer) = &transcript.writer {
+            writer.segment(&segment);
+        }
+        segment
+    }
+
+    /// Swap the final segments of `source` in `[start, end)` for
+    /// re-transcribed ones, returned with their new utterance IDs.
+    pub fn replace_range(
+        &self,
+        source: &AudioSource,
+        start: f64,
+        end: f64,
+        replacements: Vec<TranscriptSegment>,
+    ) -> Vec<TranscriptSegment> {
+        let mut transcript = self.lock();
+        transcript.segments.retain(|s| {
+            s.source != *source || !s.is_final || s.timestamp < start || s.timestamp >= end
+        });
+        let segments: Vec<_> = replacements
+            .into_iter()
+            .filter(|s| s.is_final)
+            .map(|mut segment| {
+                segment.utterance_id = Some(transcript.new_id());
+                transcript.insert(segment.clone());
+                segment
+            })
+            .collect();
+        if let Some(writer) = &transcript.writer {
+            writer.replace_range(source, start, end, &segments);
+        }
+        segments
+    }
+
+    /// Apply the user's `edit` to final segments. It can be undone until a
+    /// new session starts.
+    pub fn edit(&self, edit: TranscriptEdit, author: &str) -> Result<TranscriptChange, String> {
+        let mut transcript = self.lock();
+        let (before, after) = transcript.plan(&edit)?;
+        let change = transcript.swap(&before, &after)?;
+        transcript.record(EditAction::Edit, &edit, author, &change);
+        transcript.undo.push(AppliedEdit {
+            edit,
+            before,
+            after,
+        });
+        transcript.redo.clear();
+        Ok(change)
+    }
+
+    /// Revert the last edit. An edit whose segments have been replaced since
+    /// can no longer be undone and is dropped.
+    pub fn undo(&self, author: &str) -> Result<TranscriptChange, String> {
+        let mut transcript = self.lock();
+        let applied = transcript.undo.pop().ok_or("Nothing to undo")?;
+        let change = transcript.swap(&applied.after, &applied.before)?;
+        transcript.record(EditAction::Undo, &applied.edit, author, &change);
+        transcript.redo.push(applied);
+        Ok(change)
+    }
+
+    /// Apply the last undone edit again.
+    pub fn redo(&self, author: &str) -> Result<TranscriptChange, String> {
+        let mut transcript = self.lock();
+        let applied = transcript.redo.pop().ok_or("Nothing to redo")?;
+        let change = transcript.swap(&applied.before, &applied.after)?;
+        transcript.record(EditAction::Redo, &applied.edit, author, &change);
+        transcript.undo.push(applied);
+        Ok(change)
+    }
+
+    pub fn history(&self) -> EditHistory {
+        let transcript = self.lock();
+        EditHistory {
+            records: transcript.audit.clone(),
+            can_undo: !transcript.undo.is_empty(),
+            can_redo: !transcript.redo.is_empty(),
+        }
+    }
+
+    /// Final segments in timeline order.
+    pub fn finals(&self) -> Vec<TranscriptSegment> {
+        self.lock()
+            .segments
+            .iter()
+            .filter(|s| s.is_final)
+            .cloned()
+            .collect()
+    }
+
+    /// Up to `limit` segments from `offset` on, of one source or all of
+    /// them, in timeline order. Open utterances appear as their latest
+    /// interim result.
+    pub fn page(
+        &self,
+        source: Option<&AudioSource>,
+        offset: usize,
+        limit: usize,
+    ) -> TranscriptPage {
+        let transcript = self.lock();
+        let matching = || {
+            transcript
+                .segments
+                .iter()
+                .filter(|s| source.is_none_or(|source| s.source == *source))
+        };
+        TranscriptPage {
+            segments: matching()
+                .skip(offset)
+                .take(limit.min(MAX_PAGE_SIZE))
+                .cloned()
+                .collect(),
+            offset,
+            total: matching().count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(segments: &[TranscriptSegment]) -> Vec<&str> {
+        segments.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_finals_replace_interims_per_source() {
+        let store = TranscriptStore::new();
+        let first = store.push(TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("mic", 1.0, "hel")
+        });
+        store.push(TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("system", 1.2, "good")
+        });
+        let second = store.push(TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("mic", 1.0, "hello the")
+        });
+        assert_eq!(first.utterance_id, second.utterance_id);
+        let page = store.page(None, 0, 10);
+        assert_eq!(texts(&page.segments), ["hello the", "good"]);
+
+        let last = store.push(TranscriptSegment::final_at("mic", 1.0, "hello there"));
+        assert_eq!(last.utterance_id, first.utterance_id);
+        let next = store.push(TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("mic", 2.0, "how")
+        });
+        assert_ne!(next.utterance_id, first.utterance_id);
+        store.push(TranscriptSegment::final_at("system", 1.1, "good morning"));
+
+        assert_eq!(
+            texts(&store.page(None, 0, 10).segments),
+            ["hello there", "good morning", "how"]
+        );
+        assert_eq!(texts(&store.finals()), ["hello there", "good morning"]);
+    }
+
+    #[test]
+    fn test_replace_range_keeps_interims_and_other_sources() {
+        let store = TranscriptStore::new();
+        for (t, text) in [(1.0, "one"), (2.0, "too"), (3.0, "three")] {
+            store.push(TranscriptSegment::final_at("mic", t, text));
+        }
+        store.push(TranscriptSegment::final_at("system", 2.5, "other"));
+        store.push(TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("mic", 2.2, "inter")
+        });
+
+        let replaced = store.replace_range(
+            &AudioSource::mic(),
+            1.5,
+            3.0,
+            vec![TranscriptSegment::final_at("mic", 2.0, "two")],
+        );
+        assert!(replaced[0].utterance_id.is_some());
+        assert_eq!(
+            texts(&store.page(None, 0, 10).segments),
+            ["one", "two", "inter", "other", "three"]
+        );
+    }
+
+    #[test]
+    fn test_replace_range_of_partly_buffered_audio() {
+        use crate::audio::ring_buffer::AudioRingBuffer;
+
+        let store = TranscriptStore::new();
+        for (t, text) in [(0.5, "early"), (2.5, "too")] {
+            store.push(TranscriptSegment::final_at("mic", t, text));
+        }
+        // Only seconds 2 to 3 of the requested 0 to 3 are still buffered
+        let mut buffer = AudioRingBuffer::new(1);
+        buffer.push(&[0.5; 1000], 1000, 1, 2.0);
+        let clip = buffer.clip(0.0, 3.0).unwrap();
+
+        store.replace_range(
+            &AudioSource::mic(),
+            clip.start,
+            clip.end(),
+            vec![TranscriptSegment::final_at("mic", 2.5, "two")],
+        );
+        assert_eq!(texts(&store.finals()), ["early", "two"]);
+    }
+
+    #[test]
+    fn test_pages_by_source() {
+        let store = TranscriptStore::new();
+        for i in 0..5 {
+            store.push(TranscriptSegment::final_at(
+                "mic",
+                i as f64,
+                &format!("mic {}", i),
+            ));
+            store.push(TranscriptSegment::final_at(
+                "system",
+                i as f64 + 0.5,
+                "system",
+            ));
+        }
+        let page = store.page(Some(&AudioSource::mic()), 3, 10);
+        assert_eq!(page.total, 5);
+        assert_eq!(page.offset, 3);
+        assert_eq!(texts(&page.segments), ["mic 3", "mic 4"]);
+        assert_eq!(store.page(None, 2, 2).segments.len(), 2);
+
+        store.reset(None);
+        assert_eq!(store.page(None, 0, 10).total, 0);
+    }
+
+    #[test]
+    fn test_edits_keep_original_text_and_undo() {
+        let store = TranscriptStore::new();
+        let first = store.push(TranscriptSegment::final_at("mic", 1.0, "helo wrld how"));
+        let id = first.utterance_id.unwrap();
+        let edit = |edit| store.edit(edit, "ana").unwrap();
+
+        edit(TranscriptEdit::Edit {
+            id,
+            text: "hello world how".to_string(),
+        });
+        let change = edit(TranscriptEdit::Split {
+            id,
+            at: 12,
+            timestamp: Some(1.8),
+        });
+        assert_eq!(change.removed, [id]);
+        let second = change.segments[1].utterance_id.unwrap();
+        assert_eq!(texts(&store.finals()), ["hello world", "how"]);
+        assert_eq!(
+            store.finals()[1].original_text.as_deref(),
+            Some("helo wrld how")
+        );
+
+        edit(TranscriptEdit::Reassign {
+            id: second,
+            source: Some(AudioSource::system()),
+            speaker: Some(" Raj ".to_string()),
+        });
+        assert_eq!(store.finals()[1].speaker.as_deref(), Some("Raj"));
+        assert!(store
+            .edit(TranscriptEdit::Merge { id, other: second }, "ana")
+            .is_err());
+
+        store.undo("raj").unwrap();
+        edit(TranscriptEdit::Merge {
+            id: second,
+            other: id,
+        });
+        assert_eq!(texts(&store.finals()), ["hello world how"]);
+        assert_eq!(
+            store.finals()[0].original_text.as_deref(),
+            Some("helo wrld how")
+        );
+        assert!(store.redo("raj").is_err());
+
+        for _ in 0..3 {
+            store.undo("raj").unwrap();
+        }
+        assert_eq!(texts(&store.finals()), ["helo wrld how"]);
+        assert_eq!(store.finals()[0].original_text, None);
+        assert!(store.undo("raj").is_err());
+        store.redo("ana").unwrap();
+        assert_eq!(texts(&store.finals()), ["hello world how"]);
+
+        let history = store.history();
+        assert_eq!(history.records.len(), 9);
+        assert_eq!(history.records[3].action, EditAction::Undo);
+        assert_eq!(history.records[3].author, "raj");
+        assert!(history.can_undo && history.can_redo);
+    }
+
+    #[test]
+    fn test_undo_drops_edits_of_replaced_segments() {
+        let store = TranscriptStore::new();
+        let kept = store.push(TranscriptSegment::final_at("system", 0.5, "hi"));
+        let id = store
+            .push(TranscriptSegment::final_at("mic", 1.0, "too"))
+            .utterance_id
+            .unwrap();
+        let interim = store.push(TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("mic", 2.0, "thr")
+        });
+        assert!(store
+            .edit(
+                TranscriptEdit::Delete {
+                    id: interim.utterance_id.unwrap()
+                },
+                "ana"
+            )
+            .is_err());
+
+        store
+            .edit(
+                TranscriptEdit::Delete {
+                    id: kept.utterance_id.unwrap(),
+                },
+                "ana",
+            )
+            .unwrap();
+        store
+            .edit(
+                TranscriptEdit::Edit {
+                    id,
+                    text: "two".to_string(),
+                },
+                "ana",
+            )
+            .unwrap();
+        store.replace_range(
+            &AudioSource::mic(),
+            0.0,
+            1.5,
+            vec![TranscriptSegment::final_at("mic", 1.0, "two")],
+        );
+
+        assert!(store.undo("ana").is_err());
+        store.undo("ana").unwrap();
+        assert_eq!(texts(&store.finals()), ["hi", "two"]);
+        assert!(!store.history().can_undo);
+    }
+
+    #[test]
+    fn test_records_segments_and_edits_in_order() {
+        use crate::storage::session_store::{SessionRecorder, SessionStore};
+        use crate::transcription::types::{Session, SourceConfig};
+        use std::sync::Arc;
+
+        let db = Arc::new(SessionStore::open_in_memory().unwrap());
+        let sources = SourceConfig::default_sources(None, None, Vec::new());
+        let session = Session::new(Utc::now(), "en", "deepgram", "nova-2", &sources);
+        let id = db
+            .create_session(&session, &sources, &serde_json::Value::Null)
+            .unwrap();
+        let writer = SessionWriter::spawn(Some(SessionRecorder::new(db.clone(), id)), None);
+        let store = TranscriptStore::new();
+        store.reset(Some(writer.clone()));
+
+        let first = store.push(TranscriptSegment::final_at("mic", 1.0, "helo"));
+        store.push(TranscriptSegment {
+            is_final: false,
+            ..TranscriptSegment::final_at("mic", 2.0, "wor")
+        });
+        let edit = TranscriptEdit::Edit {
+            id: first.utterance_id.unwrap(),
+            text: "hello".to_string(),
+        };
+        store.edit(edit, "Ana").unwrap();
+        store.push(TranscriptSegment::final_at("mic", 2.0, "world"));
+        store.undo("Ana").unwrap();
+        writer.flush();
+
+        let stored = db.session(id).unwrap();
+        assert_eq!(texts(&stored.segments), ["helo", "world"]);
+        let actions: Vec<_> = db
+            .edit_history(id)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.action)
+            .collect();
+        assert_eq!(actions, [EditAction::Edit, EditAction::Undo]);
+    }
+}
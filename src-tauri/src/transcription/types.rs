@@ -1,10 +1,219 @@
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
+/// ID of a capture source. Serialized as a plain string; `mic` and `system`
+/// are the IDs of the default sources.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AudioSource(pub String);
+
+impl AudioSource {
+    pub const MIC: &'static str = "mic";
+    pub const SYSTEM: &'static str = "system";
+
+    pub fn mic() -> Self {
+        Self(Self::MIC.to_string())
+    }
+
+    pub fn system() -> Self {
+        Self(Self::SYSTEM.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AudioSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for AudioSource {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+/// What a capture source records.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
-pub enum AudioSource {
-    Mic,
+pub enum SourceKind {
+    /// An input device such as a microphone.
+    Input,
+    /// System output audio, from a device or selected applications.
     System,
+    /// Raw PCM written by another program, see `ExternalInput`.
+    External,
+}
+
+/// Where an external source reads PCM from. Each writer sends a header line
+/// such as `rate=48000 channels=2 format=s16le` before its samples.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "transport", rename_all = "lowercase")]
+pub enum ExternalInput {
+    /// A Unix domain socket, created on start; one writer at a time.
+    Socket { path: String },
+    /// A named pipe, created if it does not exist.
+    Fifo { path: String },
+}
+
+/// How a source turns a multi-channel capture into the mono audio it
+/// transcribes. Channels are counted from 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum ChannelMap {
+    /// Average all channels.
+    #[default]
+    Mix,
+    /// Use a single channel, e.g. one input of an audio interface.
+    Select { channel: u16 },
+    /// Average a subset of the channels.
+    Average { channels: Vec<u16> },
+    /// Transcribe channels as separate sources sharing one capture.
+    Split { outputs: Vec<ChannelOutput> },
+}
+
+/// A source split off one channel of a multi-channel capture.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelOutput {
+    pub channel: u16,
+    pub id: AudioSource,
+    pub label: String,
+}
+
+/// Capture format requested for an input source. Unset fields keep the
+/// host's default device and the device's default config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureFormat {
+    /// cpal host to capture through, e.g. `ALSA` or `JACK` on Linux.
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+    /// Frames per capture callback; smaller buffers cut latency.
+    #[serde(default)]
+    pub buffer_size: Option<u32>,
+}
+
+/// One supported capture configuration range of a device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupportedCaptureConfig {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    /// Buffer size range in frames, when the host reports one.
+    pub min_buffer_size: Option<u32>,
+    pub max_buffer_size: Option<u32>,
+    /// Sample format, e.g. `f32` or `i16`.
+    pub sample_format: String,
+}
+
+/// What an input device can capture, as returned by `get_device_capabilities`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCapabilities {
+    pub host: String,
+    /// Carries the device's default sample rate and channel count.
+    pub device: AudioDeviceInfo,
+    pub configs: Vec<SupportedCaptureConfig>,
+}
+
+/// One capture source of a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceConfig {
+    pub id: AudioSource,
+    /// Display name, used as the section heading in exports.
+    pub label: String,
+    pub kind: SourceKind,
+    /// Device to capture from; the default device when unset.
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// For system sources, capture only these applications.
+    #[serde(default)]
+    pub app_ids: Vec<String>,
+    /// Overrides the session's transcription language.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Overrides the provider's default model.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Seconds without captured audio before capture is restarted.
+    #[serde(default)]
+    pub stall_timeout_secs: Option<u64>,
+    /// Seconds of recent audio kept for replay and re-transcription; 0
+    /// disables it.
+    #[serde(default)]
+    pub rewind_secs: Option<u64>,
+    /// Which captured channels are transcribed, applied before resampling.
+    #[serde(default)]
+    pub channel_map: ChannelMap,
+    /// Host, sample rate and buffer size for input sources.
+    #[serde(default)]
+    pub capture_format: CaptureFormat,
+    /// For external sources, the socket or FIFO to read.
+    #[serde(default)]
+    pub external: Option<ExternalInput>,
+}
+
+impl SourceConfig {
+    /// The default mic and system sources.
+    pub fn default_sources(
+        mic_device_id: Option<String>,
+        system_device_id: Option<String>,
+        system_app_ids: Vec<String>,
+    ) -> Vec<Self> {
+        vec![
+            Self {
+                id: AudioSource::mic(),
+                label: "You".to_string(),
+                kind: SourceKind::Input,
+                device_id: mic_device_id,
+                app_ids: Vec::new(),
+                language: None,
+                model: None,
+                stall_timeout_secs: None,
+                rewind_secs: None,
+                channel_map: ChannelMap::Mix,
+                capture_format: CaptureFormat::default(),
+                external: None,
+            },
+            Self {
+                id: AudioSource::system(),
+                label: "System Audio".to_string(),
+                kind: SourceKind::System,
+                device_id: system_device_id,
+                app_ids: system_app_ids,
+                language: None,
+                model: None,
+                stall_timeout_secs: None,
+                rewind_secs: None,
+                channel_map: ChannelMap::Mix,
+                capture_format: CaptureFormat::default(),
+                external: None,
+            },
+        ]
+    }
+
+    /// The sources fed by this source's capture: itself, or one per output
+    /// of a split channel map, each selecting its channel.
+    pub fn routed_sources(&self) -> Vec<SourceConfig> {
+        match &self.channel_map {
+            ChannelMap::Split { outputs } => outputs
+                .iter()
+                .map(|output| SourceConfig {
+                    id: output.id.clone(),
+                    label: output.label.clone(),
+                    channel_map: ChannelMap::Select {
+                        channel: output.channel,
+                    },
+                    ..self.clone()
+                })
+                .collect(),
+            _ => vec![self.clone()],
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +223,42 @@ pub struct TranscriptSegment {
     pub timestamp: f64,
     pub confidence: f64,
     pub source: AudioSource,
+    /// Shared by an utterance's interim results and its final segment.
+    /// Unset for segments loaded from the history.
+    #[serde(default)]
+    pub utterance_id: Option<u64>,
+    /// Who spoke, if set by the user; otherwise the source's label.
+    #[serde(default)]
+    pub speaker: Option<String>,
+    /// The recognizer's text, kept once the segment was edited.
+    #[serde(default)]
+    pub original_text: Option<String>,
+}
+
+#[cfg(test)]
+impl TranscriptSegment {
+    /// A final segment of `source` with nothing else set, for tests.
+    pub fn final_at(source: &str, timestamp: f64, text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            is_final: true,
+            timestamp,
+            confidence: 0.9,
+            source: AudioSource::from(source),
+            utterance_id: None,
+            speaker: None,
+            original_text: None,
+        }
+    }
+}
+
+/// A slice of the current transcript, see `get_transcript`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptPage {
+    pub segments: Vec<TranscriptSegment>,
+    pub offset: usize,
+    /// Segments matching the query across all pages.
+    pub total: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -33,10 +278,39 @@ pub struct ConnectionStatusEvent {
     pub error: Option<String>,
 }
 
+/// State of a source's capture, as seen by its stall watchdog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureStatus {
+    Running,
+    /// No audio arrived within the stall timeout.
+    Stalled,
+    /// Capture is being restarted.
+    Recovering,
+    /// Restarts were exhausted; the source has ended.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureStatusEvent {
+    pub source: AudioSource,
+    pub status: CaptureStatus,
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioLevelEvent {
     pub source: AudioSource,
+    /// Linear RMS in 0.0..=1.0.
     pub level: f32,
+    pub rms_db: f32,
+    pub peak_db: f32,
+    /// RMS with VU-style decay.
+    pub vu_db: f32,
+    /// Clipped samples since the previous event.
+    pub clipped: u32,
+    /// Coarse band levels in dBFS, only for subscribers that asked for them.
+    pub spectrum: Option<Vec<f32>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +318,422 @@ pub struct AudioDeviceInfo {
     pub name: String,
     pub id: String,
     pub is_default: bool,
+    /// Native sample rate, when the backend reports one.
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+    /// Native channel count, when the backend reports one.
+    #[serde(default)]
+    pub channels: Option<u16>,
+}
+
+/// An application currently playing audio that can be captured on its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioApplicationInfo {
+    pub id: String,
+    pub name: String,
+    pub media_name: Option<String>,
+    pub process_id: Option<u32>,
+    pub binary: Option<String>,
+}
+
+/// Emitted by the device monitor when the set of input or output devices changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevicesChangedEvent {
+    /// Input devices of every available host.
+    pub inputs: Vec<AudioDeviceInfo>,
+    pub outputs: Vec<AudioDeviceInfo>,
+}
+
+/// Emitted when a capture backend opened a different device than the one requested,
+/// e.g. because the requested device has been unplugged or removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceFallbackEvent {
+    pub source: AudioSource,
+    pub requested_device_id: String,
+    pub device: AudioDeviceInfo,
+}
+
+/// A span of session time (in seconds) during which one source was paused.
+/// `end` is `None` while the pause is still ongoing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PauseInterval {
+    pub source: AudioSource,
+    pub start: f64,
+    pub end: Option<f64>,
+}
+
+/// Progress of a file transcription, emitted as `file-progress`. The final
+/// event has `done` set, and `error` if decoding failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileProgressEvent {
+    pub path: String,
+    /// Seconds of audio decoded and sent so far.
+    pub processed: f64,
+    pub duration: Option<f64>,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+/// Counters and queue levels of one source's audio path. Queue fills are
+/// fractions of capacity; peaks and latencies cover the whole session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStatsSnapshot {
+    pub source: AudioSource,
+    pub chunks_captured: u64,
+    /// Chunks lost because the pipeline fell behind the capture callback.
+    pub chunks_dropped: u64,
+    /// 16 kHz frames sent to the provider.
+    pub frames_sent: u64,
+    /// 16 kHz frames lost between the pipeline and the provider.
+    pub frames_dropped: u64,
+    pub capture_queue_fill: f32,
+    pub capture_queue_peak: f32,
+    pub pcm_queue_fill: f32,
+    pub pcm_queue_peak: f32,
+    /// Capture-to-send latency of the most recent audio.
+    pub latency_ms: Option<f64>,
+    pub avg_latency_ms: Option<f64>,
+    pub max_latency_ms: Option<f64>,
+}
+
+/// Emitted periodically as `pipeline-health` while a session runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineHealthEvent {
+    pub sources: Vec<PipelineStatsSnapshot>,
+}
+
+/// What to start live transcription with beyond the saved settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StartParams {
+    /// Provider whose stored API key to use; Deepgram by default.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Applications to capture instead of the whole system output, when no
+    /// sources are configured.
+    #[serde(default)]
+    pub system_app_ids: Vec<String>,
+}
+
+/// A range of one source's recent audio to transcribe again, optionally
+/// with a different language or model than the session used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetranscribeRequest {
+    pub source: AudioSource,
+    pub start: f64,
+    pub end: f64,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// A change to the transcript by the user. Segments are addressed by
+/// utterance ID and must be final.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum TranscriptEdit {
+    /// Correct a segment's text.
+    Edit {
+        id: u64,
+        text: String,
+    },
+    /// Split a segment before character `at`. The second part starts at
+    /// `timestamp`, or at the same time as the first.
+    Split {
+        id: u64,
+        at: usize,
+        #[serde(default)]
+        timestamp: Option<f64>,
+    },
+    /// Join two segments from the same source, in timeline order.
+    Merge {
+        id: u64,
+        other: u64,
+    },
+    Delete {
+        id: u64,
+    },
+    /// Move a segment to another source, name who spoke it, or both. An
+    /// empty speaker resets it to the source's label.
+    Reassign {
+        id: u64,
+        #[serde(default)]
+        source: Option<AudioSource>,
+        #[serde(default)]
+        speaker: Option<String>,
+    },
+}
+
+/// What an edit, undo or redo did to the transcript; emitted as
+/// `transcript-edited`. Segments with the `removed` utterance IDs are gone
+/// and `segments` were added in their place.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranscriptChange {
+    pub removed: Vec<u64>,
+    pub segments: Vec<TranscriptSegment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditAction {
+    Edit,
+    Undo,
+    Redo,
+}
+
+/// One entry of a transcript's audit trail.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditRecord {
+    pub action: EditAction,
+    pub edit: TranscriptEdit,
+    pub author: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Every edit, undo and redo of the current transcript, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditHistory {
+    pub records: Vec<EditRecord>,
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+/// Emitted as `segments-replaced` when a range was transcribed again. Final
+/// segments of `source` between `start` and `end` are superseded by
+/// `segments`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentsReplacedEvent {
+    pub source: AudioSource,
+    pub start: f64,
+    pub end: f64,
+    pub segments: Vec<TranscriptSegment>,
+}
+
+/// Emitted when a source is paused or resumed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourcePausedEvent {
+    pub source: AudioSource,
+    pub paused: bool,
+    pub timestamp: f64,
+}
+
+/// A recorded session, as listed in the session history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub id: i64,
+    pub title: String,
+    pub started_at: DateTime<Utc>,
+    /// Unset while the session runs, or if the app quit during it.
+    pub ended_at: Option<DateTime<Utc>>,
+    pub language: String,
+    pub segment_count: u32,
+}
+
+/// The capture device behind one of a session's sources.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionDevice {
+    pub source: AudioSource,
+    pub label: String,
+    pub kind: SourceKind,
+    /// Device ID, or the socket or FIFO of an external source; unset for
+    /// the default device.
+    pub device: Option<String>,
+    /// Provider model the source was transcribed with; empty if unknown.
+    #[serde(default)]
+    pub model: String,
+}
+
+/// A transcription session: when it ran, how it was captured and what the
+/// user noted about it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// ID in the session history; unset if the session is not recorded there.
+    pub id: Option<i64>,
+    pub title: String,
+    pub started_at: DateTime<Utc>,
+    /// Unset while the session runs, or if the app quit during it.
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Seconds from start to end, once ended.
+    pub duration: Option<f64>,
+    pub language: String,
+    pub provider: String,
+    /// Provider model, unless a source overrides it; empty if unknown.
+    pub model: String,
+    pub devices: Vec<SessionDevice>,
+    pub participants: Vec<String>,
+    pub tags: Vec<String>,
+    pub notes: String,
+}
+
+impl Session {
+    /// A session starting at `started_at`, titled by its local start time
+    /// until renamed.
+    pub fn new(
+        started_at: DateTime<Utc>,
+        language: &str,
+        provider: &str,
+        model: &str,
+        sources: &[SourceConfig],
+    ) -> Self {
+        let devices = sources
+            .iter()
+            .map(|source| SessionDevice {
+                source: source.id.clone(),
+                label: source.label.clone(),
+                kind: source.kind,
+                device: match &source.external {
+                    Some(ExternalInput::Socket { path } | ExternalInput::Fifo { path }) => {
+                        Some(path.clone())
+                    }
+                    None => source.device_id.clone(),
+                },
+                model: source.model.clone().unwrap_or_else(|| model.to_string()),
+            })
+            .collect();
+        Self {
+            id: None,
+            title: format!(
+                "Session {}",
+                started_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            started_at,
+            ended_at: None,
+            duration: None,
+            language: language.to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            devices,
+            participants: Vec::new(),
+            tags: Vec::new(),
+            notes: String::new(),
+        }
+    }
+
+    pub fn end(&mut self, ended_at: DateTime<Utc>) {
+        self.ended_at = Some(ended_at);
+        self.duration = Some((ended_at - self.started_at).num_milliseconds() as f64 / 1000.0);
+    }
+
+    /// Apply the user's edits. Names and tags are trimmed, blank ones
+    /// dropped, and repeated tags kept once.
+    pub fn apply(&mut self, update: &SessionUpdate) -> Result<(), String> {
+        if let Some(title) = &update.title {
+            let title = title.trim();
+            if title.is_empty() {
+                return Err("Session title cannot be empty".to_string());
+            }
+            self.title = title.to_string();
+        }
+        let cleaned = |values: &[String]| {
+            let mut cleaned: Vec<String> = Vec::new();
+            for value in values.iter().map(|v| v.trim()).filter(|v| !v.is_empty()) {
+                if !cleaned.iter().any(|c| c.eq_ignore_ascii_case(value)) {
+                    cleaned.push(value.to_string());
+                }
+            }
+            cleaned
+        };
+        if let Some(participants) = &update.participants {
+            self.participants = cleaned(participants);
+        }
+        if let Some(tags) = &update.tags {
+            self.tags = cleaned(tags);
+        }
+        if let Some(notes) = &update.notes {
+            self.notes = notes.clone();
+        }
+        Ok(())
+    }
+}
+
+/// Edits to a session's metadata; unset fields are left as they are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionUpdate {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub participants: Option<Vec<String>>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// A recorded session with everything needed to show or export it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSession {
+    #[serde(flatten)]
+    pub session: Session,
+    pub sources: Vec<SourceConfig>,
+    /// App settings when the session started, without credentials.
+    pub settings: serde_json::Value,
+    /// Final segments in timeline order.
+    pub segments: Vec<TranscriptSegment>,
+    pub pauses: Vec<PauseInterval>,
+}
+
+/// Final segments of a session that did not end cleanly, replayed from its
+/// journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveredJournal {
+    /// Name of the journal, for discarding it.
+    pub id: String,
+    pub session: Session,
+    pub sources: Vec<SourceConfig>,
+    /// Final segments in timeline order.
+    pub segments: Vec<TranscriptSegment>,
+}
+
+/// Whether the app data is encrypted at rest, and if it still has to be
+/// unlocked with the passphrase.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub locked: bool,
+}
+
+/// A full-text search over recorded sessions. Unset filters match anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchQuery {
+    /// Words to find; every word must occur in a segment.
+    pub text: String,
+    #[serde(default)]
+    pub session_id: Option<i64>,
+    #[serde(default)]
+    pub source: Option<AudioSource>,
+    /// Source label, e.g. "You"; matched case-insensitively.
+    #[serde(default)]
+    pub speaker: Option<String>,
+    /// Session time range, in seconds.
+    #[serde(default)]
+    pub start: Option<f64>,
+    #[serde(default)]
+    pub end: Option<f64>,
+    /// Only sessions started in this range.
+    #[serde(default)]
+    pub started_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub started_before: Option<DateTime<Utc>>,
+    /// Maximum hits to return; 50 by default.
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+/// A segment matching a search, best matches first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub session_id: i64,
+    pub session_title: String,
+    pub session_started_at: DateTime<Utc>,
+    pub source: AudioSource,
+    /// Label of the source in that session.
+    pub speaker: String,
+    pub timestamp: f64,
+    pub text: String,
+    /// Excerpt around the matches, which are wrapped in `**`.
+    pub snippet: String,
+    /// BM25 score; lower is a better match.
+    pub rank: f64,
 }
 
 /// Raw Deepgram WebSocket response
@@ -185,4 +875,42 @@ mod tests {
         let resp: DeepgramResponse = serde_json::from_str(json).unwrap();
         assert!(resp.extract_transcript().is_none());
     }
+
+    #[test]
+    fn test_session_metadata() {
+        let mut sources = SourceConfig::default_sources(Some("usb".to_string()), None, Vec::new());
+        sources[1].external = Some(ExternalInput::Fifo {
+            path: "/tmp/feed".to_string(),
+        });
+        let started_at = Utc::now();
+        let mut session = Session::new(started_at, "en", "deepgram", "nova-2", &sources);
+        assert!(session.title.starts_with("Session "));
+        assert_eq!(session.devices[0].device.as_deref(), Some("usb"));
+        assert_eq!(session.devices[1].device.as_deref(), Some("/tmp/feed"));
+
+        let update = SessionUpdate {
+            title: Some(" Q3 pricing ".to_string()),
+            tags: Some(vec![
+                "sales".into(),
+                " ".into(),
+                "Sales".into(),
+                "q3".into(),
+            ]),
+            notes: Some("Follow up on discounts".to_string()),
+            ..Default::default()
+        };
+        session.apply(&update).unwrap();
+        assert_eq!(session.title, "Q3 pricing");
+        assert_eq!(session.tags, ["sales", "q3"]);
+        assert!(session.participants.is_empty());
+        assert!(session
+            .apply(&SessionUpdate {
+                title: Some(String::new()),
+                ..Default::default()
+            })
+            .is_err());
+
+        session.end(started_at + chrono::Duration::seconds(90));
+        assert_eq!(session.duration, Some(90.0));
+    }
 }
@@ -1,17 +1,26 @@
-import { useEffect } from "react";
+import { useEffect, useState } from "react";
 import { ControlBar } from "@/components/ControlBar";
 import { DeviceBar } from "@/components/DeviceBar";
 import { SettingsDialog } from "@/components/SettingsDialog";
 import { StatusIndicator } from "@/components/StatusIndicator";
 import { TranscriptView } from "@/components/TranscriptView";
+import { UnlockScreen } from "@/components/UnlockScreen";
 import { useAudioDevices } from "@/hooks/useAudioDevices";
 import { useSettings } from "@/hooks/useSettings";
 import { useTranscription } from "@/hooks/useTranscription";
+import { getEncryptionStatus, listCredentials } from "@/lib/tauri-commands";
+import type { AppSettings, EncryptionStatus } from "@/lib/types";
 
 function App() {
   const { settings, updateSettings, loading } = useSettings();
-  const { inputDevices, outputDevices } = useAudioDevices();
-  const transcription = useTranscription();
+  // Unknown until the backend answers; nothing else runs before then
+  const [encryption, setEncryption] = useState<EncryptionStatus | null>(null);
+
+  useEffect(() => {
+    getEncryptionStatus()
+      .then(setEncryption)
+      .catch(() => setEncryption({ enabled: false, locked: false }));
+  }, []);
 
   useEffect(() => {
     const root = document.documentElement;
@@ -25,20 +34,7 @@ function App() {
     }
   }, [settings.theme]);
 
-  const handleStart = () => {
-    if (!settings.api_key) {
-      alert("Please set your Deepgram API key in Settings first.");
-      return;
-    }
-    transcription.start({
-      apiKey: settings.api_key,
-      language: settings.language,
-      micDeviceId: settings.mic_device_id,
-      systemDeviceId: settings.system_device_id,
-    });
-  };
-
-  if (loading)
+  if (loading || !encryption)
     return (
       <div className="flex items-center justify-center h-screen bg-background text-foreground">
         <div className="flex flex-col items-center gap-3">
@@ -48,6 +44,40 @@ function App() {
       </div>
     );
 
+  if (encryption.locked)
+    return <UnlockScreen onUnlocked={() => setEncryption({ enabled: true, locked: false })} />;
+
+  return (
+    <Workspace
+      settings={settings}
+      updateSettings={updateSettings}
+      encryption={encryption}
+      onEncryptionChange={setEncryption}
+    />
+  );
+}
+
+interface WorkspaceProps {
+  settings: AppSettings;
+  updateSettings: (updates: Partial<AppSettings>) => void;
+  encryption: EncryptionStatus;
+  onEncryptionChange: (status: EncryptionStatus) => void;
+}
+
+/** The main UI, mounted once the app data is unlocked. */
+function Workspace({ settings, updateSettings, encryption, onEncryptionChange }: WorkspaceProps) {
+  const { inputDevices, outputDevices } = useAudioDevices(settings.mic_capture_format.host);
+  const transcription = useTranscription(settings.sources);
+
+  const handleStart = async () => {
+    const providers = await listCredentials().catch(() => []);
+    if (!providers.includes("deepgram")) {
+      alert("Please set your Deepgram API key in Settings first.");
+      return;
+    }
+    transcription.start({ provider: "deepgram" });
+  };
+
   return (
     <div className="flex flex-col h-screen bg-background text-foreground">
       {/* Header */}
@@ -59,7 +89,13 @@ function App() {
           </div>
           <h1 className="text-lg font-semibold tracking-tight">DualScribe</h1>
         </div>
-        <SettingsDialog settings={settings} onUpdate={updateSettings} />
+        <SettingsDialog
+          settings={settings}
+          onUpdate={updateSettings}
+          encryption={encryption}
+          onEncryptionChange={onEncryptionChange}
+          isRunning={transcription.isRunning}
+        />
       </header>
 
       {/* Device selection */}
@@ -29,7 +29,7 @@ export function ControlBar({ isRunning, onStart, onStop, segments }: Props) {
       : path.endsWith(".txt")
         ? ("text" as const)
         : ("markdown" as const);
-    const content = await exportTranscript(segments, format, true);
+    const content = await exportTranscript(format, true);
     await writeTextFile(path, content);
   };
 
@@ -1,4 +1,5 @@
 import { Settings } from "lucide-react";
+import { useEffect, useState } from "react";
 import { Button } from "@/components/ui/button";
 import {
   Dialog,
@@ -18,14 +19,32 @@ import {
 } from "@/components/ui/select";
 import { Slider } from "@/components/ui/slider";
 import { Switch } from "@/components/ui/switch";
-import type { AppSettings } from "@/lib/types";
+import {
+  deleteCredential,
+  listCredentials,
+  rekeyAppData,
+  setCredential,
+  testCredential,
+} from "@/lib/tauri-commands";
+import type { AppSettings, EncryptionStatus } from "@/lib/types";
+
+const PROVIDER = "deepgram";
 
 interface Props {
   settings: AppSettings;
   onUpdate: (updates: Partial<AppSettings>) => void;
+  encryption: EncryptionStatus;
+  onEncryptionChange: (status: EncryptionStatus) => void;
+  isRunning: boolean;
 }
 
-export function SettingsDialog({ settings, onUpdate }: Props) {
+export function SettingsDialog({
+  settings,
+  onUpdate,
+  encryption,
+  onEncryptionChange,
+  isRunning,
+}: Props) {
   return (
     <Dialog>
       <DialogTrigger asChild>
@@ -34,23 +53,12 @@ export function SettingsDialog({ settings, onUpdate }: Props) {
           Settings
         </Button>
       </DialogTrigger>
-      <DialogContent className="sm:max-w-md">
+      <DialogContent className="sm:max-w-md max-h-[90vh] overflow-y-auto">
         <DialogHeader>
           <DialogTitle>Settings</DialogTitle>
         </DialogHeader>
         <div className="space-y-5 pt-2">
-          <div className="space-y-2">
-            <Label htmlFor="api-key" className="text-sm font-medium">
-              Deepgram API Key
-            </Label>
-            <Input
-              id="api-key"
-              type="password"
-              value={settings.api_key ?? ""}
-              onChange={(e) => onUpdate({ api_key: e.target.value || null })}
-              placeholder="Enter your Deepgram API key"
-            />
-          </div>
+          <ApiKeyField />
           <div className="space-y-2">
             <Label className="text-sm font-medium">Language</Label>
             <Select value={settings.language} onValueChange={(v) => onUpdate({ language: v })}>
@@ -108,8 +116,188 @@ export function SettingsDialog({ settings, onUpdate }: Props) {
               onCheckedChange={(v) => onUpdate({ timestamps_enabled: v })}
             />
           </div>
+          <EncryptionField
+            encryption={encryption}
+            onChange={onEncryptionChange}
+            disabled={isRunning}
+          />
         </div>
       </DialogContent>
     </Dialog>
   );
 }
+
+/** The stored key is write-only: it can be replaced, tested or deleted, but
+ * is never shown. */
+function ApiKeyField() {
+  const [apiKey, setApiKey] = useState("");
+  const [stored, setStored] = useState(false);
+  const [status, setStatus] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  useEffect(() => {
+    listCredentials()
+      .then((providers) => setStored(providers.includes(PROVIDER)))
+      .catch(() => setStored(false));
+  }, []);
+
+  const run = async (action: () => Promise<void>, done: string) => {
+    setBusy(true);
+    setStatus(null);
+    try {
+      await action();
+      setStatus(done);
+    } catch (e) {
+      setStatus(String(e));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const save = () =>
+    run(async () => {
+      await setCredential(PROVIDER, apiKey);
+      setApiKey("");
+      setStored(true);
+    }, "API key saved");
+
+  const test = () => run(() => testCredential(PROVIDER, apiKey || null), "API key works");
+
+  const remove = () =>
+    run(async () => {
+      await deleteCredential(PROVIDER);
+      setStored(false);
+    }, "API key deleted");
+
+  return (
+    <div className="space-y-2">
+      <Label htmlFor="api-key" className="text-sm font-medium">
+        Deepgram API Key
+      </Label>
+      <Input
+        id="api-key"
+        type="password"
+        autoComplete="off"
+        value={apiKey}
+        onChange={(e) => setApiKey(e.target.value)}
+        placeholder={
+          stored ? "Saved; enter a new key to replace it" : "Enter your Deepgram API key"
+        }
+      />
+      <div className="flex gap-2">
+        <Button size="sm" onClick={save} disabled={busy || !apiKey.trim()}>
+          Save
+        </Button>
+        <Button
+          size="sm"
+          variant="outline"
+          onClick={test}
+          disabled={busy || (!apiKey.trim() && !stored)}
+        >
+          Test
+        </Button>
+        <Button size="sm" variant="ghost" onClick={remove} disabled={busy || !stored}>
+          Delete
+        </Button>
+      </div>
+      {status && <p className="text-xs text-muted-foreground">{status}</p>}
+    </div>
+  );
+}
+
+interface EncryptionFieldProps {
+  encryption: EncryptionStatus;
+  onChange: (status: EncryptionStatus) => void;
+  disabled: boolean;
+}
+
+/** Encrypt the session history and journals with a passphrase, change it,
+ * or decrypt them again. */
+function EncryptionField({ encryption, onChange, disabled }: EncryptionFieldProps) {
+  const [passphrase, setPassphrase] = useState("");
+  const [newPassphrase, setNewPassphrase] = useState("");
+  const [confirm, setConfirm] = useState("");
+  const [status, setStatus] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  const rekey = async (encrypt: boolean) => {
+    if (encrypt && newPassphrase !== confirm) {
+      setStatus("Passphrases do not match");
+      return;
+    }
+    setBusy(true);
+    setStatus(null);
+    try {
+      const next = await rekeyAppData(
+        encryption.enabled ? passphrase : null,
+        encrypt ? newPassphrase : null,
+      );
+      onChange(next);
+      setPassphrase("");
+      setNewPassphrase("");
+      setConfirm("");
+      setStatus(next.enabled ? "History is encrypted" : "History is no longer encrypted");
+    } catch (e) {
+      setStatus(String(e));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  return (
+    <div className="space-y-2">
+      <Label className="text-sm font-medium">Encryption</Label>
+      <p className="text-xs text-muted-foreground">
+        {encryption.enabled
+          ? "Session history is encrypted with your passphrase."
+          : "Encrypt session history with a passphrase asked for at startup."}
+      </p>
+      {encryption.enabled && (
+        <Input
+          type="password"
+          autoComplete="current-password"
+          value={passphrase}
+          onChange={(e) => setPassphrase(e.target.value)}
+          placeholder="Current passphrase"
+        />
+      )}
+      <Input
+        type="password"
+        autoComplete="new-password"
+        value={newPassphrase}
+        onChange={(e) => setNewPassphrase(e.target.value)}
+        placeholder="New passphrase"
+      />
+      <Input
+        type="password"
+        autoComplete="new-password"
+        value={confirm}
+        onChange={(e) => setConfirm(e.target.value)}
+        placeholder="Confirm new passphrase"
+      />
+      <div className="flex gap-2">
+        <Button
+          size="sm"
+          onClick={() => rekey(true)}
+          disabled={disabled || busy || !newPassphrase || (encryption.enabled && !passphrase)}
+        >
+          {encryption.enabled ? "Change passphrase" : "Encrypt"}
+        </Button>
+        {encryption.enabled && (
+          <Button
+            size="sm"
+            variant="ghost"
+            onClick={() => rekey(false)}
+            disabled={disabled || busy || !passphrase}
+          >
+            Decrypt
+          </Button>
+        )}
+      </div>
+      {disabled && (
+        <p className="text-xs text-muted-foreground">Stop transcription to change encryption.</p>
+      )}
+      {status && <p className="text-xs text-muted-foreground">{status}</p>}
+    </div>
+  );
+}
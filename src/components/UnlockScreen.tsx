@@ -0,0 +1,53 @@
+import { type FormEvent, useState } from "react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+import { unlockAppData } from "@/lib/tauri-commands";
+
+interface UnlockScreenProps {
+  onUnlocked: () => void;
+}
+
+export function UnlockScreen({ onUnlocked }: UnlockScreenProps) {
+  const [passphrase, setPassphrase] = useState("");
+  const [error, setError] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  const handleSubmit = async (e: FormEvent) => {
+    e.preventDefault();
+    setBusy(true);
+    setError(null);
+    try {
+      await unlockAppData(passphrase);
+      onUnlocked();
+    } catch (err) {
+      setError(String(err));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  return (
+    <div className="flex items-center justify-center h-screen bg-background text-foreground">
+      <form onSubmit={handleSubmit} className="w-80 space-y-4">
+        <div className="space-y-2">
+          <Label htmlFor="passphrase" className="text-sm font-medium">
+            Passphrase
+          </Label>
+          <Input
+            id="passphrase"
+            type="password"
+            autoFocus
+            value={passphrase}
+            onChange={(e) => setPassphrase(e.target.value)}
+            placeholder="Unlock your encrypted transcripts"
+          />
+        </div>
+        {error && <p className="text-sm text-destructive">{error}</p>}
+        <Button type="submit" className="w-full" disabled={busy || !passphrase}>
+          {busy ? "Unlocking..." : "Unlock"}
+        </Button>
+      </form>
+    </div>
+  );
+}
@@ -1,20 +1,33 @@
+import { listen } from "@tauri-apps/api/event";
 import { useCallback, useEffect, useState } from "react";
 import { listInputDevices, listOutputDevices } from "@/lib/tauri-commands";
 import type { AudioDeviceInfo } from "@/lib/types";
 
-export function useAudioDevices() {
+/** Input devices are listed from `inputHost`, or the platform default host. */
+export function useAudioDevices(inputHost?: string | null) {
   const [inputDevices, setInputDevices] = useState<AudioDeviceInfo[]>([]);
   const [outputDevices, setOutputDevices] = useState<AudioDeviceInfo[]>([]);
 
   const refresh = useCallback(async () => {
-    const [inputs, outputs] = await Promise.all([listInputDevices(), listOutputDevices()]);
+    const [inputs, outputs] = await Promise.all([
+      listInputDevices(inputHost),
+      listOutputDevices(),
+    ]);
     setInputDevices(inputs);
     setOutputDevices(outputs);
-  }, []);
+  }, [inputHost]);
 
   useEffect(() => {
     refresh();
   }, [refresh]);
 
+  // Re-list from the selected host whenever the backend sees a device come or go
+  useEffect(() => {
+    const unlisten = listen("devices-changed", () => void refresh());
+    return () => {
+      unlisten.then((u) => u());
+    };
+  }, [refresh]);
+
   return { inputDevices, outputDevices, refresh };
 }
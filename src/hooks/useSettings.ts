@@ -1,42 +1,60 @@
-import { Store } from "@tauri-apps/plugin-store";
+import { listen } from "@tauri-apps/api/event";
 import { useCallback, useEffect, useState } from "react";
+import { getSettings, saveSettings } from "@/lib/tauri-commands";
 import type { AppSettings } from "@/lib/types";
 
-const STORE_FILE = "settings.json";
 const DEFAULT_SETTINGS: AppSettings = {
-  api_key: null,
   language: "en",
   mic_device_id: null,
   system_device_id: null,
   font_size: 14,
   theme: "system",
   timestamps_enabled: true,
+  mic_capture_format: {},
+  sources: null,
 };
 
+/** Settings are kept by the backend; this mirrors them and follows
+ * `settings-changed`. */
 export function useSettings() {
   const [settings, setSettings] = useState<AppSettings>(DEFAULT_SETTINGS);
-  const [store, setStore] = useState<Store | null>(null);
   const [loading, setLoading] = useState(true);
 
   useEffect(() => {
-    Store.load(STORE_FILE).then(async (s) => {
-      setStore(s);
-      const saved = await s.get<AppSettings>("settings");
-      if (saved) setSettings({ ...DEFAULT_SETTINGS, ...saved });
-      setLoading(false);
+    let cancelled = false;
+    let unlisten: (() => void) | undefined;
+
+    getSettings()
+      .then((saved) => {
+        if (!cancelled) setSettings(saved);
+      })
+      .catch((e) => console.error("Failed to load settings:", e))
+      .finally(() => {
+        if (!cancelled) setLoading(false);
+      });
+    listen<AppSettings>("settings-changed", (e) => setSettings(e.payload)).then((u) => {
+      if (cancelled) u();
+      else unlisten = u;
     });
+
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
   }, []);
 
   const updateSettings = useCallback(
     async (updates: Partial<AppSettings>) => {
-      const newSettings = { ...settings, ...updates };
-      setSettings(newSettings);
-      if (store) {
-        await store.set("settings", newSettings);
-        await store.save();
+      // Shown right away, and rolled back if the backend rejects it
+      setSettings({ ...settings, ...updates });
+      try {
+        setSettings(await saveSettings({ ...settings, ...updates }));
+      } catch (e) {
+        console.error("Failed to save settings:", e);
+        setSettings(settings);
       }
     },
-    [settings, store],
+    [settings],
   );
 
   return { settings, updateSettings, loading };
@@ -1,16 +1,27 @@
 import { listen } from "@tauri-apps/api/event";
-import { invoke } from "@tauri-apps/api/core";
 import { useCallback, useEffect, useReducer } from "react";
-import { startTranscription, stopTranscription } from "@/lib/tauri-commands";
+import {
+  startTranscription,
+  stopTranscription,
+  subscribeAudioLevels,
+} from "@/lib/tauri-commands";
 import type {
   AudioLevelEvent,
+  AudioSource,
+  CaptureStatusEvent,
   ConnectionStatus,
   ConnectionStatusEvent,
+  SegmentsReplacedEvent,
+  SourceConfig,
+  StartParams,
+  TranscriptChange,
   TranscriptSegment,
 } from "@/lib/types";
 
 interface TranscriptionState {
   isRunning: boolean;
+  /** Sources shown in the mic panel; every other source is shown as system audio. */
+  micSources: AudioSource[];
   micSegments: TranscriptSegment[];
   systemSegments: TranscriptSegment[];
   micInterim: string | null;
@@ -24,16 +35,19 @@ interface TranscriptionState {
 }
 
 type Action =
-  | { type: "START" }
+  | { type: "START"; micSources: AudioSource[] }
   | { type: "STOP" }
-  | { type: "MIC_TRANSCRIPT"; segment: TranscriptSegment }
-  | { type: "SYSTEM_TRANSCRIPT"; segment: TranscriptSegment }
+  | { type: "TRANSCRIPT"; segment: TranscriptSegment }
   | { type: "CONNECTION_STATUS"; event: ConnectionStatusEvent }
+  | { type: "CAPTURE_STATUS"; event: CaptureStatusEvent }
   | { type: "AUDIO_LEVEL"; event: AudioLevelEvent }
+  | { type: "REPLACE_SEGMENTS"; event: SegmentsReplacedEvent }
+  | { type: "TRANSCRIPT_EDITED"; change: TranscriptChange }
   | { type: "ERROR"; error: string };
 
 const initialState: TranscriptionState = {
   isRunning: false,
+  micSources: ["mic"],
   micSegments: [],
   systemSegments: [],
   micInterim: null,
@@ -46,10 +60,50 @@ const initialState: TranscriptionState = {
   startTime: null,
 };
 
+/** Swap the final segments in `[start, end)` for re-transcribed ones. */
+function replaceRange(
+  segments: TranscriptSegment[],
+  { start, end, segments: replacements }: SegmentsReplacedEvent,
+): TranscriptSegment[] {
+  return [
+    ...segments.filter((s) => s.timestamp < start || s.timestamp >= end),
+    ...replacements,
+  ].sort((a, b) => a.timestamp - b.timestamp);
+}
+
+/** Drop the segments an edit removed and add the ones it made for one panel. */
+function applyChange(
+  segments: TranscriptSegment[],
+  { removed, segments: added }: TranscriptChange,
+  inPanel: (source: AudioSource) => boolean,
+): TranscriptSegment[] {
+  return [
+    ...segments.filter((s) => s.utterance_id == null || !removed.includes(s.utterance_id)),
+    ...added.filter((s) => inPanel(s.source)),
+  ].sort((a, b) => a.timestamp - b.timestamp);
+}
+
+/** The IDs of the input sources in `sources`, or of the default mic source
+ * when none are configured. */
+function inputSourceIds(sources: SourceConfig[] | null | undefined): AudioSource[] {
+  if (!sources) return ["mic"];
+  return sources
+    .filter((s) => s.kind === "input")
+    .flatMap((s) =>
+      s.channel_map?.mode === "split" ? s.channel_map.outputs.map((o) => o.id) : [s.id],
+    );
+}
+
 function reducer(state: TranscriptionState, action: Action): TranscriptionState {
+  const isMic = (source: AudioSource) => state.micSources.includes(source);
   switch (action.type) {
     case "START":
-      return { ...initialState, isRunning: true, startTime: Date.now() };
+      return {
+        ...initialState,
+        isRunning: true,
+        micSources: action.micSources,
+        startTime: Date.now(),
+      };
     case "STOP":
       return {
         ...state,
@@ -61,15 +115,16 @@ function reducer(state: TranscriptionState, action: Action): TranscriptionState
         micLevel: 0,
         systemLevel: 0,
       };
-    case "MIC_TRANSCRIPT":
-      if (action.segment.is_final)
-        return {
-          ...state,
-          micSegments: [...state.micSegments, action.segment],
-          micInterim: null,
-        };
-      return { ...state, micInterim: action.segment.text };
-    case "SYSTEM_TRANSCRIPT":
+    case "TRANSCRIPT":
+      if (isMic(action.segment.source)) {
+        if (action.segment.is_final)
+          return {
+            ...state,
+            micSegments: [...state.micSegments, action.segment],
+            micInterim: null,
+          };
+        return { ...state, micInterim: action.segment.text };
+      }
       if (action.segment.is_final)
         return {
           ...state,
@@ -78,7 +133,7 @@ function reducer(state: TranscriptionState, action: Action): TranscriptionState
         };
       return { ...state, systemInterim: action.segment.text };
     case "CONNECTION_STATUS":
-      if (action.event.source === "mic")
+      if (isMic(action.event.source))
         return {
           ...state,
           micStatus: action.event.status,
@@ -89,9 +144,26 @@ function reducer(state: TranscriptionState, action: Action): TranscriptionState
         systemStatus: action.event.status,
         error: action.event.error ?? state.error,
       };
+    case "CAPTURE_STATUS":
+      // Stalls are recovered automatically; only a capture that gave up is an error
+      if (action.event.status !== "failed") return state;
+      return {
+        ...state,
+        error: `${action.event.source} capture failed: ${action.event.error ?? "no audio"}`,
+      };
     case "AUDIO_LEVEL":
-      if (action.event.source === "mic") return { ...state, micLevel: action.event.level };
+      if (isMic(action.event.source)) return { ...state, micLevel: action.event.level };
       return { ...state, systemLevel: action.event.level };
+    case "REPLACE_SEGMENTS":
+      if (isMic(action.event.source))
+        return { ...state, micSegments: replaceRange(state.micSegments, action.event) };
+      return { ...state, systemSegments: replaceRange(state.systemSegments, action.event) };
+    case "TRANSCRIPT_EDITED":
+      return {
+        ...state,
+        micSegments: applyChange(state.micSegments, action.change, isMic),
+        systemSegments: applyChange(state.systemSegments, action.change, (s) => !isMic(s)),
+      };
     case "ERROR":
       return { ...state, error: action.error, isRunning: false };
     default:
@@ -99,7 +171,9 @@ function reducer(state: TranscriptionState, action: Action): TranscriptionState
   }
 }
 
-export function useTranscription() {
+/** Live transcription of the configured `sources`, shown as two panels:
+ * input sources on the mic side and all others on the system side. */
+export function useTranscription(sources?: SourceConfig[] | null) {
   const [state, dispatch] = useReducer(reducer, initialState);
 
   useEffect(() => {
@@ -107,22 +181,30 @@ export function useTranscription() {
     const unlistens: (() => void)[] = [];
 
     const setup = async () => {
-      const u1 = await listen<TranscriptSegment>("mic-transcript", (e) =>
-        dispatch({ type: "MIC_TRANSCRIPT", segment: e.payload }),
+      const u1 = await listen<TranscriptSegment>("transcript", (e) =>
+        dispatch({ type: "TRANSCRIPT", segment: e.payload }),
       );
       unlistens.push(u1);
-      const u2 = await listen<TranscriptSegment>("system-transcript", (e) =>
-        dispatch({ type: "SYSTEM_TRANSCRIPT", segment: e.payload }),
-      );
-      unlistens.push(u2);
-      const u3 = await listen<ConnectionStatusEvent>("connection-status", (e) =>
+      const u2 = await listen<ConnectionStatusEvent>("connection-status", (e) =>
         dispatch({ type: "CONNECTION_STATUS", event: e.payload }),
       );
-      unlistens.push(u3);
-      const u4 = await listen<AudioLevelEvent>("audio-level", (e) =>
-        dispatch({ type: "AUDIO_LEVEL", event: e.payload }),
+      unlistens.push(u2);
+      const u4 = await listen<CaptureStatusEvent>("capture-status", (e) =>
+        dispatch({ type: "CAPTURE_STATUS", event: e.payload }),
       );
       unlistens.push(u4);
+      const u5 = await listen<SegmentsReplacedEvent>("segments-replaced", (e) =>
+        dispatch({ type: "REPLACE_SEGMENTS", event: e.payload }),
+      );
+      unlistens.push(u5);
+      const u6 = await listen<TranscriptChange>("transcript-edited", (e) =>
+        dispatch({ type: "TRANSCRIPT_EDITED", change: e.payload }),
+      );
+      unlistens.push(u6);
+      const u3 = await subscribeAudioLevels((event) =>
+        dispatch({ type: "AUDIO_LEVEL", event }),
+      );
+      unlistens.push(() => void u3());
 
       if (cancelled) {
         unlistens.forEach((u) => u());
@@ -136,37 +218,16 @@ export function useTranscription() {
     };
   }, []);
 
-  // Auto-save transcript every 60 seconds while running
-  useEffect(() => {
-    if (!state.isRunning) return;
-    const interval = setInterval(async () => {
-      const all = [...state.micSegments, ...state.systemSegments];
-      if (all.length > 0) {
-        try {
-          await invoke("auto_save_transcript", { segments: all });
-        } catch (e) {
-          console.error("Auto-save failed:", e);
-        }
-      }
-    }, 60_000);
-    return () => clearInterval(interval);
-  }, [state.isRunning, state.micSegments.length, state.systemSegments.length]);
-
   const start = useCallback(
-    async (params: {
-      apiKey: string;
-      language: string;
-      micDeviceId: string | null;
-      systemDeviceId: string | null;
-    }) => {
-      dispatch({ type: "START" });
+    async (params: StartParams) => {
+      dispatch({ type: "START", micSources: inputSourceIds(sources) });
       try {
         await startTranscription(params);
       } catch (e) {
         dispatch({ type: "ERROR", error: String(e) });
       }
     },
-    [],
+    [sources],
   );
 
   const stop = useCallback(async () => {
@@ -175,12 +236,6 @@ export function useTranscription() {
     } catch (e) {
       console.error("Stop failed:", e);
     }
-    // Clear autosave on intentional stop
-    try {
-      await invoke("clear_autosave");
-    } catch (e) {
-      console.error("Clear autosave failed:", e);
-    }
     dispatch({ type: "STOP" });
   }, []);
 
@@ -1,25 +1,83 @@
-import { invoke } from "@tauri-apps/api/core";
-import type { AudioDeviceInfo, TranscriptSegment } from "./types";
+import { Channel, invoke } from "@tauri-apps/api/core";
+import type {
+  AppSettings,
+  AudioApplicationInfo,
+  AudioDeviceInfo,
+  AudioLevelEvent,
+  AudioSource,
+  DeviceCapabilities,
+  EditHistory,
+  EncryptionStatus,
+  PipelineStatsSnapshot,
+  RecoveredJournal,
+  RetranscribeRequest,
+  SearchHit,
+  SearchQuery,
+  Session,
+  SessionSummary,
+  SessionUpdate,
+  StartParams,
+  StoredSession,
+  TranscriptChange,
+  TranscriptEdit,
+  TranscriptPage,
+  TranscriptSegment,
+} from "./types";
 
-export async function listInputDevices(): Promise<AudioDeviceInfo[]> {
-  return invoke<AudioDeviceInfo[]>("list_input_devices");
+/** Input devices of a cpal host, or of the platform default host. */
+export async function listInputDevices(host?: string | null): Promise<AudioDeviceInfo[]> {
+  return invoke<AudioDeviceInfo[]>("list_input_devices", { host: host ?? null });
+}
+
+export async function listAudioHosts(): Promise<string[]> {
+  return invoke<string[]>("list_audio_hosts");
+}
+
+export async function getDeviceCapabilities(
+  host?: string | null,
+  deviceId?: string | null,
+): Promise<DeviceCapabilities> {
+  return invoke<DeviceCapabilities>("get_device_capabilities", {
+    host: host ?? null,
+    deviceId: deviceId ?? null,
+  });
 }
 
 export async function listOutputDevices(): Promise<AudioDeviceInfo[]> {
   return invoke<AudioDeviceInfo[]>("list_output_devices");
 }
 
-export async function startTranscription(params: {
-  apiKey: string;
-  language: string;
-  micDeviceId: string | null;
-  systemDeviceId: string | null;
+export async function listAudioApplications(): Promise<AudioApplicationInfo[]> {
+  return invoke<AudioApplicationInfo[]>("list_audio_applications");
+}
+
+/** Receive level meters (~20 per second per source) until the returned
+ * function is called. */
+export async function subscribeAudioLevels(
+  onLevel: (event: AudioLevelEvent) => void,
+  spectrum = false,
+): Promise<() => Promise<void>> {
+  const onEvent = new Channel<AudioLevelEvent>();
+  onEvent.onmessage = onLevel;
+  const id = await invoke<number>("subscribe_audio_levels", { onEvent, spectrum });
+  return () => invoke("unsubscribe_audio_levels", { id });
+}
+
+/** Start live transcription with the language, devices and sources from the
+ * saved settings. */
+export async function startTranscription(params: StartParams): Promise<void> {
+  return invoke("start_transcription", { params });
+}
+
+export async function transcribeFile(params: {
+  provider?: string | null;
+  path: string;
+  splitChannels?: boolean;
 }): Promise<void> {
-  return invoke("start_transcription", {
-    apiKey: params.apiKey,
-    language: params.language,
-    micDeviceId: params.micDeviceId,
-    systemDeviceId: params.systemDeviceId,
+  return invoke("transcribe_file", {
+    provider: params.provider ?? null,
+    path: params.path,
+    splitChannels: params.splitChannels ?? false,
   });
 }
 
@@ -27,10 +85,184 @@ export async function stopTranscription(): Promise<void> {
   return invoke("stop_transcription");
 }
 
+export async function pauseSource(source: AudioSource): Promise<void> {
+  return invoke("pause_source", { source });
+}
+
+export async function resumeSource(source: AudioSource): Promise<void> {
+  return invoke("resume_source", { source });
+}
+
+export async function getPipelineStats(): Promise<PipelineStatsSnapshot[]> {
+  return invoke<PipelineStatsSnapshot[]>("get_pipeline_stats");
+}
+
+/** A source's recent audio between two session times, as a WAV file. */
+export async function getAudioClip(
+  source: AudioSource,
+  start: number,
+  end: number,
+): Promise<ArrayBuffer> {
+  return invoke<ArrayBuffer>("get_audio_clip", { source, start, end });
+}
+
+/** Transcribe a range of recent audio again; the new segments also arrive
+ * as a `segments-replaced` event. */
+export async function retranscribeRange(
+  request: RetranscribeRequest,
+  provider?: string | null,
+): Promise<TranscriptSegment[]> {
+  return invoke<TranscriptSegment[]>("retranscribe_range", {
+    provider: provider ?? null,
+    request,
+  });
+}
+
+/** A page of the current (or last) session's transcript, of one source or
+ * all of them. Open utterances appear as their latest interim result. */
+export async function getTranscript(params: {
+  source?: AudioSource | null;
+  offset?: number | null;
+  limit?: number | null;
+}): Promise<TranscriptPage> {
+  return invoke<TranscriptPage>("get_transcript", {
+    source: params.source ?? null,
+    offset: params.offset ?? null,
+    limit: params.limit ?? null,
+  });
+}
+
+/** Edit, split, merge, delete or reassign final segments of the current
+ * transcript. `author` defaults to the OS user. */
+export async function editTranscript(
+  edit: TranscriptEdit,
+  author?: string | null,
+): Promise<TranscriptChange> {
+  return invoke<TranscriptChange>("edit_transcript", { edit, author: author ?? null });
+}
+
+export async function undoTranscriptEdit(author?: string | null): Promise<TranscriptChange> {
+  return invoke<TranscriptChange>("undo_transcript_edit", { author: author ?? null });
+}
+
+export async function redoTranscriptEdit(author?: string | null): Promise<TranscriptChange> {
+  return invoke<TranscriptChange>("redo_transcript_edit", { author: author ?? null });
+}
+
+/** Who edited a transcript and how, oldest first: the current session's,
+ * or that of a recorded session. */
+export async function getEditHistory(sessionId?: number | null): Promise<EditHistory> {
+  return invoke<EditHistory>("get_edit_history", { sessionId: sessionId ?? null });
+}
+
+/** Export the current (or last) session's final segments. JSON exports are an
+ * object with a `version` field; version 1 was a bare array of segments. */
 export async function exportTranscript(
-  segments: TranscriptSegment[],
   format: "markdown" | "text" | "json",
   showTimestamps: boolean,
 ): Promise<string> {
-  return invoke<string>("export_transcript", { segments, format, showTimestamps });
+  return invoke<string>("export_transcript", { format, showTimestamps });
+}
+
+/** Recorded sessions, newest first. */
+export async function listSessions(): Promise<SessionSummary[]> {
+  return invoke<SessionSummary[]>("list_sessions");
+}
+
+export async function openSession(id: number): Promise<StoredSession> {
+  return invoke<StoredSession>("open_session", { id });
+}
+
+export async function renameSession(id: number, title: string): Promise<void> {
+  return invoke("rename_session", { id, title });
+}
+
+/** Edit a recorded session's title, participants, tags or notes. */
+export async function updateSession(id: number, update: SessionUpdate): Promise<Session> {
+  return invoke<Session>("update_session", { id, update });
+}
+
+/** The running session, or the last one if none is running. */
+export async function getCurrentSession(): Promise<Session | null> {
+  return invoke<Session | null>("get_current_session");
+}
+
+export async function updateCurrentSession(update: SessionUpdate): Promise<Session> {
+  return invoke<Session>("update_current_session", { update });
+}
+
+export async function deleteSession(id: number): Promise<void> {
+  return invoke("delete_session", { id });
+}
+
+export async function exportSession(
+  id: number,
+  format: "markdown" | "text" | "json",
+  showTimestamps: boolean,
+): Promise<string> {
+  return invoke<string>("export_session", { id, format, showTimestamps });
+}
+
+/** Sessions that did not end cleanly, replayed from their journals. */
+export async function checkAutosave(): Promise<RecoveredJournal[]> {
+  return invoke<RecoveredJournal[]>("check_autosave");
+}
+
+/** Discard one recovered journal, or all of them without an ID. */
+export async function clearAutosave(id?: string): Promise<void> {
+  return invoke("clear_autosave", { id: id ?? null });
+}
+
+export async function getEncryptionStatus(): Promise<EncryptionStatus> {
+  return invoke<EncryptionStatus>("get_encryption_status");
+}
+
+/** Unlock the encrypted app data; sessions cannot start until then. */
+export async function unlockAppData(passphrase: string): Promise<void> {
+  return invoke("unlock_app_data", { passphrase });
+}
+
+/** Encrypt the app data under `newPassphrase`, or decrypt it without one.
+ * `passphrase` is the current one if the data is already encrypted. */
+export async function rekeyAppData(
+  passphrase: string | null,
+  newPassphrase: string | null,
+): Promise<EncryptionStatus> {
+  return invoke<EncryptionStatus>("rekey_app_data", { passphrase, newPassphrase });
+}
+
+export async function getSettings(): Promise<AppSettings> {
+  return invoke<AppSettings>("get_settings");
+}
+
+/** Validate and persist `settings`; also announced as `settings-changed`. */
+export async function saveSettings(settings: AppSettings): Promise<AppSettings> {
+  return invoke<AppSettings>("save_settings", { settings });
+}
+
+/** Providers with a stored API key. Keys never leave the backend. */
+export async function listCredentials(): Promise<string[]> {
+  return invoke<string[]>("list_credentials");
+}
+
+export async function setCredential(provider: string, apiKey: string): Promise<void> {
+  return invoke("set_credential", { provider, apiKey });
+}
+
+/** Check `apiKey` with the provider, or the stored key if none is given. */
+export async function testCredential(provider: string, apiKey?: string | null): Promise<void> {
+  return invoke("test_credential", { provider, apiKey: apiKey ?? null });
+}
+
+export async function deleteCredential(provider: string): Promise<void> {
+  return invoke("delete_credential", { provider });
+}
+
+/** Segments from all recorded sessions matching `query`, best matches first. */
+export async function searchTranscripts(query: SearchQuery): Promise<SearchHit[]> {
+  return invoke<SearchHit[]>("search_transcripts", { query });
+}
+
+export async function rebuildSearchIndex(): Promise<void> {
+  return invoke("rebuild_search_index");
 }
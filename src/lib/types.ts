@@ -1,4 +1,70 @@
-export type AudioSource = "mic" | "system";
+/** Source ID; "mic" and "system" are the default sources. */
+export type AudioSource = string;
+
+export type SourceKind = "input" | "system" | "external";
+
+/**
+ * Socket or FIFO an external source reads raw PCM from. Each writer first
+ * sends a header line such as "rate=48000 channels=2 format=s16le".
+ */
+export type ExternalInput =
+  | { transport: "socket"; path: string }
+  | { transport: "fifo"; path: string };
+
+/** How a source turns a multi-channel capture into mono audio. Channels count from 0. */
+export type ChannelMap =
+  | { mode: "mix" }
+  | { mode: "select"; channel: number }
+  | { mode: "average"; channels: number[] }
+  | { mode: "split"; outputs: ChannelOutput[] };
+
+/** A source split off one channel of a capture. */
+export interface ChannelOutput {
+  channel: number;
+  id: AudioSource;
+  label: string;
+}
+
+/** Capture format for an input source; unset fields keep the defaults. */
+export interface CaptureFormat {
+  /** cpal host, e.g. "ALSA" or "JACK" on Linux. */
+  host?: string | null;
+  sample_rate?: number | null;
+  /** Frames per capture callback. */
+  buffer_size?: number | null;
+}
+
+export interface SupportedCaptureConfig {
+  channels: number;
+  min_sample_rate: number;
+  max_sample_rate: number;
+  min_buffer_size: number | null;
+  max_buffer_size: number | null;
+  sample_format: string;
+}
+
+export interface DeviceCapabilities {
+  host: string;
+  device: AudioDeviceInfo;
+  configs: SupportedCaptureConfig[];
+}
+
+export interface SourceConfig {
+  id: AudioSource;
+  label: string;
+  kind: SourceKind;
+  device_id?: string | null;
+  app_ids?: string[];
+  language?: string | null;
+  model?: string | null;
+  /** Seconds without captured audio before capture is restarted. */
+  stall_timeout_secs?: number | null;
+  /** Seconds of recent audio kept for replay and re-transcription; 0 disables it. */
+  rewind_secs?: number | null;
+  channel_map?: ChannelMap;
+  capture_format?: CaptureFormat;
+  external?: ExternalInput | null;
+}
 
 export interface TranscriptSegment {
   text: string;
@@ -6,6 +72,51 @@ export interface TranscriptSegment {
   timestamp: number;
   confidence: number;
   source: AudioSource;
+  /** Shared by an utterance's interim results and its final segment. Null
+   * for segments loaded from the history. */
+  utterance_id?: number | null;
+  /** Who spoke, if set by the user; otherwise the source's label. */
+  speaker?: string | null;
+  /** The recognizer's text, kept once the segment was edited. */
+  original_text?: string | null;
+}
+
+/** A change to final segments, addressed by utterance ID. */
+export type TranscriptEdit =
+  | { op: "edit"; id: number; text: string }
+  /** Split before character `at`; the second part starts at `timestamp`. */
+  | { op: "split"; id: number; at: number; timestamp?: number | null }
+  | { op: "merge"; id: number; other: number }
+  | { op: "delete"; id: number }
+  /** An empty speaker resets it to the source's label. */
+  | { op: "reassign"; id: number; source?: AudioSource | null; speaker?: string | null };
+
+/** Segments with the `removed` utterance IDs were replaced by `segments`. */
+export interface TranscriptChange {
+  removed: number[];
+  segments: TranscriptSegment[];
+}
+
+export interface EditRecord {
+  action: "edit" | "undo" | "redo";
+  edit: TranscriptEdit;
+  author: string;
+  /** RFC 3339 */
+  at: string;
+}
+
+export interface EditHistory {
+  records: EditRecord[];
+  can_undo: boolean;
+  can_redo: boolean;
+}
+
+/** A slice of the current transcript. */
+export interface TranscriptPage {
+  segments: TranscriptSegment[];
+  offset: number;
+  /** Segments matching the query across all pages. */
+  total: number;
 }
 
 export type ConnectionStatus = "disconnected" | "connecting" | "connected" | "reconnecting" | "failed";
@@ -16,23 +127,230 @@ export interface ConnectionStatusEvent {
   error: string | null;
 }
 
+export type CaptureStatus = "running" | "stalled" | "recovering" | "failed";
+
+export interface CaptureStatusEvent {
+  source: AudioSource;
+  status: CaptureStatus;
+  error: string | null;
+}
+
 export interface AudioLevelEvent {
   source: AudioSource;
   level: number;
+  rms_db: number;
+  peak_db: number;
+  vu_db: number;
+  clipped: number;
+  spectrum: number[] | null;
 }
 
 export interface AudioDeviceInfo {
   name: string;
   id: string;
   is_default: boolean;
+  sample_rate: number | null;
+  channels: number | null;
+}
+
+export interface DevicesChangedEvent {
+  /** Input devices of every available host. */
+  inputs: AudioDeviceInfo[];
+  outputs: AudioDeviceInfo[];
+}
+
+export interface DeviceFallbackEvent {
+  source: AudioSource;
+  requested_device_id: string;
+  device: AudioDeviceInfo;
+}
+
+export interface PauseInterval {
+  source: AudioSource;
+  start: number;
+  end: number | null;
+}
+
+export interface FileProgressEvent {
+  path: string;
+  processed: number;
+  duration: number | null;
+  done: boolean;
+  error: string | null;
+}
+
+/** What to start live transcription with beyond the saved settings. */
+export interface StartParams {
+  /** Provider whose stored API key to use; Deepgram by default. */
+  provider?: string | null;
+  /** Applications to capture instead of the whole system output, when no
+   * sources are configured. */
+  system_app_ids?: string[];
+}
+
+export interface RetranscribeRequest {
+  source: AudioSource;
+  start: number;
+  end: number;
+  language?: string | null;
+  model?: string | null;
+}
+
+/** Final segments of `source` between `start` and `end` are superseded by `segments`. */
+export interface SegmentsReplacedEvent {
+  source: AudioSource;
+  start: number;
+  end: number;
+  segments: TranscriptSegment[];
+}
+
+export interface SourcePausedEvent {
+  source: AudioSource;
+  paused: boolean;
+  timestamp: number;
+}
+
+/** A recorded session in the session history. Times are RFC 3339 strings. */
+export interface SessionSummary {
+  id: number;
+  title: string;
+  started_at: string;
+  /** Null while the session runs, or if the app quit during it. */
+  ended_at: string | null;
+  language: string;
+  segment_count: number;
+}
+
+/** The capture device behind one of a session's sources. */
+export interface SessionDevice {
+  source: AudioSource;
+  label: string;
+  kind: SourceKind;
+  /** Device ID, or the socket or FIFO of an external source; null for the default device. */
+  device: string | null;
+  /** Provider model the source was transcribed with; empty if unknown. */
+  model: string;
+}
+
+/** A transcription session with its metadata. Times are RFC 3339 strings. */
+export interface Session {
+  /** ID in the session history; null if the session is not recorded there. */
+  id: number | null;
+  title: string;
+  started_at: string;
+  ended_at: string | null;
+  /** Seconds from start to end, once ended. */
+  duration: number | null;
+  language: string;
+  provider: string;
+  /** Provider model, unless a source overrides it; empty if unknown. */
+  model: string;
+  devices: SessionDevice[];
+  participants: string[];
+  tags: string[];
+  notes: string;
+}
+
+/** Edits to a session's metadata; omitted fields are left as they are. */
+export interface SessionUpdate {
+  title?: string | null;
+  participants?: string[] | null;
+  tags?: string[] | null;
+  notes?: string | null;
+}
+
+export interface StoredSession extends Session {
+  id: number;
+  sources: SourceConfig[];
+  /** App settings when the session started, without credentials. */
+  settings: Record<string, unknown>;
+  segments: TranscriptSegment[];
+  pauses: PauseInterval[];
+}
+
+/** A session that did not end cleanly, replayed from its journal. */
+export interface RecoveredJournal {
+  /** Name of the journal, for discarding it. */
+  id: string;
+  session: Session;
+  sources: SourceConfig[];
+  segments: TranscriptSegment[];
+}
+
+/** Whether the app data is encrypted at rest and still has to be unlocked. */
+export interface EncryptionStatus {
+  enabled: boolean;
+  locked: boolean;
+}
+
+export interface SearchQuery {
+  /** Words to find; every word must occur in a segment. */
+  text: string;
+  session_id?: number | null;
+  source?: AudioSource | null;
+  /** Source label, e.g. "You"; matched case-insensitively. */
+  speaker?: string | null;
+  /** Session time range, in seconds. */
+  start?: number | null;
+  end?: number | null;
+  /** Only sessions started in this range, as RFC 3339 strings. */
+  started_after?: string | null;
+  started_before?: string | null;
+  /** Maximum hits to return; 50 by default. */
+  limit?: number | null;
+}
+
+export interface SearchHit {
+  session_id: number;
+  session_title: string;
+  session_started_at: string;
+  source: AudioSource;
+  /** Label of the source in that session. */
+  speaker: string;
+  timestamp: number;
+  text: string;
+  /** Excerpt around the matches, which are wrapped in `**`. */
+  snippet: string;
+  /** BM25 score; lower is a better match. */
+  rank: number;
+}
+
+export interface PipelineStatsSnapshot {
+  source: AudioSource;
+  chunks_captured: number;
+  chunks_dropped: number;
+  frames_sent: number;
+  frames_dropped: number;
+  /** Queue fill levels in 0..1. */
+  capture_queue_fill: number;
+  capture_queue_peak: number;
+  pcm_queue_fill: number;
+  pcm_queue_peak: number;
+  latency_ms: number | null;
+  avg_latency_ms: number | null;
+  max_latency_ms: number | null;
+}
+
+export interface PipelineHealthEvent {
+  sources: PipelineStatsSnapshot[];
+}
+
+export interface AudioApplicationInfo {
+  id: string;
+  name: string;
+  media_name: string | null;
+  process_id: number | null;
+  binary: string | null;
 }
 
 export interface AppSettings {
-  api_key: string | null;
   language: string;
   mic_device_id: string | null;
   system_device_id: string | null;
   font_size: number;
   theme: "light" | "dark" | "system";
   timestamps_enabled: boolean;
+  mic_capture_format: CaptureFormat;
+  /** Capture sources with their channel maps; the default mic and system sources when null. */
+  sources: SourceConfig[] | null;
 }